@@ -0,0 +1,85 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::frontmatter::{line_ending, Frontmatter};
+use crate::paths::PathResolver;
+use crate::status::{Propagation, Status};
+
+/// Markers wrapping the notice so it can be found and removed again
+const START_MARKER: &str = "<!-- drifty:outdated -->";
+const END_MARKER: &str = "<!-- /drifty:outdated -->";
+
+/// Default text of the notice added to drifted docs
+pub const DEFAULT_MESSAGE: &str =
+    "**Warning:** this page may be out of date. The code it documents has changed since it was last reviewed.";
+
+/// Whether a doc should carry the notice: any entry is DRIFTED. Transitive
+/// drift and missing files are left to `drifty check`.
+pub fn needs_notice(doc_path: &Path, fm: &Frontmatter) -> Result<bool> {
+    let resolver = PathResolver::new(doc_path)?;
+    let mut propagation = Propagation::new();
+    Ok(fm
+        .entries
+        .iter()
+        .any(|entry| propagation.check_entry(&resolver, entry).0 == Status::Drifted))
+}
+
+/// Insert the notice at `offset` (the start of the doc body), replacing any
+/// notice already present so that annotating twice changes nothing
+pub fn insert_notice(content: &str, offset: usize, message: &str) -> String {
+    let (before, after) = content.split_at(offset);
+    let after = remove_notice(after);
+    let eol = line_ending(content);
+    let quoted: Vec<String> = message.lines().map(|l| format!("> {}", l)).collect();
+    format!(
+        "{}{}{eol}{}{eol}{}{eol}{eol}{}",
+        before,
+        START_MARKER,
+        quoted.join(eol),
+        END_MARKER,
+        after
+    )
+}
+
+/// Remove a notice added by `insert_notice`, along with the blank line after it
+pub fn remove_notice(content: &str) -> String {
+    let start = match content.find(START_MARKER) {
+        Some(s) => s,
+        None => return content.to_string(),
+    };
+    let end = match content[start..].find(END_MARKER) {
+        Some(e) => start + e + END_MARKER.len(),
+        None => return content.to_string(),
+    };
+
+    let rest = &content[end..];
+    let eol = line_ending(content);
+    let rest = rest.strip_prefix(eol).unwrap_or(rest);
+    let rest = rest.strip_prefix(eol).unwrap_or(rest);
+    format!("{}{}", &content[..start], rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = "---\ndriftwatcher:\n  - \"a.rs\": 123\n---\n# Title\n\nBody\n";
+
+    #[test]
+    fn test_insert_is_idempotent() {
+        let offset = DOC.find("# Title").unwrap();
+        let once = insert_notice(DOC, offset, "Outdated");
+        let twice = insert_notice(&once, offset, "Outdated");
+        assert_eq!(once, twice);
+        assert!(once.starts_with("---\ndriftwatcher:\n  - \"a.rs\": 123\n---\n<!-- drifty"));
+        assert!(once.contains("> Outdated\n"));
+    }
+
+    #[test]
+    fn test_remove_restores_original() {
+        let offset = DOC.find("# Title").unwrap();
+        let annotated = insert_notice(DOC, offset, "Outdated");
+        assert_eq!(remove_notice(&annotated), DOC);
+        assert_eq!(remove_notice(DOC), DOC);
+    }
+}
@@ -1,31 +1,57 @@
 use anyhow::{anyhow, Result};
-use std::fs;
 use std::path::Path;
 
-use crate::frontmatter;
-use crate::paths::PathResolver;
+use crate::document::Format;
+use crate::frontmatter::Frontmatter;
+use crate::lock::ProjectLock;
+use crate::manifest::MANIFEST_FILE;
+use crate::paths::{PathResolver, CODE_CELLS_SUFFIX};
+use crate::status::Propagation;
+use crate::store::EntryStore;
+
+pub fn run(doc_file: &Path, watch_pattern: &str, code_only: bool, manifest: bool) -> Result<()> {
+    let watch_pattern = if code_only && !watch_pattern.ends_with(CODE_CELLS_SUFFIX) {
+        format!("{}{}", watch_pattern, CODE_CELLS_SUFFIX)
+    } else {
+        watch_pattern.to_string()
+    };
+    let watch_pattern = watch_pattern.as_str();
 
-pub fn run(doc_file: &Path, watch_pattern: &str) -> Result<()> {
     // Check doc file exists
     if !doc_file.exists() {
         return Err(anyhow!("Invalid file: {}", doc_file.display()));
     }
 
-    // Read content
-    let content = fs::read_to_string(doc_file)?;
+    // Read entries, keeping other drifty processes out until they are written back
+    let _lock = ProjectLock::acquire(doc_file)?;
+    let mut store = if manifest {
+        EntryStore::load_manifest(doc_file)?
+    } else {
+        EntryStore::load(doc_file)?
+    };
 
-    // Check frontmatter exists and has driftwatcher
-    let fm = frontmatter::parse(&content)?;
-    if fm.is_none() || !fm.as_ref().unwrap().has_driftwatcher() {
+    // Check frontmatter exists and has driftwatcher. Rust files need no
+    // setup: their entries are standalone doc comment directives.
+    let is_rust = Format::from_path(doc_file) == Some(Format::Rust);
+    let fm = store.frontmatter()?;
+    if !is_rust && !fm.as_ref().is_some_and(|fm| fm.has_driftwatcher()) {
         return Err(anyhow!(
-            "File not initialized. Run 'drifty init {}' first.",
-            doc_file.display()
+            "File not initialized. Run 'drifty init {}' first, or add --manifest to keep its entries in {}.",
+            doc_file.display(),
+            MANIFEST_FILE
         ));
     }
 
+    // The translation source is hashed by its body only
+    let translation_pattern = fm
+        .as_ref()
+        .filter(|fm| fm.translation_of.as_deref() == Some(watch_pattern))
+        .and_then(Frontmatter::translation_pattern);
+    let watch_pattern = translation_pattern.as_deref().unwrap_or(watch_pattern);
+
     // Check if pattern already exists
-    let fm = fm.unwrap();
-    if fm.entries.iter().any(|e| e.pattern == watch_pattern) {
+    let entries = fm.map(|fm| fm.entries).unwrap_or_default();
+    if entries.iter().any(|e| e.pattern == watch_pattern) {
         return Err(anyhow!(
             "Pattern '{}' already exists in {}",
             watch_pattern,
@@ -41,11 +67,11 @@ pub fn run(doc_file: &Path, watch_pattern: &str) -> Result<()> {
         return Err(anyhow!("Pattern '{}' matches no files", watch_pattern));
     }
 
-    let hash = resolver.hash_pattern(watch_pattern)?;
+    let hash = Propagation::new().hash_pattern(&resolver, watch_pattern)?;
 
-    // Add entry to frontmatter
-    let new_content = frontmatter::add_entry(&content, watch_pattern, &hash)?;
-    frontmatter::write_file(doc_file, &new_content)?;
+    // Add entry to frontmatter (or the manifest)
+    store.add(watch_pattern, &hash)?;
+    store.save()?;
 
     println!(
         "Added '{}' to {} ({} file(s), hash: {}...)",
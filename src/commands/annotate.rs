@@ -0,0 +1,135 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::annotate::{self, DEFAULT_MESSAGE};
+use crate::document::Format;
+use crate::frontmatter;
+use crate::lock::ProjectLock;
+use crate::scanner;
+
+pub fn run(target: Option<&Path>, remove: bool, message: Option<&str>) -> Result<()> {
+    let docs = scanner::find_documents(target)?;
+    let message = message.unwrap_or(DEFAULT_MESSAGE);
+    let mut annotated = 0;
+    let mut cleared = 0;
+
+    for doc_path in docs {
+        // The notice is Markdown (a blockquote between HTML comments)
+        if Format::from_path(&doc_path) != Some(Format::Markdown) {
+            continue;
+        }
+
+        let _lock = ProjectLock::acquire(&doc_path)?;
+        let content = match frontmatter::read_file(&doc_path) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+
+        let fm = match frontmatter::parse_document(&doc_path, &content) {
+            Ok(Some(fm)) if fm.has_driftwatcher() => fm,
+            Ok(_) => continue,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+
+        let offset = fm.body_start(&content);
+        let outdated = !remove
+            && match annotate::needs_notice(&doc_path, &fm) {
+                Ok(outdated) => outdated,
+                Err(e) => {
+                    eprintln!("Warning: {}: {}", doc_path.display(), e);
+                    continue;
+                }
+            };
+
+        let new_content = if outdated {
+            annotate::insert_notice(&content, offset, message)
+        } else {
+            format!(
+                "{}{}",
+                &content[..offset],
+                annotate::remove_notice(&content[offset..])
+            )
+        };
+
+        if new_content == content {
+            continue;
+        }
+        frontmatter::write_file(&doc_path, &new_content)?;
+        if outdated {
+            println!("Annotated {}", doc_path.display());
+            annotated += 1;
+        } else {
+            println!("Removed notice from {}", doc_path.display());
+            cleared += 1;
+        }
+    }
+
+    println!(
+        "\n{} doc(s) annotated, {} notice(s) removed.",
+        annotated, cleared
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use crate::paths::PathResolver;
+
+    #[test]
+    fn test_annotates_only_drifted_docs() {
+        let dir = tempfile::Builder::new()
+            .prefix("annotate")
+            .tempdir()
+            .unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join("a.rs"), "a").unwrap();
+        let hash = PathResolver::new(&root.join("doc.md"))
+            .unwrap()
+            .hash_pattern("a.rs")
+            .unwrap();
+        let current = format!(
+            "---\ndriftwatcher:\n  - \"a.rs\": {}\n---\n# Current\n",
+            hash
+        );
+        let missing = "---\ndriftwatcher:\n  - \"gone.rs\": old\n---\n# Missing\n";
+        let drifted = "---\ndriftwatcher:\n  - \"a.rs\": old\n---\n# Drifted\n";
+        fs::write(root.join("current.md"), &current).unwrap();
+        fs::write(root.join("missing.md"), missing).unwrap();
+        fs::write(root.join("drifted.md"), drifted).unwrap();
+
+        run(Some(root), false, Some("Stale")).unwrap();
+        let annotated = fs::read_to_string(root.join("drifted.md")).unwrap();
+        assert!(annotated.starts_with("---\ndriftwatcher:\n  - \"a.rs\": old\n---\n<!-- drifty"));
+        assert!(annotated.contains("> Stale\n"));
+        assert_eq!(
+            fs::read_to_string(root.join("current.md")).unwrap(),
+            current
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("missing.md")).unwrap(),
+            missing
+        );
+
+        // Annotating again changes nothing, and --remove restores the doc
+        run(Some(root), false, Some("Stale")).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("drifted.md")).unwrap(),
+            annotated
+        );
+        run(Some(root), true, None).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.join("drifted.md")).unwrap(),
+            drifted
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::frontmatter;
+use crate::lock::ProjectLock;
+use crate::manifest::Manifest;
+use crate::paths::{self, PathResolver};
+use crate::plan::{self, Plan, PlannedUpdate};
+use crate::status::{self, Propagation};
+
+pub fn run(plan_path: &Path) -> Result<()> {
+    let plan = plan::load(plan_path)?;
+    if plan.updates.is_empty() {
+        println!("Plan has no updates.");
+        return Ok(());
+    }
+
+    // Plan paths are relative to the project root, wherever `apply` runs
+    let root = paths::find_project_root(Path::new("."))?;
+    let count = apply(&root, &plan)?;
+    println!("Applied {} updates.", count);
+    Ok(())
+}
+
+/// Verify a plan and apply it to the project at `root`, returning how many
+/// updates were made. Nothing is written unless every update can be made.
+fn apply(root: &Path, plan: &Plan) -> Result<usize> {
+    let docs: Vec<PathBuf> = plan.updates.iter().map(|u| root.join(&u.doc)).collect();
+
+    // Hold the lock from verifying to writing
+    let _lock = ProjectLock::acquire(&root.join(".git"))?;
+
+    // Verify every update before writing anything
+    let mut propagation = Propagation::new();
+    let mut stale = Vec::new();
+    for (update, doc) in plan.updates.iter().zip(&docs) {
+        if let Err(e) = verify(doc, update, &mut propagation) {
+            stale.push(format!(
+                "  {}: {} - {}",
+                update.doc.display(),
+                update.pattern,
+                e
+            ));
+        }
+    }
+    if !stale.is_empty() {
+        return Err(anyhow!(
+            "Plan is stale, nothing was applied:\n{}\nRe-run 'drifty check --plan' to make a new plan.",
+            stale.join("\n")
+        ));
+    }
+
+    let updates: Vec<(&PathBuf, &str, String)> = plan
+        .updates
+        .iter()
+        .zip(&docs)
+        .map(|(update, doc)| (doc, update.pattern.as_str(), update.new_hash.clone()))
+        .collect();
+    write(root, &updates)?;
+
+    // Acknowledging a doc changes the state of the entries watching it, so
+    // re-hash the planned entries that watch docs once their docs are written
+    let mut propagation = Propagation::new();
+    let mut refreshed = Vec::new();
+    for (doc, pattern, hash) in updates {
+        if status::split_hash(&hash).1.is_none() {
+            continue;
+        }
+        let current = propagation.hash_pattern(&PathResolver::new(doc)?, pattern)?;
+        if current != hash {
+            refreshed.push((doc, pattern, current));
+        }
+    }
+    write(root, &refreshed)?;
+    Ok(plan.updates.len())
+}
+
+/// Store new hashes in their docs or the manifest
+fn write(root: &Path, updates: &[(&PathBuf, &str, String)]) -> Result<()> {
+    if updates.is_empty() {
+        return Ok(());
+    }
+    let mut by_doc: BTreeMap<&PathBuf, Vec<(&str, &str)>> = BTreeMap::new();
+    for (doc, pattern, hash) in updates {
+        by_doc.entry(*doc).or_default().push((pattern, hash));
+    }
+
+    // Work out every new doc and manifest before writing any of them, so a
+    // doc that can't be edited leaves the plan unapplied
+    let mut manifest = Manifest::load(root)?;
+    let mut manifest_changed = false;
+    let mut contents = Vec::new();
+    for (doc, updates) in by_doc {
+        if let Some(entries) = manifest.entries_mut(doc) {
+            for (pattern, hash) in updates {
+                let entry = entries
+                    .iter_mut()
+                    .find(|e| e.pattern == pattern)
+                    .ok_or_else(|| anyhow!("Entry not found: {}", pattern))?;
+                entry.hash = Some(hash.to_string());
+            }
+            manifest_changed = true;
+            continue;
+        }
+
+        let mut content = frontmatter::read_file(doc)?;
+        for (pattern, hash) in updates {
+            content = frontmatter::update_entry(doc, &content, pattern, hash)?;
+        }
+        contents.push((doc, content));
+    }
+
+    for (doc, content) in contents {
+        frontmatter::write_file(doc, &content)?;
+    }
+    if manifest_changed {
+        manifest.save()?;
+    }
+    Ok(())
+}
+
+/// Check that the doc still stores the old hash and the files (and the docs
+/// among them) still hash to the new one
+fn verify(doc: &Path, update: &PlannedUpdate, propagation: &mut Propagation) -> Result<()> {
+    let fm = frontmatter::parse_file(doc)?.ok_or_else(|| anyhow!("no frontmatter"))?;
+    let entry = fm
+        .entries
+        .iter()
+        .find(|e| e.pattern == update.pattern)
+        .ok_or_else(|| anyhow!("entry no longer exists"))?;
+
+    if entry.hash.as_deref() != Some(update.old_hash.as_str()) {
+        return Err(anyhow!("stored hash changed since the plan was made"));
+    }
+
+    let resolver = PathResolver::new(doc)?;
+    if propagation.hash_pattern(&resolver, &update.pattern)? != update.new_hash {
+        return Err(anyhow!("files changed since the plan was made"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    use crate::commands::check;
+
+    #[test]
+    fn test_plan_round_trip() {
+        let dir = tempfile::Builder::new().prefix("apply").tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("a.rs"), "a").unwrap();
+        fs::write(
+            root.join("docs/guide.md"),
+            "---\ndriftwatcher:\n  - \"../a.rs\": old\n---\n",
+        )
+        .unwrap();
+
+        let plan_path = root.join("plan.json");
+        check::run(Some(root), Some(&plan_path)).unwrap();
+        let plan = plan::load(&plan_path).unwrap();
+        assert_eq!(plan.updates.len(), 1);
+        assert_eq!(plan.updates[0].doc, PathBuf::from("docs/guide.md"));
+
+        assert_eq!(apply(root, &plan).unwrap(), 1);
+        let fm = frontmatter::parse_file(&root.join("docs/guide.md"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fm.entries[0].hash, Some(plan.updates[0].new_hash.clone()));
+    }
+
+    #[test]
+    fn test_rejects_stale_plan() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join("a.rs"), "a").unwrap();
+        fs::write(root.join("b.rs"), "b").unwrap();
+        let doc = "---\ndriftwatcher:\n  - \"a.rs\": old\n  - \"b.rs\": old\n---\n";
+        fs::write(root.join("doc.md"), doc).unwrap();
+
+        let resolver = PathResolver::new(&root.join("doc.md")).unwrap();
+        let update = |pattern: &str| PlannedUpdate {
+            doc: PathBuf::from("doc.md"),
+            pattern: pattern.to_string(),
+            old_hash: "old".to_string(),
+            new_hash: resolver.hash_pattern(pattern).unwrap(),
+        };
+        let plan = Plan::new(vec![update("a.rs"), update("b.rs")]);
+
+        // One of the files changed after planning: nothing is written
+        fs::write(root.join("b.rs"), "changed").unwrap();
+        let err = apply(root, &plan).unwrap_err().to_string();
+        assert!(err.contains("b.rs - files changed since the plan was made"));
+        assert_eq!(fs::read_to_string(root.join("doc.md")).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_acknowledges_doc_chain_at_once() {
+        let dir = tempfile::Builder::new().prefix("apply").tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join("lib.rs"), "a").unwrap();
+        fs::write(
+            root.join("ref.md"),
+            "---\ndriftwatcher:\n  - \"lib.rs\": old\n---\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("tut.md"),
+            "---\ndriftwatcher:\n  - \"ref.md\": old\n---\n",
+        )
+        .unwrap();
+
+        // Acknowledging ref.md in the same plan leaves tut.md current too
+        let plan_path = root.join("plan.json");
+        check::run(Some(root), Some(&plan_path)).unwrap();
+        let plan = plan::load(&plan_path).unwrap();
+        assert_eq!(apply(root, &plan).unwrap(), 2);
+
+        let mut propagation = Propagation::new();
+        let doc = root.join("tut.md");
+        let fm = frontmatter::parse_file(&doc).unwrap().unwrap();
+        let resolver = PathResolver::new(&doc).unwrap();
+        let (status, _) = propagation.check_entry(&resolver, &fm.entries[0]);
+        assert_eq!(status, status::Status::Current);
+    }
+}
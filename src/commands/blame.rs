@@ -0,0 +1,132 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::frontmatter;
+use crate::git::{self, CommitInfo};
+use crate::history;
+use crate::ignore_revs;
+use crate::paths::PathResolver;
+use crate::scanner;
+use crate::status::{check_entry, split_hash, Status};
+
+use super::report::OutputFormat;
+
+#[derive(Debug, Serialize)]
+struct BlameEntry {
+    doc: String,
+    pattern: String,
+    /// Commit whose files match the acknowledged hash
+    acknowledged: Option<String>,
+    /// First commit after the acknowledged state that changed the files
+    commit: Option<CommitInfo>,
+    /// The work tree has changes that are not committed yet
+    uncommitted: bool,
+}
+
+pub fn run(target: Option<&Path>, format: OutputFormat) -> Result<()> {
+    let docs = scanner::find_documents(target)?;
+    let mut ignored_by_root = HashMap::new();
+    let mut entries = Vec::new();
+
+    for doc_path in docs {
+        let fm = match frontmatter::parse_file(&doc_path) {
+            Ok(Some(fm)) if fm.has_driftwatcher() => fm,
+            Ok(_) => continue,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+
+        let resolver = match PathResolver::new(&doc_path) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+
+        let root: PathBuf = resolver.project_root().to_path_buf();
+        if !ignored_by_root.contains_key(&root) {
+            ignored_by_root.insert(root.clone(), ignore_revs::load(&root)?);
+        }
+        let ignored = &ignored_by_root[&root];
+
+        for entry in &fm.entries {
+            let (stored_hash, current_hash) = match (check_entry(&resolver, entry), &entry.hash) {
+                ((Status::Drifted, Some(current)), Some(stored)) => (split_hash(stored).0, current),
+                _ => continue,
+            };
+
+            let history =
+                match history::trace(&resolver, &entry.pattern, stored_hash, &current_hash) {
+                    Ok(h) => h,
+                    Err(e) => {
+                        eprintln!("Warning: {}: {}: {}", doc_path.display(), entry.pattern, e);
+                        continue;
+                    }
+                };
+
+            let commit = history
+                .first_change(|c| ignored.contains(c))
+                .map(|c| git::commit_info(&root, c))
+                .transpose()?;
+
+            entries.push(BlameEntry {
+                doc: doc_path.display().to_string(),
+                pattern: entry.pattern.clone(),
+                acknowledged: history.acknowledged,
+                commit,
+                uncommitted: history.uncommitted,
+            });
+        }
+    }
+
+    match format {
+        OutputFormat::Plaintext => print_plaintext(&entries),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&entries)?),
+    }
+
+    Ok(())
+}
+
+fn print_plaintext(entries: &[BlameEntry]) {
+    if entries.is_empty() {
+        println!("No drifted entries found.");
+        return;
+    }
+
+    let mut last_doc = None;
+    for entry in entries {
+        if last_doc != Some(&entry.doc) {
+            if last_doc.is_some() {
+                println!();
+            }
+            println!("{}", entry.doc);
+            last_doc = Some(&entry.doc);
+        }
+
+        println!("  {}", entry.pattern);
+        match (&entry.commit, &entry.acknowledged) {
+            (Some(c), _) => {
+                println!(
+                    "    {} {} <{}> {}",
+                    &c.id[..c.id.len().min(12)],
+                    c.author,
+                    c.email,
+                    c.date
+                );
+                println!("    {}", c.summary);
+            }
+            (None, Some(_)) if entry.uncommitted => println!("    uncommitted changes"),
+            (None, Some(_)) => println!("    only changed by ignored revisions"),
+            (None, None) => println!("    acknowledged hash not found in history"),
+        }
+        if entry.commit.is_some() && entry.uncommitted {
+            println!("    (plus uncommitted changes)");
+        }
+    }
+}
@@ -1,37 +1,69 @@
-use anyhow::Result;
-use dialoguer::{theme::ColorfulTheme, MultiSelect};
-use std::fs;
+use anyhow::{anyhow, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
-use crate::frontmatter::{self, WatchEntry};
+use crate::frontmatter;
+use crate::git;
+use crate::history;
+use crate::lock::ProjectLock;
 use crate::paths::PathResolver;
+use crate::plan::{self, Plan, PlannedUpdate};
 use crate::scanner;
-use crate::status::Status;
+use crate::snippets::{self, Snippet};
+use crate::snooze::{self, Snooze, SNOOZE_DAYS};
+use crate::status::{split_hash, Propagation, Status};
+use crate::store::EntryStore;
 
+/// Files listed before the list is cut short
+const MAX_LISTED_FILES: usize = 10;
+
+/// A drifted or missing entry waiting for review
 #[derive(Debug)]
-struct DriftedEntry {
+struct ReviewEntry {
     doc_path: PathBuf,
     pattern: String,
-    current_hash: String,
+    status: Status,
+    stored_hash: Option<String>,
+    /// Current hash of the files (drifted entries only)
+    current_hash: Option<String>,
+    /// Drifted docs among the watched files (transitively drifted entries only)
+    drifted_docs: Vec<PathBuf>,
+}
+
+/// What to do with a reviewed entry
+#[derive(Debug)]
+enum Action {
+    Acknowledge,
+    Skip,
+    Snooze,
+    Remove,
+    Repoint { pattern: String, hash: String },
 }
 
-pub fn run(target: Option<&Path>) -> Result<()> {
-    let docs = scanner::find_markdown_files(target)?;
-    let mut drifted: Vec<DriftedEntry> = Vec::new();
+pub fn run(target: Option<&Path>, plan_path: Option<&Path>) -> Result<()> {
+    let docs = scanner::find_documents(target)?;
+    let mut review: Vec<ReviewEntry> = Vec::new();
     let mut broken_files: Vec<(PathBuf, String)> = Vec::new();
+    let mut snoozes_by_root: HashMap<PathBuf, Vec<Snooze>> = HashMap::new();
     let mut current_count = 0;
+    let mut drifted_count = 0;
     let mut missing_count = 0;
+    let mut transitive_count = 0;
+    let mut snoozed_count = 0;
+    let mut propagation = Propagation::new();
+    let mut stale_snippets: Vec<(PathBuf, Snippet, Status)> = Vec::new();
 
     for doc_path in docs {
-        let content = match fs::read_to_string(&doc_path) {
-            Ok(c) => c,
-            Err(e) => {
-                broken_files.push((doc_path, e.to_string()));
-                continue;
+        for (snippet, status) in snippets::check_doc(&doc_path) {
+            if status != Status::Current {
+                stale_snippets.push((doc_path.clone(), snippet, status));
             }
-        };
+        }
 
-        let fm = match frontmatter::parse(&content) {
+        let fm = match frontmatter::parse_file(&doc_path) {
             Ok(Some(fm)) if fm.has_driftwatcher() => fm,
             Ok(_) => continue,
             Err(e) => {
@@ -48,129 +80,435 @@ pub fn run(target: Option<&Path>) -> Result<()> {
             }
         };
 
+        let root = resolver.project_root().to_path_buf();
+        let snoozes = snoozes_by_root
+            .entry(root.clone())
+            .or_insert_with(|| snooze::load(&root));
+        let doc_rel = resolver
+            .repo_relative(&doc_path)
+            .unwrap_or(doc_path.clone());
+
         for entry in &fm.entries {
-            match check_entry(&resolver, entry) {
-                (Status::Current, _) => current_count += 1,
-                (Status::Missing, _) => {
-                    missing_count += 1;
-                    eprintln!("MISSING: {} -> {}", doc_path.display(), entry.pattern);
+            let (status, current_hash) = propagation.check_entry(&resolver, entry);
+            match status {
+                Status::Current => {
+                    current_count += 1;
+                    continue;
                 }
-                (Status::Invalid, _) => {
+                Status::Invalid => {
                     eprintln!(
                         "INVALID: {} -> {} (no hash)",
                         doc_path.display(),
                         entry.pattern
                     );
+                    continue;
                 }
-                (Status::Drifted, Some(current_hash)) => {
-                    drifted.push(DriftedEntry {
-                        doc_path: doc_path.clone(),
-                        pattern: entry.pattern.clone(),
-                        current_hash,
-                    });
-                }
-                (Status::Drifted, None) => {
-                    // Shouldn't happen, but handle gracefully
-                    missing_count += 1;
+                Status::Drifted => drifted_count += 1,
+                Status::TransitivelyDrifted => transitive_count += 1,
+                Status::Missing => missing_count += 1,
+                // Only code snippets have this status
+                Status::SnippetDrifted => continue,
+            }
+
+            // Snoozes only apply to the interactive review
+            if let (Some(hash), None) = (&current_hash, plan_path) {
+                if snooze::is_snoozed(snoozes, &doc_rel, &entry.pattern, hash) {
+                    snoozed_count += 1;
+                    continue;
                 }
             }
+
+            review.push(ReviewEntry {
+                doc_path: doc_path.clone(),
+                pattern: entry.pattern.clone(),
+                status,
+                stored_hash: entry.hash.clone(),
+                current_hash,
+                drifted_docs: match status {
+                    Status::TransitivelyDrifted => {
+                        propagation.drifted_docs(&resolver, &entry.pattern)
+                    }
+                    _ => Vec::new(),
+                },
+            });
         }
     }
 
     // Report summary
-    println!(
+    print!(
         "\nFound {} current, {} drifted, {} missing",
-        current_count,
-        drifted.len(),
-        missing_count
+        current_count, drifted_count, missing_count
     );
+    if transitive_count > 0 {
+        print!(", {} transitively drifted", transitive_count);
+    }
+    if !stale_snippets.is_empty() {
+        print!(", {} stale code snippets", stale_snippets.len());
+    }
+    println!();
+    print_stale_snippets(&stale_snippets);
+    if snoozed_count > 0 {
+        println!("({} snoozed entries not shown)", snoozed_count);
+    }
 
-    if drifted.is_empty() {
-        if current_count > 0 {
+    if let Some(plan_path) = plan_path {
+        write_plan(plan_path, &review)?;
+    } else if review.is_empty() {
+        let problems = drifted_count + missing_count + transitive_count + stale_snippets.len();
+        if current_count > 0 && problems == 0 {
             println!("All documentation is up-to-date!");
         }
+    } else if io::stdin().is_terminal() && io::stdout().is_terminal() {
+        interactive_review(&review, &mut snoozes_by_root)?;
     } else {
-        // Present TUI for selection
-        let items: Vec<String> = drifted
-            .iter()
-            .map(|d| format!("{}: {}", d.doc_path.display(), d.pattern))
-            .collect();
-
-        println!();
-        let selections = MultiSelect::with_theme(&ColorfulTheme::default())
-            .with_prompt(
-                "Select entries to update (space to toggle, enter to confirm, 'a' for all)",
-            )
-            .items(&items)
-            .interact()?;
-
-        if selections.is_empty() {
-            println!("No entries selected.");
-        } else {
-            // Group updates by document
-            let mut updates: std::collections::HashMap<PathBuf, Vec<(&str, &str)>> =
-                std::collections::HashMap::new();
-
-            for idx in &selections {
-                let entry = &drifted[*idx];
-                updates
-                    .entry(entry.doc_path.clone())
-                    .or_default()
-                    .push((&entry.pattern, &entry.current_hash));
+        plain_review(&review);
+    }
+
+    // Report broken files at end
+    if !broken_files.is_empty() {
+        eprintln!("\nWarning: The following files had errors:");
+        for (path, err) in broken_files {
+            eprintln!("  {}: {}", path.display(), err);
+        }
+    }
+
+    Ok(())
+}
+
+/// List the code snippets that differ from their source or lost it. They are
+/// not reviewed one by one: `sync-snippets` rewrites them all.
+fn print_stale_snippets(stale: &[(PathBuf, Snippet, Status)]) {
+    if stale.is_empty() {
+        return;
+    }
+    println!("\nCode snippets out of sync with their source:");
+    for (doc_path, snippet, status) in stale {
+        println!(
+            "  {} {}:{} {}",
+            status,
+            doc_path.display(),
+            snippet.line,
+            snippet.label()
+        );
+    }
+    println!("Run 'drifty sync-snippets' to copy the current source into them.");
+}
+
+/// Whether an entry is resolved by storing its current hash
+fn is_acknowledgeable(status: Status) -> bool {
+    matches!(status, Status::Drifted | Status::TransitivelyDrifted)
+}
+
+/// Record the proposed update of every drifted entry without changing docs
+fn write_plan(path: &Path, review: &[ReviewEntry]) -> Result<()> {
+    let mut updates = Vec::new();
+    for entry in review
+        .iter()
+        .filter(|entry| is_acknowledgeable(entry.status))
+    {
+        let doc = PathResolver::new(&entry.doc_path)
+            .ok()
+            .and_then(|resolver| resolver.repo_relative(&entry.doc_path));
+        let skipped = match (doc, &entry.stored_hash, &entry.current_hash) {
+            (Some(doc), Some(old_hash), Some(new_hash)) => {
+                updates.push(PlannedUpdate {
+                    doc,
+                    pattern: entry.pattern.clone(),
+                    old_hash: old_hash.clone(),
+                    new_hash: new_hash.clone(),
+                });
+                continue;
             }
+            (None, _, _) => "the doc is outside the project root",
+            _ => "no stored or current hash",
+        };
+        eprintln!(
+            "Warning: {}: '{}' left out of the plan: {}",
+            entry.doc_path.display(),
+            entry.pattern,
+            skipped
+        );
+    }
+
+    let count = updates.len();
+    plan::save(path, &Plan::new(updates))?;
+    println!("Wrote plan with {} update(s) to {}", count, path.display());
+    Ok(())
+}
+
+/// Non-interactive fallback: list what needs review without changing anything
+fn plain_review(review: &[ReviewEntry]) {
+    for entry in review {
+        println!(
+            "\n{} {}: {}",
+            entry.status,
+            entry.doc_path.display(),
+            entry.pattern
+        );
+        print_changed_files(entry);
+    }
+    println!("\nNot a terminal: no changes made. Run 'drifty check' interactively to update.");
+}
 
-            // Apply updates
-            for (doc_path, entries) in updates {
-                let mut content = fs::read_to_string(&doc_path)?;
+fn interactive_review(
+    review: &[ReviewEntry],
+    snoozes_by_root: &mut HashMap<PathBuf, Vec<Snooze>>,
+) -> Result<()> {
+    let theme = ColorfulTheme::default();
+    let mut decisions: Vec<(&ReviewEntry, Action)> = Vec::new();
 
-                for (pattern, new_hash) in entries {
-                    content = frontmatter::update_entry(&content, pattern, new_hash)?;
-                }
+    for (i, entry) in review.iter().enumerate() {
+        println!(
+            "\n[{}/{}] {} {}: {}",
+            i + 1,
+            review.len(),
+            entry.status,
+            entry.doc_path.display(),
+            entry.pattern
+        );
+        print_changed_files(entry);
 
-                frontmatter::write_file(&doc_path, &content)?;
+        let action = loop {
+            let mut choices = Vec::new();
+            match entry.status {
+                Status::Drifted => choices.extend(["Acknowledge", "Show diff"]),
+                Status::TransitivelyDrifted => choices.push("Acknowledge"),
+                Status::Missing => choices.push("Re-point to another path"),
+                _ => {}
+            }
+            choices.extend(["Skip", "Snooze", "Remove entry", "Open doc in $EDITOR"]);
+            if is_acknowledgeable(entry.status) {
+                choices.push("Acknowledge all remaining");
             }
 
-            println!("Updated {} entries.", selections.len());
+            let choice = Select::with_theme(&theme)
+                .with_prompt("Action")
+                .items(&choices)
+                .default(0)
+                .interact()?;
+
+            match choices[choice] {
+                "Acknowledge" => break Action::Acknowledge,
+                "Show diff" => print_diff(entry),
+                "Re-point to another path" => {
+                    if let Some(action) = prompt_repoint(&theme, entry)? {
+                        break action;
+                    }
+                }
+                "Skip" => break Action::Skip,
+                "Snooze" if entry.current_hash.is_some() => break Action::Snooze,
+                "Snooze" => println!("Missing entries cannot be snoozed."),
+                "Remove entry" => break Action::Remove,
+                "Open doc in $EDITOR" => open_editor(&entry.doc_path)?,
+                _ => {
+                    // Acknowledge all remaining drifted entries
+                    for rest in &review[i..] {
+                        let action = if is_acknowledgeable(rest.status) {
+                            Action::Acknowledge
+                        } else {
+                            Action::Skip
+                        };
+                        decisions.push((rest, action));
+                    }
+                    return finish_review(decisions, snoozes_by_root);
+                }
+            }
+        };
+        decisions.push((entry, action));
+    }
+
+    finish_review(decisions, snoozes_by_root)
+}
+
+/// Print a summary of the decisions, confirm, and apply them
+fn finish_review(
+    decisions: Vec<(&ReviewEntry, Action)>,
+    snoozes_by_root: &mut HashMap<PathBuf, Vec<Snooze>>,
+) -> Result<()> {
+    let count = |f: fn(&Action) -> bool| decisions.iter().filter(|(_, a)| f(a)).count();
+    let acknowledge = count(|a| matches!(a, Action::Acknowledge));
+    let remove = count(|a| matches!(a, Action::Remove));
+    let repoint = count(|a| matches!(a, Action::Repoint { .. }));
+    let snoozed = count(|a| matches!(a, Action::Snooze));
+    let skip = count(|a| matches!(a, Action::Skip));
+
+    println!(
+        "\nSummary: {} to acknowledge, {} to remove, {} to re-point, {} to snooze ({} days), {} skipped",
+        acknowledge, remove, repoint, snoozed, SNOOZE_DAYS, skip
+    );
+    if acknowledge + remove + repoint + snoozed == 0 {
+        println!("No changes to apply.");
+        return Ok(());
+    }
+
+    if !Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Apply these changes?")
+        .default(true)
+        .interact()?
+    {
+        println!("No changes made.");
+        return Ok(());
+    }
+
+    // Group frontmatter edits by document
+    let mut edits: HashMap<&Path, Vec<(&ReviewEntry, &Action)>> = HashMap::new();
+    for (entry, action) in &decisions {
+        match action {
+            Action::Skip => {}
+            Action::Snooze => {
+                let resolver = PathResolver::new(&entry.doc_path)?;
+                let root = resolver.project_root().to_path_buf();
+                let doc_rel = resolver
+                    .repo_relative(&entry.doc_path)
+                    .unwrap_or(entry.doc_path.clone());
+                let hash = entry.current_hash.as_deref().unwrap_or_default();
+                let snoozes = snoozes_by_root.entry(root.clone()).or_default();
+                snoozes.push(snooze::new_snooze(&doc_rel, &entry.pattern, hash));
+                snooze::save(&root, snoozes)?;
+            }
+            _ => edits
+                .entry(&entry.doc_path)
+                .or_default()
+                .push((entry, action)),
         }
     }
 
-    // Report broken files at end
-    if !broken_files.is_empty() {
-        eprintln!("\nWarning: The following files had errors:");
-        for (path, err) in broken_files {
-            eprintln!("  {}: {}", path.display(), err);
+    let mut applied = 0;
+    for (doc_path, entries) in edits {
+        let _lock = ProjectLock::acquire(doc_path)?;
+        let mut store = EntryStore::load(doc_path)?;
+        for (entry, action) in entries {
+            let result = match action {
+                Action::Acknowledge => match &entry.current_hash {
+                    Some(hash) => store.update(&entry.pattern, hash),
+                    None => continue,
+                },
+                Action::Remove => store.remove(&entry.pattern),
+                Action::Repoint { pattern, hash } => store.replace(&entry.pattern, pattern, hash),
+                Action::Skip | Action::Snooze => continue,
+            };
+            match result {
+                Ok(()) => applied += 1,
+                Err(e) => eprintln!("{}: {}", doc_path.display(), e),
+            }
         }
+        store.save()?;
     }
 
+    println!("Updated {} entries.", applied);
+    if snoozed > 0 {
+        println!("Snoozed {} entries.", snoozed);
+    }
     Ok(())
 }
 
-fn check_entry(resolver: &PathResolver, entry: &WatchEntry) -> (Status, Option<String>) {
-    // Check if entry has a hash
-    let stored_hash = match &entry.hash {
-        Some(h) => h,
-        None => return (Status::Invalid, None),
-    };
+fn prompt_repoint(theme: &ColorfulTheme, entry: &ReviewEntry) -> Result<Option<Action>> {
+    let resolver = PathResolver::new(&entry.doc_path)?;
+    let pattern: String = Input::with_theme(theme)
+        .with_prompt("New path or pattern (empty to cancel)")
+        .allow_empty(true)
+        .interact_text()?;
+    if pattern.is_empty() {
+        return Ok(None);
+    }
 
-    // Check if files exist
-    let paths = match resolver.resolve(&entry.pattern) {
-        Ok(p) => p,
-        Err(_) => return (Status::Missing, None),
-    };
+    match Propagation::new().hash_pattern(&resolver, &pattern) {
+        Ok(hash) => Ok(Some(Action::Repoint { pattern, hash })),
+        Err(e) => {
+            println!("{}", e);
+            Ok(None)
+        }
+    }
+}
 
-    if paths.is_empty() {
-        return (Status::Missing, None);
+/// Show which files changed since the acknowledged state (from git history
+/// when it can be found there)
+fn print_changed_files(entry: &ReviewEntry) {
+    if entry.status == Status::Missing {
+        println!("  No files match this pattern anymore.");
+        return;
+    }
+    if entry.status == Status::TransitivelyDrifted {
+        if entry.drifted_docs.is_empty() {
+            println!("  Unchanged, but the docs it watches were re-acknowledged since.");
+            return;
+        }
+        println!("  Unchanged, but watches docs that drifted:");
+        for doc in &entry.drifted_docs {
+            println!("    {}", doc.display());
+        }
+        return;
     }
 
-    // Compute current hash
-    let current_hash = match resolver.hash_pattern(&entry.pattern) {
-        Ok(h) => h,
-        Err(_) => return (Status::Missing, None),
-    };
+    if let Some((root, rev, spec)) = acknowledged_revision(entry) {
+        if let Ok(stat) = git::diff(&root, &rev, &spec, true) {
+            if !stat.trim().is_empty() {
+                println!("  Changed since {}:", &rev[..rev.len().min(12)]);
+                for line in stat.lines() {
+                    println!("  {}", line);
+                }
+                return;
+            }
+        }
+    }
 
-    if current_hash == *stored_hash {
-        (Status::Current, Some(current_hash))
-    } else {
-        (Status::Drifted, Some(current_hash))
+    // No history available: list the matched files instead
+    let files = PathResolver::new(&entry.doc_path)
+        .and_then(|r| r.resolve_files(&entry.pattern))
+        .unwrap_or_default();
+    println!("  Acknowledged state not found in git history. Matched files:");
+    for file in files.iter().take(MAX_LISTED_FILES) {
+        println!("    {}", file.display());
     }
+    if files.len() > MAX_LISTED_FILES {
+        println!("    ... and {} more", files.len() - MAX_LISTED_FILES);
+    }
+}
+
+fn print_diff(entry: &ReviewEntry) {
+    match acknowledged_revision(entry) {
+        Some((root, rev, spec)) => match git::diff(&root, &rev, &spec, false) {
+            Ok(diff) if !diff.trim().is_empty() => println!("{}", diff),
+            Ok(_) => println!("No committed or tracked changes to show."),
+            Err(e) => println!("{}", e),
+        },
+        None => println!("No diff available: acknowledged state not found in git history."),
+    }
+}
+
+/// Project root, acknowledged commit and git pathspec of a drifted entry
+fn acknowledged_revision(entry: &ReviewEntry) -> Option<(PathBuf, String, String)> {
+    let resolver = PathResolver::new(&entry.doc_path).ok()?;
+    // History only knows the files part of hashes
+    let history = history::trace(
+        &resolver,
+        &entry.pattern,
+        split_hash(entry.stored_hash.as_deref()?).0,
+        split_hash(entry.current_hash.as_deref()?).0,
+    )
+    .ok()?;
+    let spec = resolver.git_pathspec(&entry.pattern)?;
+    Some((
+        resolver.project_root().to_path_buf(),
+        history.acknowledged?,
+        spec,
+    ))
+}
+
+fn open_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    // Run through the shell so EDITOR may contain arguments
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(anyhow!("Editor exited with {}", status));
+    }
+    Ok(())
 }
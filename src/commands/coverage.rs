@@ -0,0 +1,206 @@
+use anyhow::{anyhow, Context, Result};
+use glob::glob;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::frontmatter;
+use crate::paths::{self, PathResolver};
+use crate::scanner;
+
+#[derive(Debug, Clone, Copy)]
+pub enum CoverageFormat {
+    Plaintext,
+    Json,
+    Lcov,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    covered: usize,
+    total: usize,
+    percent: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct CoverageReport {
+    /// Number of docs watching each source file
+    files: BTreeMap<String, usize>,
+    directories: BTreeMap<String, Summary>,
+    total: Summary,
+}
+
+pub fn run(include: &[String], format: CoverageFormat) -> Result<()> {
+    if include.is_empty() {
+        return Err(anyhow!("No --include patterns given"));
+    }
+
+    let sources = find_sources(include)?;
+    let watchers = collect_watchers()?;
+
+    let files: BTreeMap<String, usize> = sources
+        .iter()
+        .map(|path| {
+            let count = watchers
+                .get(&paths::absolute(path))
+                .map(|docs| docs.len())
+                .unwrap_or(0);
+            (path.display().to_string(), count)
+        })
+        .collect();
+
+    let mut directories: BTreeMap<String, Summary> = BTreeMap::new();
+    let mut total = Summary::default();
+    for (file, count) in &files {
+        let dir = Path::new(file)
+            .parent()
+            .map(|p| p.display().to_string())
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| ".".to_string());
+        for summary in [directories.entry(dir).or_default(), &mut total] {
+            summary.total += 1;
+            if *count > 0 {
+                summary.covered += 1;
+            }
+        }
+    }
+    for summary in directories.values_mut().chain([&mut total]) {
+        summary.percent = percent(summary.covered, summary.total);
+    }
+
+    let report = CoverageReport {
+        files,
+        directories,
+        total,
+    };
+
+    match format {
+        CoverageFormat::Plaintext => print_plaintext(&report),
+        CoverageFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        CoverageFormat::Lcov => print_lcov(&report),
+    }
+
+    Ok(())
+}
+
+/// Expand the include globs (relative to the current directory) into files
+fn find_sources(include: &[String]) -> Result<BTreeSet<PathBuf>> {
+    let mut sources = BTreeSet::new();
+    for pattern in include {
+        for entry in glob(pattern).with_context(|| format!("Invalid glob pattern: {}", pattern))? {
+            match entry {
+                Ok(path) if path.is_file() && !paths::is_hidden(&path) => {
+                    sources.insert(path);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Warning: glob error: {}", e),
+            }
+        }
+    }
+    Ok(sources)
+}
+
+/// Map every watched file (absolute) to the set of docs watching it
+fn collect_watchers() -> Result<BTreeMap<PathBuf, BTreeSet<PathBuf>>> {
+    let mut watchers: BTreeMap<PathBuf, BTreeSet<PathBuf>> = BTreeMap::new();
+
+    for doc_path in scanner::find_documents(None)? {
+        let fm = match frontmatter::parse_file(&doc_path) {
+            Ok(Some(fm)) if fm.has_driftwatcher() => fm,
+            Ok(_) => continue,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+
+        let resolver = match PathResolver::new(&doc_path) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+
+        for entry in &fm.entries {
+            for path in resolver.resolve_files(&entry.pattern).unwrap_or_default() {
+                watchers
+                    .entry(paths::absolute(&path))
+                    .or_default()
+                    .insert(doc_path.clone());
+            }
+        }
+    }
+
+    Ok(watchers)
+}
+
+fn percent(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    (covered as f64 * 1000.0 / total as f64).round() / 10.0
+}
+
+fn print_plaintext(report: &CoverageReport) {
+    if report.files.is_empty() {
+        println!("No source files matched.");
+        return;
+    }
+
+    let uncovered: Vec<_> = report.files.iter().filter(|(_, c)| **c == 0).collect();
+    if !uncovered.is_empty() {
+        println!("Uncovered files:");
+        for (file, _) in uncovered {
+            println!("  {}", file);
+        }
+        println!();
+    }
+
+    let width = report
+        .directories
+        .keys()
+        .map(|d| d.len())
+        .max()
+        .unwrap_or(0)
+        .max("Directory".len());
+    println!(
+        "{:width$}  {:>7}  {:>5}  {:>6}",
+        "Directory", "Covered", "Total", "Percent"
+    );
+    for (dir, summary) in &report.directories {
+        print_summary_line(dir, summary, width);
+    }
+    print_summary_line("Total", &report.total, width);
+}
+
+fn print_summary_line(name: &str, summary: &Summary, width: usize) {
+    println!(
+        "{:width$}  {:>7}  {:>5}  {:>6.1}%",
+        name, summary.covered, summary.total, summary.percent
+    );
+}
+
+/// lcov-style tracefile with one record per source file. Each file is a single
+/// "line" whose hit count is the number of docs watching it.
+fn print_lcov(report: &CoverageReport) {
+    for (file, count) in &report.files {
+        println!("SF:{}", file);
+        println!("DA:1,{}", count);
+        println!("LF:1");
+        println!("LH:{}", if *count > 0 { 1 } else { 0 });
+        println!("end_of_record");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent() {
+        assert_eq!(percent(0, 0), 0.0);
+        assert_eq!(percent(1, 3), 33.3);
+        assert_eq!(percent(2, 2), 100.0);
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::status::Status;
+
+use super::report::{self, DocumentReport, EntryReport};
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2em auto; max-width: 72em; color: #24292f; padding: 0 1em; }
+h1 { font-size: 1.6em; }
+h2 { font-size: 1.2em; margin-top: 2em; border-bottom: 1px solid #d0d7de; padding-bottom: .3em; }
+table { border-collapse: collapse; width: 100%; font-size: .9em; }
+th, td { text-align: left; padding: .4em .6em; border-bottom: 1px solid #eaeef2; vertical-align: top; }
+code { font-family: ui-monospace, Menlo, monospace; font-size: .95em; }
+.bar { display: flex; height: 1.4em; border-radius: 4px; overflow: hidden; margin: .5em 0; background: #eaeef2; }
+.legend span { margin-right: 1.5em; }
+.badge { display: inline-block; padding: .1em .5em; border-radius: 1em; color: #fff; font-size: .8em; font-weight: 600; }
+.current { background: #1a7f37; }
+.drifted { background: #bf8700; }
+.transitively-drifted { background: #9a6700; }
+.snippet-drifted { background: #8250df; }
+.missing { background: #cf222e; }
+.invalid { background: #6e7781; }
+.muted { color: #6e7781; }
+details summary { cursor: pointer; }
+"#;
+
+const STATUSES: [Status; 6] = [
+    Status::Current,
+    Status::Drifted,
+    Status::TransitivelyDrifted,
+    Status::SnippetDrifted,
+    Status::Missing,
+    Status::Invalid,
+];
+
+/// Write the HTML dashboard to `<dir>/index.html`
+pub fn run(dir: &Path) -> Result<()> {
+    let reports = report::collect(None, true, true)?;
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+
+    let path = dir.join("index.html");
+    fs::write(&path, render(&reports))
+        .with_context(|| format!("Failed to write file: {}", path.display()))?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// Render a self-contained HTML page (no scripts or external assets)
+pub fn render(reports: &[DocumentReport]) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Documentation drift</title>\n<style>");
+    html.push_str(STYLE);
+    html.push_str("</style>\n</head>\n<body>\n<h1>Documentation drift</h1>\n");
+
+    render_summary(&mut html, reports);
+
+    if reports.is_empty() {
+        html.push_str("<p class=\"muted\">No driftwatcher entries found.</p>\n");
+    }
+
+    // Group docs by directory
+    let mut groups: BTreeMap<String, Vec<&DocumentReport>> = BTreeMap::new();
+    for report in reports {
+        let dir = report
+            .doc_path
+            .parent()
+            .map(|p| p.display().to_string())
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| ".".to_string());
+        groups.entry(dir).or_default().push(report);
+    }
+
+    for (dir, docs) in groups {
+        html.push_str(&format!("<h2><code>{}</code></h2>\n", escape(&dir)));
+        html.push_str("<table>\n<tr><th>Document</th><th>Pattern</th><th>Status</th>");
+        html.push_str("<th>Matched files</th><th>Last acknowledged</th></tr>\n");
+        for doc in docs {
+            let name = doc
+                .doc_path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            for entry in &doc.results {
+                render_entry(&mut html, &name, entry);
+            }
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn render_summary(html: &mut String, reports: &[DocumentReport]) {
+    let counts: Vec<(Status, usize)> = STATUSES
+        .iter()
+        .map(|status| {
+            let count = reports
+                .iter()
+                .flat_map(|r| &r.results)
+                .filter(|e| e.status == *status)
+                .count();
+            (*status, count)
+        })
+        .collect();
+    let total: usize = counts.iter().map(|(_, c)| c).sum();
+
+    html.push_str("<div class=\"bar\">");
+    for (status, count) in &counts {
+        if *count > 0 {
+            html.push_str(&format!(
+                "<div class=\"{}\" style=\"width: {:.2}%\" title=\"{} {}\"></div>",
+                class(*status),
+                *count as f64 * 100.0 / total as f64,
+                count,
+                status
+            ));
+        }
+    }
+    html.push_str("</div>\n<p class=\"legend\">");
+    for (status, count) in &counts {
+        html.push_str(&format!(
+            "<span><span class=\"badge {}\">{}</span> {}</span>",
+            class(*status),
+            status,
+            count
+        ));
+    }
+    html.push_str("</p>\n");
+}
+
+fn render_entry(html: &mut String, doc_name: &str, entry: &EntryReport) {
+    html.push_str(&format!(
+        "<tr><td>{}</td><td><code>{}</code></td><td><span class=\"badge {}\">{}</span></td>",
+        escape(doc_name),
+        escape(&entry.pattern),
+        class(entry.status),
+        entry.status
+    ));
+
+    match entry.files.len() {
+        0 => html.push_str("<td class=\"muted\">none</td>"),
+        1 => html.push_str(&format!(
+            "<td><code>{}</code></td>",
+            escape(&entry.files[0].display().to_string())
+        )),
+        n => {
+            html.push_str(&format!("<td><details><summary>{} files</summary>", n));
+            for file in &entry.files {
+                html.push_str(&format!(
+                    "<code>{}</code><br>",
+                    escape(&file.display().to_string())
+                ));
+            }
+            html.push_str("</details></td>");
+        }
+    }
+
+    match &entry.acknowledged {
+        Some(commit) => html.push_str(&format!(
+            "<td><code title=\"{}\">{}</code> {} <span class=\"muted\">{}</span></td>",
+            escape(&commit.summary),
+            escape(&commit.id[..commit.id.len().min(10)]),
+            escape(&commit.author),
+            escape(&commit.date)
+        )),
+        None => html.push_str("<td class=\"muted\">unknown</td>"),
+    }
+    html.push_str("</tr>\n");
+}
+
+fn class(status: Status) -> &'static str {
+    match status {
+        Status::Current => "current",
+        Status::Drifted => "drifted",
+        Status::TransitivelyDrifted => "transitively-drifted",
+        Status::SnippetDrifted => "snippet-drifted",
+        Status::Missing => "missing",
+        Status::Invalid => "invalid",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_render_groups_by_directory() {
+        let reports = vec![DocumentReport {
+            doc_path: PathBuf::from("docs/api.md"),
+            results: vec![EntryReport {
+                pattern: "src/**/*.rs".to_string(),
+                status: Status::Drifted,
+                files: vec![PathBuf::from("src/a.rs"), PathBuf::from("src/b.rs")],
+                acknowledged: None,
+                snippet_line: None,
+            }],
+        }];
+
+        let html = render(&reports);
+        assert!(html.contains("<h2><code>docs</code></h2>"));
+        assert!(html.contains("2 files"));
+        assert!(html.contains("badge drifted"));
+        assert!(!html.contains("<script"));
+    }
+}
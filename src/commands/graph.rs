@@ -0,0 +1,272 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::paths::{self, PathResolver};
+use crate::status::Status;
+
+use super::report;
+
+/// Output formats of `drifty graph`
+#[derive(Debug, Clone, Copy)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum NodeKind {
+    Doc,
+    File,
+    Directory,
+    /// A pattern that matches nothing
+    Missing,
+}
+
+#[derive(Debug, Serialize)]
+struct Node {
+    /// Path relative to the project root
+    id: String,
+    kind: NodeKind,
+}
+
+#[derive(Debug, Serialize)]
+struct Edge {
+    from: String,
+    to: String,
+    pattern: String,
+    status: Status,
+}
+
+/// Docs and the files they watch, with an edge per doc and watched node
+#[derive(Debug, Default, Serialize)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+pub fn run(
+    target: Option<&Path>,
+    format: GraphFormat,
+    collapse: bool,
+    subtree: Option<&Path>,
+) -> Result<()> {
+    let subtree = subtree.map(repo_relative).transpose()?;
+    let graph = collect(target, collapse, subtree.as_deref())?;
+
+    match format {
+        GraphFormat::Dot => print!("{}", render_dot(&graph)),
+        GraphFormat::Mermaid => print!("{}", render_mermaid(&graph)),
+        GraphFormat::Json => println!("{}", serde_json::to_string_pretty(&graph)?),
+    }
+    Ok(())
+}
+
+/// Build the graph from every doc's entries. Watched docs are doc nodes if
+/// they have entries of their own in the graph, so source files that can hold
+/// directives count as files. With `collapse`, a glob becomes
+/// one node for the directory it matches in; with `subtree`, only edges to
+/// nodes inside it are kept.
+fn collect(target: Option<&Path>, collapse: bool, subtree: Option<&Path>) -> Result<Graph> {
+    let mut nodes = BTreeMap::new();
+    let mut edges = Vec::new();
+
+    for report in report::collect(target, true, false)? {
+        let resolver = PathResolver::new(&report.doc_path)?;
+        let doc = match resolver.repo_relative(&report.doc_path) {
+            Some(doc) => doc,
+            None => continue,
+        };
+
+        for entry in &report.results {
+            // A glob is expanded into its files unless collapsed. Otherwise the
+            // node is the pattern's literal part: its file, its directory, or
+            // the directory its glob matches in. A code snippet links to the
+            // file it copies.
+            let expand = !collapse && paths::is_glob_pattern(&entry.pattern);
+            let snippet = entry.snippet_line.is_some();
+            let targets: Vec<PathBuf> = if snippet || expand && !entry.files.is_empty() {
+                entry.files.clone()
+            } else {
+                resolver.pathspec(&entry.pattern).into_iter().collect()
+            };
+
+            for target in targets {
+                if subtree.is_some_and(|s| !target.starts_with(s)) {
+                    continue;
+                }
+                let absolute = resolver.project_root().join(&target);
+                let kind = if entry.files.is_empty() {
+                    NodeKind::Missing
+                } else if absolute.is_dir() {
+                    NodeKind::Directory
+                } else {
+                    NodeKind::File
+                };
+                let to = id(&target);
+                nodes.entry(to.clone()).or_insert(kind);
+                nodes.insert(id(&doc), NodeKind::Doc);
+                edges.push(Edge {
+                    from: id(&doc),
+                    to,
+                    pattern: entry.pattern.clone(),
+                    status: entry.status,
+                });
+            }
+        }
+    }
+
+    Ok(Graph {
+        nodes: nodes
+            .into_iter()
+            .map(|(id, kind)| Node { id, kind })
+            .collect(),
+        edges,
+    })
+}
+
+/// A path relative to the root of its project
+fn repo_relative(path: &Path) -> Result<PathBuf> {
+    let root = paths::normalize(&paths::find_project_root(path)?);
+    paths::absolute(path)
+        .strip_prefix(&root)
+        .map(Path::to_path_buf)
+        .map_err(|_| anyhow!("{} is outside the project", path.display()))
+}
+
+fn id(path: &Path) -> String {
+    let id = path.to_string_lossy().replace('\\', "/");
+    if id.is_empty() {
+        ".".to_string()
+    } else {
+        id
+    }
+}
+
+/// Edge color for a status
+fn color(status: Status) -> &'static str {
+    match status {
+        Status::Current => "#1a7f37",
+        Status::Drifted => "#bf8700",
+        Status::TransitivelyDrifted => "#9a6700",
+        Status::SnippetDrifted => "#8250df",
+        Status::Missing => "#cf222e",
+        Status::Invalid => "#6e7781",
+    }
+}
+
+fn render_dot(graph: &Graph) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut out = String::from("digraph drifty {\n  rankdir=LR;\n");
+    for node in &graph.nodes {
+        let shape = match node.kind {
+            NodeKind::Doc => "note",
+            NodeKind::File => "box",
+            NodeKind::Directory => "folder",
+            NodeKind::Missing => "box, style=dashed",
+        };
+        out.push_str(&format!("  {} [shape={}];\n", quote(&node.id), shape));
+    }
+    for edge in &graph.edges {
+        out.push_str(&format!(
+            "  {} -> {} [color={}, tooltip={}];\n",
+            quote(&edge.from),
+            quote(&edge.to),
+            quote(color(edge.status)),
+            quote(&format!("{} ({})", edge.pattern, edge.status))
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn render_mermaid(graph: &Graph) -> String {
+    // Mermaid ids can't hold paths, so nodes are numbered
+    let ids: BTreeMap<&str, usize> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.id.as_str(), i))
+        .collect();
+    let label = |s: &str| s.replace('"', "#quot;");
+
+    let mut out = String::from("flowchart LR\n");
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let (open, close) = match node.kind {
+            NodeKind::Doc => ("[/", "/]"),
+            NodeKind::Directory => ("[(", ")]"),
+            NodeKind::File | NodeKind::Missing => ("[", "]"),
+        };
+        out.push_str(&format!(
+            "  n{}{}\"{}\"{}\n",
+            i,
+            open,
+            label(&node.id),
+            close
+        ));
+    }
+    for edge in &graph.edges {
+        out.push_str(&format!(
+            "  n{} -->|{}| n{}\n",
+            ids[edge.from.as_str()],
+            edge.status,
+            ids[edge.to.as_str()]
+        ));
+    }
+    for (i, edge) in graph.edges.iter().enumerate() {
+        out.push_str(&format!(
+            "  linkStyle {} stroke:{}\n",
+            i,
+            color(edge.status)
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Graph {
+        Graph {
+            nodes: vec![
+                Node {
+                    id: "docs/a \"b\".md".to_string(),
+                    kind: NodeKind::Doc,
+                },
+                Node {
+                    id: "src".to_string(),
+                    kind: NodeKind::Directory,
+                },
+            ],
+            edges: vec![Edge {
+                from: "docs/a \"b\".md".to_string(),
+                to: "src".to_string(),
+                pattern: "../src/*.rs".to_string(),
+                status: Status::Drifted,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_render_dot() {
+        let dot = render_dot(&sample());
+        assert!(dot.contains("  \"docs/a \\\"b\\\".md\" [shape=note];\n"));
+        assert!(dot.contains(
+            "  \"docs/a \\\"b\\\".md\" -> \"src\" [color=\"#bf8700\", tooltip=\"../src/*.rs (DRIFTED)\"];\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_mermaid() {
+        let mermaid = render_mermaid(&sample());
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains("  n0[/\"docs/a #quot;b#quot;.md\"/]\n"));
+        assert!(mermaid.contains("  n1[(\"src\")]\n"));
+        assert!(mermaid.contains("  n0 -->|DRIFTED| n1\n"));
+        assert!(mermaid.contains("  linkStyle 0 stroke:#bf8700\n"));
+    }
+}
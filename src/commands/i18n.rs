@@ -0,0 +1,184 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+
+use crate::frontmatter;
+use crate::git;
+use crate::history;
+use crate::paths::PathResolver;
+use crate::scanner;
+use crate::status::{check_entry, Status};
+
+use super::report::OutputFormat;
+
+#[derive(Debug, Serialize)]
+struct TranslationEntry {
+    /// Source doc, relative to the project root
+    source: PathBuf,
+    /// Translated doc, relative to the project root
+    translation: PathBuf,
+    locale: String,
+    status: Status,
+    /// Commit whose source body matches the hash the translation recorded
+    translated_from: Option<String>,
+    /// Changes to the source since the translated version (lagging
+    /// translations only)
+    diff: Option<String>,
+    /// `drifty add` command recording the source version, when none is
+    #[serde(skip)]
+    add_command: String,
+}
+
+pub fn run(target: Option<&Path>, format: OutputFormat) -> Result<()> {
+    let docs = scanner::find_documents(target)?;
+    let mut entries = Vec::new();
+
+    for doc_path in docs {
+        let fm = match frontmatter::parse_file(&doc_path) {
+            Ok(Some(fm)) => fm,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+        let (Some(source), Some(pattern)) = (&fm.translation_of, fm.translation_pattern()) else {
+            continue;
+        };
+
+        let resolver = match PathResolver::new(&doc_path) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+        let relative = |path: &Path| resolver.repo_relative(path).unwrap_or(path.to_path_buf());
+        let source_path = relative(&doc_path.parent().unwrap_or(Path::new(".")).join(source));
+        let translation = relative(&doc_path);
+
+        // Without an entry for the source, the translated version is unknown
+        let entry = fm.entries.iter().find(|e| e.pattern == pattern);
+        let (status, current_hash) = match entry {
+            Some(entry) => check_entry(&resolver, entry),
+            None => (Status::Invalid, None),
+        };
+        let stored_hash = entry.and_then(|e| e.hash.as_deref());
+
+        let mut translated_from = None;
+        let mut diff = None;
+        if let (Status::Drifted, Some(stored), Some(current)) =
+            (status, stored_hash, current_hash.as_deref())
+        {
+            match history::trace(&resolver, &pattern, stored, current) {
+                Ok(history) => translated_from = history.acknowledged,
+                Err(e) => eprintln!("Warning: {}: {}: {}", doc_path.display(), pattern, e),
+            }
+            if let (Some(rev), Some(spec)) = (&translated_from, resolver.git_pathspec(&pattern)) {
+                diff = git::diff(resolver.project_root(), rev, &spec, false).ok();
+            }
+        }
+
+        entries.push(TranslationEntry {
+            add_command: format!("drifty add {} {}", doc_path.display(), source),
+            locale: locale(&source_path, &translation),
+            source: source_path,
+            translation,
+            status,
+            translated_from,
+            diff,
+        });
+    }
+
+    entries.sort_by(|a, b| (&a.source, &a.locale).cmp(&(&b.source, &b.locale)));
+
+    match format {
+        OutputFormat::Plaintext => print_plaintext(&entries),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&entries)?),
+    }
+
+    Ok(())
+}
+
+fn print_plaintext(entries: &[TranslationEntry]) {
+    if entries.is_empty() {
+        println!("No translations found.");
+        return;
+    }
+
+    let mut last_source = None;
+    for entry in entries {
+        if last_source != Some(&entry.source) {
+            if last_source.is_some() {
+                println!();
+            }
+            println!("{}", entry.source.display());
+            last_source = Some(&entry.source);
+        }
+
+        println!(
+            "  {} {} {}",
+            entry.status,
+            entry.locale,
+            entry.translation.display()
+        );
+        match entry.status {
+            Status::Drifted => match (&entry.translated_from, &entry.diff) {
+                (Some(rev), Some(diff)) if !diff.trim().is_empty() => {
+                    println!("    Source changed since {}:", &rev[..rev.len().min(12)]);
+                    for line in diff.lines() {
+                        println!("    {}", line);
+                    }
+                }
+                (Some(_), _) => println!("    No committed or tracked changes to show"),
+                (None, _) => println!("    Translated version not found in git history"),
+            },
+            Status::Missing => println!("    Source no longer exists"),
+            Status::Invalid => println!(
+                "    No source version recorded; run '{}'",
+                entry.add_command
+            ),
+            _ => {}
+        }
+    }
+}
+
+/// Locale of a translation: the first path component where it differs from
+/// its source (`ja` for `docs/ja/guide.md` and `docs/en/guide.md`), or the
+/// extra extension of a file next to its source (`ja` for `guide.ja.md`)
+fn locale(source: &Path, translation: &Path) -> String {
+    let source: Vec<Component> = source.components().collect();
+    let translation: Vec<Component> = translation.components().collect();
+
+    for (i, part) in translation.iter().enumerate() {
+        if source.get(i) == Some(part) {
+            continue;
+        }
+        let name = Path::new(part.as_os_str());
+        if i + 1 == translation.len() {
+            let stem = name.file_stem().map(Path::new);
+            if let Some(ext) = stem.and_then(Path::extension) {
+                return ext.to_string_lossy().into_owned();
+            }
+        }
+        return name.to_string_lossy().into_owned();
+    }
+    "?".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale() {
+        let en = Path::new("docs/en/guide.md");
+        assert_eq!(locale(en, Path::new("docs/ja/guide.md")), "ja");
+        assert_eq!(locale(en, Path::new("docs/de-DE/setup/guide.md")), "de-DE");
+        assert_eq!(
+            locale(Path::new("docs/guide.md"), Path::new("docs/guide.fr.md")),
+            "fr"
+        );
+    }
+}
@@ -1,8 +1,9 @@
 use anyhow::{anyhow, Result};
-use std::fs;
 use std::path::Path;
 
+use crate::document::Format;
 use crate::frontmatter;
+use crate::lock::ProjectLock;
 
 pub fn run(doc_file: &Path) -> Result<()> {
     // Check file exists
@@ -10,18 +11,19 @@ pub fn run(doc_file: &Path) -> Result<()> {
         return Err(anyhow!("Invalid file: {}", doc_file.display()));
     }
 
-    // Read content
-    let content = fs::read_to_string(doc_file)?;
+    // Read content, keeping other drifty processes out until it is written back
+    let _lock = ProjectLock::acquire(doc_file)?;
+    let content = frontmatter::read_file(doc_file)?;
 
     // Check current state
-    match frontmatter::parse(&content)? {
+    match frontmatter::parse_document(doc_file, &content)? {
         Some(fm) if fm.has_driftwatcher() => {
             println!("driftwatcher already initialized in {}", doc_file.display());
             Ok(())
         }
         Some(_) => {
             // Has frontmatter but no driftwatcher key - add it
-            let new_content = frontmatter::add_driftwatcher_to_existing(&content)?;
+            let new_content = frontmatter::add_driftwatcher_to_existing(doc_file, &content)?;
             frontmatter::write_file(doc_file, &new_content)?;
             println!(
                 "Added driftwatcher to existing frontmatter in {}",
@@ -31,7 +33,9 @@ pub fn run(doc_file: &Path) -> Result<()> {
         }
         None => {
             // No frontmatter - add complete block
-            let new_content = frontmatter::add_empty_frontmatter(&content);
+            let format = Format::from_path(doc_file)
+                .ok_or_else(|| anyhow!("Not a document: {}", doc_file.display()))?;
+            let new_content = frontmatter::add_empty_frontmatter(&content, format)?;
             frontmatter::write_file(doc_file, &new_content)?;
             println!("Initialized driftwatcher in {}", doc_file.display());
             Ok(())
@@ -0,0 +1,164 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::frontmatter;
+use crate::lint::{self, Config, Finding, Fix, Severity};
+use crate::lock::ProjectLock;
+use crate::paths::PathResolver;
+use crate::scanner;
+use crate::store::EntryStore;
+
+pub fn run(target: Option<&Path>, fix: bool) -> Result<()> {
+    let docs = scanner::find_documents(target)?;
+    let mut configs: HashMap<PathBuf, Config> = HashMap::new();
+    let mut errors = 0;
+    let mut warnings = 0;
+    let mut fixed = 0;
+    let mut fixable_count = 0;
+
+    for doc_path in docs {
+        let _lock = fix.then(|| ProjectLock::acquire(&doc_path)).transpose()?;
+        let resolver = match PathResolver::new(&doc_path) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+        let root = resolver.project_root().to_path_buf();
+        if !configs.contains_key(&root) {
+            configs.insert(root.clone(), Config::load(&root)?);
+        }
+        let config = &configs[&root];
+
+        let mut findings = lint_doc(&doc_path, &resolver, config);
+        if fix && findings.iter().any(|(f, _)| f.fix.is_some()) {
+            let applied = apply_fixes(&doc_path, &findings)?;
+            for (finding, _) in &findings {
+                if finding.fix.as_ref().is_some_and(|f| applied.contains(f)) {
+                    fixed += 1;
+                    println!(
+                        "{}: fixed [{}] {}",
+                        doc_path.display(),
+                        finding.rule,
+                        finding.message
+                    );
+                }
+            }
+            // Lines and patterns may have changed, so the doc is linted again
+            findings = lint_doc(&doc_path, &resolver, config);
+        }
+
+        for (finding, severity) in findings {
+            match severity {
+                Severity::Error => errors += 1,
+                _ => warnings += 1,
+            }
+            let location = match finding.line {
+                Some(line) => format!("{}:{}", doc_path.display(), line),
+                None => doc_path.display().to_string(),
+            };
+            let fixable = if finding.fix.is_some() && !fix {
+                fixable_count += 1;
+                " (fixable)"
+            } else {
+                ""
+            };
+            eprintln!(
+                "{}: {}[{}]: {}{}",
+                location, severity, finding.rule, finding.message, fixable
+            );
+        }
+    }
+
+    if fixed > 0 {
+        println!("\nFixed {} problem(s).", fixed);
+    }
+    if errors == 0 && warnings == 0 {
+        println!("No lint problems found.");
+        return Ok(());
+    }
+    eprintln!("\n{} error(s), {} warning(s).", errors, warnings);
+    if fixable_count > 0 {
+        eprintln!("Run 'drifty lint --fix' to fix the problems marked fixable.");
+    }
+    if errors > 0 {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// The findings of the rules that are not turned off, with their severity.
+/// Docs without entries have none, and invalid frontmatter is left to
+/// `drifty validate`.
+fn lint_doc(doc_path: &Path, resolver: &PathResolver, config: &Config) -> Vec<(Finding, Severity)> {
+    let Ok(content) = frontmatter::read_file(doc_path) else {
+        return Vec::new();
+    };
+    let fm = match EntryStore::load(doc_path).and_then(|store| store.frontmatter()) {
+        Ok(Some(fm)) if fm.has_driftwatcher() => fm,
+        _ => return Vec::new(),
+    };
+
+    lint::check(doc_path, &fm, &content, resolver)
+        .into_iter()
+        .map(|finding| {
+            let severity = config.severity(finding.rule);
+            (finding, severity)
+        })
+        .filter(|(_, severity)| *severity != Severity::Off)
+        .collect()
+}
+
+/// Apply the fixes of a doc's findings, returning the ones that worked
+fn apply_fixes(doc_path: &Path, findings: &[(Finding, Severity)]) -> Result<Vec<Fix>> {
+    let mut applied = Vec::new();
+    let fixes: Vec<&Fix> = findings
+        .iter()
+        .filter_map(|(f, _)| f.fix.as_ref())
+        .collect();
+
+    // Removing the empty key rewrites the block, so it's done on its own
+    if fixes.contains(&&Fix::RemoveDriftwatcher) {
+        let content = frontmatter::read_file(doc_path)?;
+        frontmatter::write_file(
+            doc_path,
+            &frontmatter::remove_driftwatcher(doc_path, &content)?,
+        )?;
+        return Ok(vec![Fix::RemoveDriftwatcher]);
+    }
+
+    let mut store = EntryStore::load(doc_path)?;
+    let mut rewritten: Vec<&str> = Vec::new();
+    for fix in &fixes {
+        let result = match fix {
+            Fix::RemoveEntry { pattern } => store.remove(pattern),
+            Fix::ReplaceEntry {
+                pattern,
+                new_pattern,
+                hash,
+            } => {
+                rewritten.push(pattern);
+                store.replace(pattern, new_pattern, hash)
+            }
+            Fix::QuotePattern { .. } | Fix::RemoveDriftwatcher => continue,
+        };
+        match result {
+            Ok(()) => applied.push((*fix).clone()),
+            Err(e) => eprintln!("Warning: {}: {}", doc_path.display(), e),
+        }
+    }
+
+    // Entries are always written quoted, so one rewritten above already is
+    for fix in fixes {
+        if let Fix::QuotePattern { pattern, hash } = fix {
+            if rewritten.contains(&pattern.as_str()) || store.update(pattern, hash).is_ok() {
+                applied.push(fix.clone());
+            }
+        }
+    }
+    store.save()?;
+    Ok(applied)
+}
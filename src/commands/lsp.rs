@@ -0,0 +1,677 @@
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::OsStr;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::document::Format;
+use crate::frontmatter::{self, Frontmatter, WatchEntry};
+use crate::hash;
+use crate::manifest::MANIFEST_FILE;
+use crate::paths::{self, PathResolver};
+use crate::scanner;
+use crate::snippets;
+use crate::status::{Propagation, Status};
+
+use super::who_documents::DocCoverage;
+
+// LSP diagnostic severities
+const SEVERITY_ERROR: u8 = 1;
+const SEVERITY_WARNING: u8 = 2;
+const SEVERITY_HINT: u8 = 4;
+
+/// Maximum number of replacement paths offered for a missing entry
+const MAX_REPOINT_CANDIDATES: usize = 5;
+
+/// Language server speaking LSP (JSON-RPC) over stdio
+struct Server {
+    root: PathBuf,
+    /// Open documents by URI, with their in-editor text
+    open: HashMap<String, String>,
+    /// Resolved entries of every doc in the project, by absolute path. Loaded
+    /// on first use and refreshed per doc when it is saved.
+    coverage: Option<BTreeMap<PathBuf, DocCoverage>>,
+}
+
+pub fn run() -> Result<()> {
+    let stdin = io::stdin();
+    let mut reader = BufReader::new(stdin.lock());
+    let mut stdout = io::stdout();
+    let mut server = Server {
+        root: std::env::current_dir()?,
+        open: HashMap::new(),
+        coverage: None,
+    };
+
+    while let Some(message) = read_message(&mut reader)? {
+        let method = message["method"].as_str().unwrap_or("");
+        let id = message.get("id").cloned();
+        let params = &message["params"];
+
+        if method == "exit" {
+            break;
+        }
+
+        let mut outgoing = Vec::new();
+        let result = server.handle(method, params, &mut outgoing);
+
+        if let Some(id) = id {
+            let response = match result {
+                Ok(Some(value)) => json!({"jsonrpc": "2.0", "id": id, "result": value}),
+                Ok(None) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32601, "message": format!("Unknown method: {}", method)},
+                }),
+                Err(e) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32603, "message": e.to_string()},
+                }),
+            };
+            write_message(&mut stdout, &response)?;
+        } else if let Err(e) = result {
+            eprintln!("drifty lsp: {}: {}", method, e);
+        }
+
+        for notification in outgoing {
+            write_message(&mut stdout, &notification)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Server {
+    /// Handle one request or notification. Returns the response for requests
+    /// (None for unknown methods); notifications to send go into `outgoing`.
+    fn handle(
+        &mut self,
+        method: &str,
+        params: &Value,
+        outgoing: &mut Vec<Value>,
+    ) -> Result<Option<Value>> {
+        match method {
+            "initialize" => {
+                if let Some(path) = params["rootUri"].as_str().and_then(uri_to_path) {
+                    self.root = path;
+                }
+                Ok(Some(json!({
+                    "capabilities": {
+                        "textDocumentSync": {"openClose": true, "change": 1, "save": true},
+                        "hoverProvider": true,
+                        "codeActionProvider": true,
+                    },
+                    "serverInfo": {"name": "drifty", "version": env!("CARGO_PKG_VERSION")},
+                })))
+            }
+            "shutdown" => Ok(Some(Value::Null)),
+            "textDocument/didOpen" => {
+                let uri = text_document_uri(params)?;
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                self.open.insert(uri.clone(), text.to_string());
+                outgoing.push(self.diagnostics(&uri));
+                Ok(Some(Value::Null))
+            }
+            "textDocument/didChange" => {
+                let uri = text_document_uri(params)?;
+                // Full sync: the last change holds the whole text
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|c| c.last())
+                    .and_then(|c| c["text"].as_str())
+                {
+                    self.open.insert(uri.clone(), text.to_string());
+                }
+                outgoing.push(self.diagnostics(&uri));
+                Ok(Some(Value::Null))
+            }
+            "textDocument/didSave" => {
+                let uri = text_document_uri(params)?;
+                if let Some(path) = uri_to_path(&uri) {
+                    self.refresh_coverage(&path);
+                }
+                // Saving any file can change the status of every open doc
+                let uris: Vec<String> = self.open.keys().cloned().collect();
+                for uri in uris {
+                    outgoing.push(self.diagnostics(&uri));
+                }
+                Ok(Some(Value::Null))
+            }
+            "textDocument/didClose" => {
+                let uri = text_document_uri(params)?;
+                self.open.remove(&uri);
+                outgoing.push(publish(&uri, Vec::new()));
+                Ok(Some(Value::Null))
+            }
+            "textDocument/hover" => {
+                let uri = text_document_uri(params)?;
+                let line = params["position"]["line"].as_u64().unwrap_or(0) as usize;
+                Ok(Some(self.hover(&uri, line).unwrap_or(Value::Null)))
+            }
+            "textDocument/codeAction" => {
+                let uri = text_document_uri(params)?;
+                let start = params["range"]["start"]["line"].as_u64().unwrap_or(0) as usize;
+                let end = params["range"]["end"]["line"].as_u64().unwrap_or(0) as usize;
+                Ok(Some(Value::Array(self.code_actions(&uri, start, end))))
+            }
+            // Other notifications (initialized, $/cancelRequest, ...) need no reply
+            "initialized" => Ok(Some(Value::Null)),
+            _ if method.starts_with("$/") => Ok(Some(Value::Null)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Build the publishDiagnostics notification for an open document
+    fn diagnostics(&mut self, uri: &str) -> Value {
+        let (path, text) = match (uri_to_path(uri), self.open.get(uri)) {
+            (Some(path), Some(text)) => (path, text),
+            _ => return publish(uri, Vec::new()),
+        };
+
+        let mut diagnostics = Vec::new();
+        if scanner::is_document(&path) {
+            diagnostics.extend(doc_diagnostics(&path, text));
+        }
+        // Rust files are source code as well as docs
+        if !scanner::is_document(&path) || Format::from_path(&path) == Some(Format::Rust) {
+            diagnostics.extend(self.source_diagnostics(&path));
+        }
+        publish(uri, diagnostics)
+    }
+
+    /// A hint on the first line of a source file naming the docs covering it
+    fn source_diagnostics(&mut self, path: &Path) -> Vec<Value> {
+        let Some(docs) = self.coverage_docs() else {
+            return Vec::new();
+        };
+        let wanted = BTreeSet::from([paths::absolute(path)]);
+        let mut propagation = Propagation::new();
+        let mut results = BTreeMap::new();
+        for doc in docs.values() {
+            doc.add_coverage(&wanted, &mut propagation, &mut results);
+        }
+        let coverage = results.into_values().next().unwrap_or_default();
+        if coverage.is_empty() {
+            return Vec::new();
+        }
+
+        let docs: Vec<String> = coverage
+            .iter()
+            .map(|c| {
+                let doc = Path::new(&c.doc);
+                let doc = doc.strip_prefix(&self.root).unwrap_or(doc);
+                format!("{} ({}) [{}]", doc.display(), c.pattern, c.status)
+            })
+            .collect();
+        vec![json!({
+            "range": line_range(0, ""),
+            "severity": SEVERITY_HINT,
+            "source": "drifty",
+            "message": format!("Documented by: {}", docs.join(", ")),
+        })]
+    }
+
+    /// The resolved entries of every doc, scanning the project on first use
+    fn coverage_docs(&mut self) -> Option<&BTreeMap<PathBuf, DocCoverage>> {
+        if self.coverage.is_none() {
+            let docs = scanner::find_documents(Some(&self.root)).ok()?;
+            let coverage = docs
+                .into_iter()
+                .filter_map(|doc| Some((paths::absolute(&doc), DocCoverage::load(&doc)?)))
+                .collect();
+            self.coverage = Some(coverage);
+        }
+        self.coverage.as_ref()
+    }
+
+    /// Pick up a saved doc's entries. Saving the manifest can change the
+    /// entries of any doc, so it drops them all.
+    fn refresh_coverage(&mut self, path: &Path) {
+        let Some(coverage) = &mut self.coverage else {
+            return;
+        };
+        if path.file_name() == Some(OsStr::new(MANIFEST_FILE)) {
+            self.coverage = None;
+        } else if scanner::is_document(path) {
+            let path = paths::absolute(path);
+            match DocCoverage::load(&path) {
+                Some(doc) => coverage.insert(path, doc),
+                None => coverage.remove(&path),
+            };
+        }
+    }
+
+    fn hover(&self, uri: &str, line: usize) -> Option<Value> {
+        let (path, text, fm) = self.open_doc(uri)?;
+        let entry = entry_at_line(&path, &fm, text, line)?;
+        let resolver = PathResolver::new(&path).ok()?;
+        let (status, current_hash) = Propagation::new().check_entry(&resolver, entry);
+
+        let mut contents = format!("**{}** `{}`\n\n", status, entry.pattern);
+        contents.push_str(&format!(
+            "- acknowledged: `{}`\n- current: `{}`\n",
+            entry.hash.as_deref().unwrap_or("(none)"),
+            current_hash.as_deref().unwrap_or("(none)")
+        ));
+        let files = resolver.resolve_files(&entry.pattern).unwrap_or_default();
+        if !files.is_empty() {
+            contents.push_str(&format!("\n{} file(s):\n", files.len()));
+            for file in files {
+                contents.push_str(&format!("- `{}`\n", file.display()));
+            }
+        }
+
+        Some(json!({"contents": {"kind": "markdown", "value": contents}}))
+    }
+
+    fn code_actions(&self, uri: &str, start: usize, end: usize) -> Vec<Value> {
+        let (path, text, fm) = match self.open_doc(uri) {
+            Some(doc) => doc,
+            None => return Vec::new(),
+        };
+        let resolver = match PathResolver::new(&path) {
+            Ok(r) => r,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut propagation = Propagation::new();
+        let mut actions = Vec::new();
+        for entry in &fm.entries {
+            match frontmatter::find_entry_line(&path, text, &entry.pattern) {
+                Some(line) if line >= start && line <= end => {}
+                _ => continue,
+            }
+
+            let (status, current_hash) = propagation.check_entry(&resolver, entry);
+            let update = match status {
+                Status::Drifted | Status::TransitivelyDrifted => {
+                    current_hash.map(|hash| (format!("Acknowledge '{}'", entry.pattern), hash))
+                }
+                // An entry without a hash isn't hashed by the check
+                Status::Invalid => propagation
+                    .hash_pattern(&resolver, &entry.pattern)
+                    .ok()
+                    .map(|hash| (format!("Add hash for '{}'", entry.pattern), hash)),
+                _ => None,
+            };
+            if let Some((title, hash)) = update {
+                if let Ok(new_text) = frontmatter::update_entry(&path, text, &entry.pattern, &hash)
+                {
+                    actions.push(action(&title, uri, text, &new_text));
+                }
+            }
+
+            if status == Status::Missing {
+                for candidate in repoint_candidates(&resolver, &entry.pattern) {
+                    let hash = match propagation.hash_pattern(&resolver, &candidate) {
+                        Ok(h) => h,
+                        Err(_) => continue,
+                    };
+                    if let Ok(new_text) =
+                        frontmatter::replace_entry(&path, text, &entry.pattern, &candidate, &hash)
+                    {
+                        actions.push(action(
+                            &format!("Re-point '{}' to '{}'", entry.pattern, candidate),
+                            uri,
+                            text,
+                            &new_text,
+                        ));
+                    }
+                }
+            }
+
+            if let Ok(new_text) = frontmatter::remove_entry(&path, text, &entry.pattern) {
+                actions.push(action(
+                    &format!("Remove entry '{}'", entry.pattern),
+                    uri,
+                    text,
+                    &new_text,
+                ));
+            }
+        }
+        actions
+    }
+
+    fn open_doc(&self, uri: &str) -> Option<(PathBuf, &String, Frontmatter)> {
+        let path = uri_to_path(uri)?;
+        let text = self.open.get(uri)?;
+        let fm = frontmatter::parse_document(&path, text).ok()??;
+        Some((path, text, fm))
+    }
+}
+
+/// Diagnostics for each problem entry of a doc, on the entry's own line, and
+/// for each code snippet that no longer matches its source
+fn doc_diagnostics(path: &Path, text: &str) -> Vec<Value> {
+    let fm = match frontmatter::parse_document(path, text) {
+        Ok(Some(fm)) if fm.has_driftwatcher() => fm,
+        Ok(_) => return snippet_diagnostics(path, text),
+        Err(e) => {
+            return vec![json!({
+                "range": line_range(0, text.lines().next().unwrap_or("")),
+                "severity": SEVERITY_ERROR,
+                "source": "drifty",
+                "message": format!("{:#}", e),
+            })]
+        }
+    };
+
+    let resolver = match PathResolver::new(path) {
+        Ok(r) => r,
+        Err(e) => {
+            return vec![json!({
+                "range": line_range(0, ""),
+                "severity": SEVERITY_ERROR,
+                "source": "drifty",
+                "message": e.to_string(),
+            })]
+        }
+    };
+
+    let lines: Vec<&str> = text.lines().collect();
+    let mut diagnostics = Vec::new();
+    let mut propagation = Propagation::new();
+    for entry in &fm.entries {
+        let (status, _) = propagation.check_entry(&resolver, entry);
+        let (severity, message) = match status {
+            Status::Current => continue,
+            Status::Drifted => (
+                SEVERITY_WARNING,
+                format!("DRIFTED: files matching '{}' changed", entry.pattern),
+            ),
+            Status::TransitivelyDrifted => {
+                let docs: Vec<String> = propagation
+                    .drifted_docs(&resolver, &entry.pattern)
+                    .iter()
+                    .map(|doc| doc.display().to_string())
+                    .collect();
+                (
+                    SEVERITY_WARNING,
+                    format!(
+                        "TRANSITIVELY_DRIFTED: '{}' watches docs that drifted: {}",
+                        entry.pattern,
+                        docs.join(", ")
+                    ),
+                )
+            }
+            // Only code snippets have this status
+            Status::SnippetDrifted => continue,
+            Status::Missing => (
+                SEVERITY_ERROR,
+                format!("MISSING: '{}' matches no files", entry.pattern),
+            ),
+            Status::Invalid => (
+                SEVERITY_ERROR,
+                format!("INVALID: '{}' has no hash", entry.pattern),
+            ),
+        };
+
+        let line = frontmatter::find_entry_line(path, text, &entry.pattern).unwrap_or(0);
+        diagnostics.push(json!({
+            "range": line_range(line, lines.get(line).unwrap_or(&"")),
+            "severity": severity,
+            "code": status.to_string(),
+            "source": "drifty",
+            "message": message,
+        }));
+    }
+    diagnostics.extend(snippet_diagnostics(path, text));
+    diagnostics
+}
+
+/// Diagnostics on the opening fence of each code snippet that differs from
+/// its source, or whose source is gone
+fn snippet_diagnostics(path: &Path, text: &str) -> Vec<Value> {
+    let resolver = match PathResolver::new(path) {
+        Ok(r) if Format::from_path(path) == Some(Format::Markdown) => r,
+        _ => return Vec::new(),
+    };
+    let lines: Vec<&str> = text.lines().collect();
+
+    snippets::find(text)
+        .into_iter()
+        .filter_map(|snippet| {
+            let status = snippet.check(&resolver, text);
+            let (severity, message) = match status {
+                Status::Current => return None,
+                Status::SnippetDrifted => (
+                    SEVERITY_WARNING,
+                    format!(
+                        "SNIPPET_DRIFTED: code block differs from {}; run 'drifty sync-snippets'",
+                        snippet.label()
+                    ),
+                ),
+                Status::Invalid => (
+                    SEVERITY_ERROR,
+                    format!("INVALID: {} is not a line or range", snippet.label()),
+                ),
+                _ => (
+                    SEVERITY_ERROR,
+                    format!("MISSING: {} not found", snippet.label()),
+                ),
+            };
+            let line = snippet.line - 1;
+            Some(json!({
+                "range": line_range(line, lines.get(line).unwrap_or(&"")),
+                "severity": severity,
+                "code": status.to_string(),
+                "source": "drifty",
+                "message": message,
+            }))
+        })
+        .collect()
+}
+
+fn entry_at_line<'a>(
+    path: &Path,
+    fm: &'a Frontmatter,
+    text: &str,
+    line: usize,
+) -> Option<&'a WatchEntry> {
+    fm.entries
+        .iter()
+        .find(|e| frontmatter::find_entry_line(path, text, &e.pattern) == Some(line))
+}
+
+/// Files elsewhere in the project with the same name as a missing path,
+/// as `$ROOT/` patterns
+fn repoint_candidates(resolver: &PathResolver, pattern: &str) -> Vec<String> {
+    let name = match Path::new(pattern).file_name() {
+        Some(n) if !pattern.contains(['*', '?', '[']) => n,
+        _ => return Vec::new(),
+    };
+
+    let files = hash::collect_files_recursive(resolver.project_root()).unwrap_or_default();
+    files
+        .iter()
+        .filter(|f| f.file_name() == Some(name))
+        .filter_map(|f| resolver.repo_relative(f))
+        .take(MAX_REPOINT_CANDIDATES)
+        .map(|rel| format!("$ROOT/{}", rel.display()))
+        .collect()
+}
+
+/// A quick fix replacing the whole document text
+fn action(title: &str, uri: &str, old_text: &str, new_text: &str) -> Value {
+    let end_line = old_text.lines().count() + 1;
+    json!({
+        "title": title,
+        "kind": "quickfix",
+        "edit": {
+            "changes": {
+                uri: [{
+                    "range": {
+                        "start": {"line": 0, "character": 0},
+                        "end": {"line": end_line, "character": 0},
+                    },
+                    "newText": new_text,
+                }],
+            },
+        },
+    })
+}
+
+fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    })
+}
+
+/// Range covering a whole line (character offsets are UTF-16 code units)
+fn line_range(line: usize, text: &str) -> Value {
+    json!({
+        "start": {"line": line, "character": 0},
+        "end": {"line": line, "character": text.encode_utf16().count()},
+    })
+}
+
+fn text_document_uri(params: &Value) -> Result<String> {
+    params["textDocument"]["uri"]
+        .as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| anyhow!("Missing textDocument.uri"))
+}
+
+/// Convert a `file://` URI to a path, decoding percent escapes
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(hex, 16) {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    Some(PathBuf::from(String::from_utf8(decoded).ok()?))
+}
+
+/// Read one JSON-RPC message framed with a Content-Length header.
+/// Returns None at end of input.
+fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .context("Invalid Content-Length")?,
+            );
+        }
+    }
+
+    let length = length.ok_or_else(|| anyhow!("Missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message<W: Write>(writer: &mut W, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri_to_path() {
+        assert_eq!(
+            uri_to_path("file:///home/me/my%20docs/a.md"),
+            Some(PathBuf::from("/home/me/my docs/a.md"))
+        );
+        assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &json!({"jsonrpc": "2.0", "method": "exit"})).unwrap();
+
+        let mut reader = io::Cursor::new(buffer);
+        let message = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(message["method"], "exit");
+        assert!(read_message(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_source_coverage_refreshed_on_doc_save() {
+        let dir = tempfile::Builder::new().prefix("lsp").tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        std::fs::create_dir(root.join(".git")).unwrap();
+        std::fs::write(root.join("a.rs"), "fn a() {}\n").unwrap();
+        let doc = root.join("doc.md");
+        std::fs::write(&doc, "---\ndriftwatcher:\n  - \"a.rs\": old\n---\n").unwrap();
+
+        let mut server = Server {
+            root: root.clone(),
+            open: HashMap::new(),
+            coverage: None,
+        };
+        let hint = server.source_diagnostics(&root.join("a.rs"));
+        assert_eq!(hint.len(), 1);
+        assert!(hint[0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("doc.md (a.rs) [DRIFTED]"));
+
+        // Entries are cached until the doc is saved
+        std::fs::write(&doc, "# Doc\n").unwrap();
+        assert_eq!(server.source_diagnostics(&root.join("a.rs")).len(), 1);
+        server.refresh_coverage(&doc);
+        assert!(server.source_diagnostics(&root.join("a.rs")).is_empty());
+    }
+
+    #[test]
+    fn test_add_hash_action_for_invalid_entry() {
+        let dir = tempfile::Builder::new().prefix("lsp").tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        std::fs::create_dir(root.join(".git")).unwrap();
+        std::fs::write(root.join("a.rs"), "fn a() {}\n").unwrap();
+        let doc = root.join("doc.md");
+        let uri = format!("file://{}", doc.display());
+        let text = "---\ndriftwatcher:\n  - \"a.rs\":\n---\n# Doc\n";
+
+        let mut server = Server {
+            root: root.clone(),
+            open: HashMap::from([(uri.clone(), text.to_string())]),
+            coverage: None,
+        };
+        let actions = server.code_actions(&uri, 0, 10);
+        let titles: Vec<&str> = actions.iter().filter_map(|a| a["title"].as_str()).collect();
+        assert_eq!(titles, ["Add hash for 'a.rs'", "Remove entry 'a.rs'"]);
+
+        let new_text = actions[0]["edit"]["changes"][&uri][0]["newText"]
+            .as_str()
+            .unwrap();
+        server.open.insert(uri.clone(), new_text.to_string());
+        std::fs::write(&doc, new_text).unwrap();
+        let fm = frontmatter::parse_file(&doc).unwrap().unwrap();
+        let resolver = PathResolver::new(&doc).unwrap();
+        let (status, _) = Propagation::new().check_entry(&resolver, &fm.entries[0]);
+        assert_eq!(status, Status::Current);
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::annotate::{self, DEFAULT_MESSAGE};
+use crate::frontmatter;
+
+/// mdBook preprocessor: reads `[context, book]` JSON from stdin, strips
+/// driftwatcher frontmatter from every chapter, adds the outdated notice to
+/// drifted chapters, and writes the book JSON to stdout.
+///
+/// With `supports`, mdBook asks whether a renderer is supported; every
+/// renderer is, since the preprocessor only edits markdown.
+pub fn run(supports: Option<&str>) -> Result<()> {
+    if supports.is_some() {
+        return Ok(());
+    }
+
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    let (context, mut book): (Value, Value) = serde_json::from_str(&input)
+        .map_err(|e| anyhow!("Invalid mdBook preprocessor input: {}", e))?;
+
+    let root = PathBuf::from(context["root"].as_str().unwrap_or("."));
+    let src = context["config"]["book"]["src"].as_str().unwrap_or("src");
+    let message = context["config"]["preprocessor"]["drifty"]["message"]
+        .as_str()
+        .unwrap_or(DEFAULT_MESSAGE)
+        .to_string();
+
+    let src_dir = root.join(src);
+    // mdBook 0.4 uses "sections", later versions "items"
+    for key in ["sections", "items"] {
+        if let Some(items) = book.get_mut(key).and_then(Value::as_array_mut) {
+            for item in items {
+                process_item(item, &src_dir, &message);
+            }
+        }
+    }
+
+    println!("{}", serde_json::to_string(&book)?);
+    Ok(())
+}
+
+fn process_item(item: &mut Value, src_dir: &Path, message: &str) {
+    let chapter = match item.get_mut("Chapter") {
+        Some(c) => c,
+        None => return,
+    };
+
+    let source = chapter["source_path"]
+        .as_str()
+        .or_else(|| chapter["path"].as_str())
+        .map(|p| src_dir.join(p));
+    if let (Some(source), Some(content)) = (source, chapter["content"].as_str()) {
+        match process_chapter(&source, content, message) {
+            Ok(Some(new_content)) => chapter["content"] = Value::String(new_content),
+            Ok(None) => {}
+            Err(e) => eprintln!("drifty: {}: {}", source.display(), e),
+        }
+    }
+
+    if let Some(sub_items) = chapter.get_mut("sub_items").and_then(Value::as_array_mut) {
+        for sub_item in sub_items {
+            process_item(sub_item, src_dir, message);
+        }
+    }
+}
+
+/// New chapter content, or None when the chapter is not a driftwatcher doc
+fn process_chapter(source: &Path, content: &str, message: &str) -> Result<Option<String>> {
+    let fm = match frontmatter::parse_document(source, content)? {
+        Some(fm) if fm.has_driftwatcher() => fm,
+        _ => return Ok(None),
+    };
+
+    let body = &content[fm.body_start(content)..];
+    if annotate::needs_notice(source, &fm)? {
+        Ok(Some(annotate::insert_notice(body, 0, message)))
+    } else {
+        Ok(Some(annotate::remove_notice(body)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn test_process_item() {
+        let dir = tempfile::Builder::new().prefix("mdbook").tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/a.rs"), "a").unwrap();
+
+        let drifted = "---\ndriftwatcher:\n  - \"a.rs\": old\n---\n# Drifted\n";
+        let plain = "# Plain\n";
+        fs::write(root.join("src/drifted.md"), drifted).unwrap();
+        fs::write(root.join("src/plain.md"), plain).unwrap();
+
+        let mut item = json!({"Chapter": {
+            "content": drifted,
+            "source_path": "drifted.md",
+            "sub_items": [{"Chapter": {"content": plain, "source_path": "plain.md"}}],
+        }});
+        process_item(&mut item, &root.join("src"), "Stale");
+
+        // Frontmatter is stripped and the notice added; other chapters are kept
+        let chapter = &item["Chapter"];
+        assert_eq!(
+            chapter["content"],
+            "<!-- drifty:outdated -->\n> Stale\n<!-- /drifty:outdated -->\n\n# Drifted\n"
+        );
+        assert_eq!(chapter["sub_items"][0]["Chapter"]["content"], plain);
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::document::Format;
+use crate::frontmatter::{self, WatchEntry};
+use crate::git;
+use crate::merge::{self, REVIEW_HASH};
+use crate::paths::PathResolver;
+use crate::status::Propagation;
+
+/// Git merge driver: merges `other` and `base` into `current` in place.
+/// Returns whether the merge was clean; the caller exits with a failure
+/// status otherwise, once the temporary files are gone.
+///
+/// `doc_path` is the path of the document in the work tree (git's `%P`). It
+/// decides the format of the three versions, and is used to resolve patterns
+/// when `recompute` is set.
+pub fn run(
+    base: &Path,
+    current: &Path,
+    other: &Path,
+    doc_path: Option<&Path>,
+    recompute: bool,
+) -> Result<bool> {
+    // git's temporary files have no extension, so the format comes from %P
+    let doc = doc_path.unwrap_or(current);
+    let base_entries = read_entries(doc, base).unwrap_or_default();
+    let (current_entries, other_entries) =
+        match (read_entries(doc, current), read_entries(doc, other)) {
+            (Some(c), Some(o)) => (c, o),
+            _ => {
+                // Not driftwatcher documents on both sides: plain text merge
+                return git::merge_file(current, base, other);
+            }
+        };
+
+    let merged = merge::merge_entries(&base_entries, &current_entries, &other_entries);
+    let resolver = match doc_path {
+        Some(path) if recompute => PathResolver::new(path).ok(),
+        _ => None,
+    };
+
+    let mut entries = merged.entries;
+    for entry in entries.iter_mut() {
+        if !merged.conflicts.contains(&entry.pattern) {
+            continue;
+        }
+        let recomputed = resolver
+            .as_ref()
+            .and_then(|r| Propagation::new().hash_pattern(r, &entry.pattern).ok());
+        match recomputed {
+            Some(hash) => {
+                eprintln!(
+                    "drifty: '{}' acknowledged on both sides, recomputed hash from work tree",
+                    entry.pattern
+                );
+                entry.hash = Some(hash);
+            }
+            None => {
+                eprintln!(
+                    "drifty: '{}' acknowledged on both sides, marked for review",
+                    entry.pattern
+                );
+                entry.hash = Some(REVIEW_HASH.to_string());
+            }
+        }
+    }
+
+    // Merge everything except the entries as plain text, on stripped copies
+    // so git's files are untouched if we fall back
+    let copies = match StrippedCopies::new(doc, [base, current, other]) {
+        Ok(copies) => copies,
+        Err(e) => {
+            eprintln!("drifty: {:#}, falling back to a text merge", e);
+            return git::merge_file(current, base, other);
+        }
+    };
+    let [stripped_base, stripped_current, stripped_other] = &copies.paths;
+    let clean = git::merge_file(stripped_current, stripped_base, stripped_other)?;
+
+    // Put the merged entries back, in order
+    let mut content = frontmatter::read_file(stripped_current)?;
+    // Rust source without its directives has no frontmatter to parse
+    let rust = Format::from_path(doc) == Some(Format::Rust);
+    if !rust && !matches!(frontmatter::parse_content(doc, &content), Ok(Some(_))) {
+        // The rest of the frontmatter conflicted: leave a regular conflict
+        eprintln!("drifty: conflict inside frontmatter, falling back to a text merge");
+        return git::merge_file(current, base, other);
+    }
+    for entry in entries.iter().rev() {
+        let hash = entry.hash.as_deref().unwrap_or("");
+        content = if rust {
+            frontmatter::add_directive(&content, &entry.pattern, hash)
+        } else {
+            frontmatter::add_entry(doc, &content, &entry.pattern, hash)?
+        };
+    }
+    frontmatter::write_file(current, &content)?;
+    Ok(clean)
+}
+
+/// Copies of the merge inputs with their watch entries removed, deleted
+/// when dropped
+struct StrippedCopies {
+    paths: [PathBuf; 3],
+}
+
+impl StrippedCopies {
+    /// Strip each of `originals`, read in the format of `doc`
+    fn new(doc: &Path, originals: [&Path; 3]) -> Result<Self> {
+        let paths = originals.map(|path| {
+            let mut name = path.file_name().unwrap_or_default().to_os_string();
+            name.push(".drifty");
+            path.with_file_name(name)
+        });
+        let copies = Self { paths };
+        for (original, copy) in originals.iter().zip(&copies.paths) {
+            let content = frontmatter::read_file(original)?;
+            // A base without frontmatter (the doc was added on both sides)
+            // has nothing to strip
+            let stripped = match frontmatter::parse_content(doc, &content)? {
+                Some(_) => frontmatter::strip_entries(doc, &content).with_context(|| {
+                    format!("Failed to strip entries from {}", original.display())
+                })?,
+                None => content,
+            };
+            frontmatter::write_file(copy, &stripped)?;
+        }
+        Ok(copies)
+    }
+}
+
+impl Drop for StrippedCopies {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The entries of one version of `doc`
+fn read_entries(doc: &Path, path: &Path) -> Option<Vec<WatchEntry>> {
+    let content = frontmatter::read_file(path).ok()?;
+    match frontmatter::parse_content(doc, &content) {
+        Ok(Some(fm)) if fm.has_driftwatcher() => Some(fm.entries),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Write the three versions as git does, in files without an extension
+    fn versions(dir: &Path, base: &str, ours: &str, theirs: &str) -> [PathBuf; 3] {
+        let paths =
+            ["base", "ours", "theirs"].map(|name| dir.join(format!(".merge_file_{}", name)));
+        for (path, content) in paths.iter().zip([base, ours, theirs]) {
+            fs::write(path, content).unwrap();
+        }
+        paths
+    }
+
+    #[test]
+    fn test_merges_rust_directives() {
+        let dir = TempDir::new().unwrap();
+        let [base, ours, theirs] = versions(
+            dir.path(),
+            "//! Lib\n//! drifty: a.rs = aaa\n\nfn f() {}\n\nfn g() {}\n",
+            "//! Lib\n//! drifty: a.rs = bbb\n\nfn f() { 1 }\n\nfn g() {}\n",
+            "//! Lib\n//! drifty: b.rs = ccc\n//! drifty: a.rs = aaa\n\nfn f() {}\n\nfn g() { 2 }\n",
+        );
+
+        let doc = Path::new("src/lib.rs");
+        assert!(run(&base, &ours, &theirs, Some(doc), false).unwrap());
+        assert_eq!(
+            fs::read_to_string(&ours).unwrap(),
+            "//! Lib\n//! drifty: a.rs = bbb\n//! drifty: b.rs = ccc\n\nfn f() { 1 }\n\nfn g() { 2 }\n"
+        );
+    }
+
+    #[test]
+    fn test_conflict_leaves_no_temporary_files() {
+        let dir = TempDir::new().unwrap();
+        let [base, ours, theirs] = versions(
+            dir.path(),
+            "---\ndriftwatcher:\n  - \"a.rs\": aaa\n---\nbody\n",
+            "---\ndriftwatcher:\n  - \"a.rs\": bbb\n---\nours\n",
+            "---\ndriftwatcher:\n  - \"a.rs\": aaa\n---\ntheirs\n",
+        );
+
+        assert!(!run(&base, &ours, &theirs, Some(Path::new("doc.md")), false).unwrap());
+        let merged = fs::read_to_string(&ours).unwrap();
+        assert!(merged.starts_with("---\ndriftwatcher:\n  - \"a.rs\": bbb\n---\n<<<<<<< ours\n"));
+        assert!(merged.contains(">>>>>>> theirs\n"));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+use crate::document::Format;
+use crate::frontmatter::{self, Style};
+use crate::lock::ProjectLock;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::scanner;
+
+/// Which way `migrate` moves entries
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    ToManifest,
+    ToFrontmatter,
+}
+
+pub fn run(target: Option<&Path>, direction: Direction) -> Result<()> {
+    let docs = scanner::find_documents(target)?;
+    let mut moved = 0;
+
+    for doc_path in docs {
+        let _lock = ProjectLock::acquire(&doc_path)?;
+        let result = match direction {
+            Direction::ToManifest => to_manifest(&doc_path),
+            Direction::ToFrontmatter => to_frontmatter(&doc_path),
+        };
+        match result {
+            Ok(Some(count)) => {
+                moved += 1;
+                println!(
+                    "Moved {} entries of {} {}",
+                    count,
+                    doc_path.display(),
+                    match direction {
+                        Direction::ToManifest => format!("to {}", MANIFEST_FILE),
+                        Direction::ToFrontmatter => "into the doc".to_string(),
+                    }
+                );
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Warning: {}: {}", doc_path.display(), e),
+        }
+    }
+
+    println!("Migrated {} docs.", moved);
+    Ok(())
+}
+
+/// Move a doc's frontmatter entries to the manifest, taking the driftwatcher
+/// key out of the doc. None when the doc has no entries of its own.
+fn to_manifest(doc: &Path) -> Result<Option<usize>> {
+    let content = frontmatter::read_file(doc)?;
+    let fm = match frontmatter::parse_document(doc, &content)? {
+        Some(fm) if fm.has_driftwatcher() && fm.style != Style::Manifest => fm,
+        _ => return Ok(None),
+    };
+
+    let mut manifest = Manifest::for_doc(doc)?
+        .ok_or_else(|| anyhow!("not in a project (no .git directory found)"))?;
+    let count = fm.entries.len();
+    manifest.set_entries(doc, fm.entries)?;
+
+    // The manifest is written first, so the entries are never lost
+    let stripped = frontmatter::remove_driftwatcher(doc, &content)?;
+    manifest.save()?;
+    frontmatter::write_file(doc, &stripped)?;
+    Ok(Some(count))
+}
+
+/// Move a doc's manifest entries into its frontmatter, adding the frontmatter
+/// if needed. None when the manifest doesn't list the doc.
+fn to_frontmatter(doc: &Path) -> Result<Option<usize>> {
+    let Some(mut manifest) = Manifest::for_doc(doc)? else {
+        return Ok(None);
+    };
+    let Some(entries) = manifest.entries(doc).cloned() else {
+        return Ok(None);
+    };
+    let format = Format::from_path(doc)
+        .ok_or_else(|| anyhow!("can't hold driftwatcher entries, leaving it in the manifest"))?;
+
+    let content = frontmatter::read_file(doc)?;
+    let (mut content, existing) = match frontmatter::parse_content(doc, &content)? {
+        Some(fm) if fm.has_driftwatcher() => (content, fm.entries),
+        Some(_) => (
+            frontmatter::add_driftwatcher_to_existing(doc, &content)?,
+            Vec::new(),
+        ),
+        None if format == Format::Rust => (content, Vec::new()),
+        None => (
+            frontmatter::add_empty_frontmatter(&content, format)?,
+            Vec::new(),
+        ),
+    };
+
+    // Added in reverse, since each goes in as the first entry
+    for entry in entries.iter().rev() {
+        let hash = entry.hash.as_deref().unwrap_or("");
+        content = if existing.iter().any(|e| e.pattern == entry.pattern) {
+            frontmatter::update_entry(doc, &content, &entry.pattern, hash)?
+        } else if format == Format::Rust {
+            frontmatter::add_directive(&content, &entry.pattern, hash)
+        } else {
+            frontmatter::add_entry(doc, &content, &entry.pattern, hash)?
+        };
+    }
+
+    frontmatter::write_file(doc, &content)?;
+    manifest.remove(doc);
+    manifest.save()?;
+    Ok(Some(entries.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::Builder::new()
+            .prefix("migrate")
+            .tempdir()
+            .unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        let doc = root.join("guide.md");
+        let original = "---\ntitle: Guide\ndriftwatcher:\n  - \"a.rs\": abc\n---\n# Guide\n";
+        fs::write(&doc, original).unwrap();
+
+        run(Some(root), Direction::ToManifest).unwrap();
+        assert_eq!(
+            fs::read_to_string(&doc).unwrap(),
+            "---\ntitle: Guide\n---\n# Guide\n"
+        );
+        let fm = frontmatter::parse_file(&doc).unwrap().unwrap();
+        assert_eq!(fm.style, Style::Manifest);
+        assert_eq!(fm.entries[0].hash.as_deref(), Some("abc"));
+
+        run(Some(root), Direction::ToFrontmatter).unwrap();
+        assert!(!root.join(MANIFEST_FILE).exists());
+        let fm = frontmatter::parse_file(&doc).unwrap().unwrap();
+        assert_ne!(fm.style, Style::Manifest);
+        assert_eq!(fm.entries[0].pattern, "a.rs");
+        assert_eq!(fm.entries[0].hash.as_deref(), Some("abc"));
+    }
+}
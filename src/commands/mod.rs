@@ -1,5 +1,22 @@
 pub mod add;
+pub mod annotate;
+pub mod apply;
+pub mod blame;
 pub mod check;
+pub mod coverage;
+pub mod export_html;
+pub mod graph;
+pub mod i18n;
 pub mod init;
+pub mod lint;
+pub mod lsp;
+pub mod mdbook;
+pub mod merge_driver;
+pub mod migrate;
+pub mod rebaseline;
 pub mod report;
+pub mod suggest;
+pub mod sync_snippets;
 pub mod validate;
+pub mod watch;
+pub mod who_documents;
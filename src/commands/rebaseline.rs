@@ -0,0 +1,161 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::frontmatter;
+use crate::ignore_revs::{self, IGNORE_REVS_FILE};
+use crate::lock::ProjectLock;
+use crate::paths::PathResolver;
+use crate::scanner;
+use crate::status::{check_entry, keep_docs_state, split_hash, Status};
+use crate::store::EntryStore;
+
+pub fn run(target: Option<&Path>, dry_run: bool) -> Result<()> {
+    let docs = scanner::find_documents(target)?;
+    let mut ignored_by_root = HashMap::new();
+    let mut updated = 0;
+    let mut legacy = 0;
+    let mut still_drifted = 0;
+
+    for doc_path in docs {
+        let fm = match frontmatter::parse_file(&doc_path) {
+            Ok(Some(fm)) if fm.has_driftwatcher() => fm,
+            Ok(_) => continue,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+
+        let resolver = match PathResolver::new(&doc_path) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+
+        let root: PathBuf = resolver.project_root().to_path_buf();
+        if !ignored_by_root.contains_key(&root) {
+            ignored_by_root.insert(root.clone(), ignore_revs::load(&root)?);
+        }
+        let ignored = &ignored_by_root[&root];
+
+        let mut updates = Vec::new();
+        for entry in &fm.entries {
+            let (stored, current_hash) = match (check_entry(&resolver, entry), &entry.hash) {
+                ((Status::Drifted, Some(current)), Some(stored)) => (stored, current),
+                _ => continue,
+            };
+            // Only the files are compared; the state of watched docs stays
+            // acknowledged
+            let stored_hash = split_hash(stored).0;
+
+            // Older versions hashed watched docs with their entries included
+            if resolver
+                .legacy_hash_pattern(&entry.pattern)
+                .is_ok_and(|legacy| legacy == stored_hash)
+            {
+                println!(
+                    "{}: {} stored with the old hash of its docs",
+                    doc_path.display(),
+                    entry.pattern
+                );
+                legacy += 1;
+                updates.push((entry.pattern.clone(), keep_docs_state(current_hash, stored)));
+                continue;
+            }
+
+            match ignore_revs::only_ignored_changes(
+                &resolver,
+                &entry.pattern,
+                stored_hash,
+                &current_hash,
+                ignored,
+            ) {
+                Ok(true) => {
+                    println!(
+                        "{}: {} changed only by ignored revisions",
+                        doc_path.display(),
+                        entry.pattern
+                    );
+                    updates.push((entry.pattern.clone(), keep_docs_state(current_hash, stored)));
+                }
+                Ok(false) => still_drifted += 1,
+                Err(e) => {
+                    eprintln!("Warning: {}: {}: {}", doc_path.display(), entry.pattern, e);
+                    still_drifted += 1;
+                }
+            }
+        }
+
+        if updates.is_empty() {
+            continue;
+        }
+        updated += updates.len();
+        if !dry_run {
+            let _lock = ProjectLock::acquire(&doc_path)?;
+            let mut store = EntryStore::load(&doc_path)?;
+            for (pattern, new_hash) in &updates {
+                store.update(pattern, new_hash)?;
+            }
+            store.save()?;
+        }
+    }
+
+    if legacy == 0 && ignored_by_root.values().all(|revs| revs.is_empty()) {
+        println!(
+            "No ignored revisions found (add commit ids to {}).",
+            IGNORE_REVS_FILE
+        );
+        return Ok(());
+    }
+
+    if dry_run {
+        println!(
+            "\nWould rebaseline {} entries ({} still drifted).",
+            updated, still_drifted
+        );
+    } else {
+        println!(
+            "\nRebaselined {} entries ({} still drifted).",
+            updated, still_drifted
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash;
+    use std::fs;
+
+    #[test]
+    fn test_rebaselines_legacy_doc_hashes() {
+        let dir = tempfile::Builder::new()
+            .prefix("rebaseline")
+            .tempdir()
+            .unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join("a.rs"), "a").unwrap();
+        let reference = "---\ndriftwatcher:\n  - \"a.rs\": abc\n---\n# Reference\n";
+        fs::write(root.join("reference.md"), reference).unwrap();
+
+        // Stored by a version that hashed the reference with its entries
+        let legacy = hash::hash_file(&root.join("reference.md")).unwrap();
+        let tutorial = format!(
+            "---\ndriftwatcher:\n  - \"reference.md\": {}\n---\n# Tutorial\n",
+            legacy
+        );
+        fs::write(root.join("tutorial.md"), tutorial).unwrap();
+
+        run(Some(root), false).unwrap();
+
+        let tutorial = root.join("tutorial.md");
+        let entry = &frontmatter::parse_file(&tutorial).unwrap().unwrap().entries[0];
+        let resolver = PathResolver::new(&tutorial).unwrap();
+        assert_eq!(check_entry(&resolver, entry).0, Status::Current);
+    }
+}
@@ -1,13 +1,19 @@
 use anyhow::Result;
 use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
 
-use crate::frontmatter::{self, WatchEntry};
+use crate::frontmatter;
+use crate::git::{self, CommitInfo};
+use crate::history;
 use crate::paths::PathResolver;
 use crate::scanner;
-use crate::status::Status;
+use crate::snippets;
+use crate::status::{split_hash, Propagation, Status};
 
+use super::export_html;
+
+/// Structured output formats shared by the reporting commands
 #[derive(Debug, Clone, Copy)]
 pub enum OutputFormat {
     Plaintext,
@@ -15,26 +21,84 @@ pub enum OutputFormat {
     Yaml,
 }
 
+/// Output formats of `drifty report`
+#[derive(Debug, Clone, Copy)]
+pub enum ReportFormat {
+    Plaintext,
+    Json,
+    Yaml,
+    Html,
+}
+
 #[derive(Debug)]
-struct DocumentReport {
-    doc_path: PathBuf,
-    results: Vec<(String, Status)>,
+pub struct DocumentReport {
+    pub doc_path: PathBuf,
+    pub results: Vec<EntryReport>,
 }
 
-pub fn run(format: OutputFormat) -> Result<()> {
-    let docs = scanner::find_markdown_files(None)?;
+#[derive(Debug)]
+pub struct EntryReport {
+    pub pattern: String,
+    pub status: Status,
+    /// Files currently matched by the pattern, relative to the project root
+    /// (only with files)
+    pub files: Vec<PathBuf>,
+    /// Last commit whose files match the stored hash (only with history)
+    pub acknowledged: Option<CommitInfo>,
+    /// Line of the code block, for a code snippet rather than an entry
+    pub snippet_line: Option<usize>,
+}
+
+pub fn run(format: ReportFormat) -> Result<()> {
+    // Only the HTML report shows files and history
+    let html = matches!(format, ReportFormat::Html);
+    let reports = collect(None, html, html)?;
+    let has_problems = reports
+        .iter()
+        .flat_map(|r| &r.results)
+        .any(|e| e.status.is_problem());
+
+    match format {
+        ReportFormat::Plaintext => print_plaintext(&reports),
+        ReportFormat::Json => print_json(&reports),
+        ReportFormat::Yaml => print_yaml(&reports),
+        ReportFormat::Html => print!("{}", export_html::render(&reports)),
+    }
+
+    if has_problems {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Check every doc under `target` and collect the status of each entry and
+/// code snippet.
+/// With `with_files`, also list the files each one matches, and with
+/// `with_history`, look up the last acknowledged commit in git.
+pub fn collect(
+    target: Option<&Path>,
+    with_files: bool,
+    with_history: bool,
+) -> Result<Vec<DocumentReport>> {
+    let docs = scanner::find_documents(target)?;
     let mut reports = Vec::new();
-    let mut has_problems = false;
+    let mut propagation = Propagation::new();
 
     for doc_path in docs {
-        let fm = match frontmatter::parse_file(&doc_path) {
-            Ok(Some(fm)) if fm.has_driftwatcher() => fm,
-            Ok(_) => continue,
+        let entries = match frontmatter::parse_file(&doc_path) {
+            Ok(Some(fm)) => fm.entries,
+            Ok(None) => Vec::new(),
             Err(e) => {
                 eprintln!("Warning: {}: {}", doc_path.display(), e);
                 continue;
             }
         };
+        // Code snippets are checked whether or not the doc has entries
+        let snippets = snippets::check_doc(&doc_path);
+        if entries.is_empty() && snippets.is_empty() {
+            continue;
+        }
 
         let resolver = match PathResolver::new(&doc_path) {
             Ok(r) => r,
@@ -46,12 +110,50 @@ pub fn run(format: OutputFormat) -> Result<()> {
 
         let mut results = Vec::new();
 
-        for entry in &fm.entries {
-            let status = check_entry(&resolver, entry);
-            if status.is_problem() {
-                has_problems = true;
-            }
-            results.push((entry.pattern.clone(), status));
+        for entry in &entries {
+            let (status, current_hash) = propagation.check_entry(&resolver, entry);
+            let acknowledged = match (&entry.hash, &current_hash) {
+                (Some(stored), Some(current)) if with_history => {
+                    // History only knows the files part of hashes
+                    let (stored, current) = (split_hash(stored).0, split_hash(current).0);
+                    history::trace(&resolver, &entry.pattern, stored, current)
+                        .ok()
+                        .and_then(|h| h.acknowledged)
+                        .and_then(|id| git::commit_info(resolver.project_root(), &id).ok())
+                }
+                _ => None,
+            };
+            results.push(EntryReport {
+                pattern: entry.pattern.clone(),
+                status,
+                files: if with_files {
+                    resolver
+                        .resolve_files(&entry.pattern)
+                        .unwrap_or_default()
+                        .iter()
+                        .filter_map(|f| resolver.repo_relative(f))
+                        .collect()
+                } else {
+                    Vec::new()
+                },
+                acknowledged,
+                snippet_line: None,
+            });
+        }
+
+        for (snippet, status) in snippets {
+            results.push(EntryReport {
+                pattern: snippet.label(),
+                status,
+                files: with_files
+                    .then(|| snippet.source_path(&resolver))
+                    .flatten()
+                    .and_then(|f| resolver.repo_relative(&f))
+                    .into_iter()
+                    .collect(),
+                acknowledged: None,
+                snippet_line: Some(snippet.line),
+            });
         }
 
         if !results.is_empty() {
@@ -59,47 +161,7 @@ pub fn run(format: OutputFormat) -> Result<()> {
         }
     }
 
-    match format {
-        OutputFormat::Plaintext => print_plaintext(&reports),
-        OutputFormat::Json => print_json(&reports),
-        OutputFormat::Yaml => print_yaml(&reports),
-    }
-
-    if has_problems {
-        process::exit(1);
-    }
-
-    Ok(())
-}
-
-fn check_entry(resolver: &PathResolver, entry: &WatchEntry) -> Status {
-    // Check if entry has a hash
-    let stored_hash = match &entry.hash {
-        Some(h) => h,
-        None => return Status::Invalid,
-    };
-
-    // Check if files exist
-    let paths = match resolver.resolve(&entry.pattern) {
-        Ok(p) => p,
-        Err(_) => return Status::Missing,
-    };
-
-    if paths.is_empty() {
-        return Status::Missing;
-    }
-
-    // Compute current hash
-    let current_hash = match resolver.hash_pattern(&entry.pattern) {
-        Ok(h) => h,
-        Err(_) => return Status::Missing,
-    };
-
-    if current_hash == *stored_hash {
-        Status::Current
-    } else {
-        Status::Drifted
-    }
+    Ok(reports)
 }
 
 fn print_plaintext(reports: &[DocumentReport]) {
@@ -110,8 +172,14 @@ fn print_plaintext(reports: &[DocumentReport]) {
 
     for report in reports {
         println!("{}", report.doc_path.display());
-        for (pattern, status) in &report.results {
-            println!("  {:8} {}", status, pattern);
+        for entry in &report.results {
+            match entry.snippet_line {
+                Some(line) => println!(
+                    "  {:8} {} (code block, line {})",
+                    entry.status, entry.pattern, line
+                ),
+                None => println!("  {:8} {}", entry.status, entry.pattern),
+            }
         }
         println!();
     }
@@ -121,7 +189,11 @@ fn print_json(reports: &[DocumentReport]) {
     let map: BTreeMap<String, BTreeMap<String, Status>> = reports
         .iter()
         .map(|r| {
-            let inner: BTreeMap<_, _> = r.results.iter().map(|(p, s)| (p.clone(), *s)).collect();
+            let inner: BTreeMap<_, _> = r
+                .results
+                .iter()
+                .map(|e| (e.pattern.clone(), e.status))
+                .collect();
             (r.doc_path.display().to_string(), inner)
         })
         .collect();
@@ -136,7 +208,7 @@ fn print_yaml(reports: &[DocumentReport]) {
             let inner: BTreeMap<_, _> = r
                 .results
                 .iter()
-                .map(|(p, s)| (p.clone(), s.to_string()))
+                .map(|e| (e.pattern.clone(), e.status.to_string()))
                 .collect();
             (r.doc_path.display().to_string(), inner)
         })
@@ -144,3 +216,83 @@ fn print_yaml(reports: &[DocumentReport]) {
 
     println!("{}", serde_yaml::to_string(&map).unwrap());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+
+    fn commit(root: &Path, message: &str) {
+        for args in [
+            vec!["add", "-A"],
+            vec![
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-qm",
+                message,
+            ],
+        ] {
+            let status = Command::new("git").args(&args).current_dir(root).status();
+            assert!(status.unwrap().success());
+        }
+    }
+
+    /// Store the current hash of every entry of a doc
+    fn acknowledge(doc: &Path) {
+        let resolver = PathResolver::new(doc).unwrap();
+        let mut content = frontmatter::read_file(doc).unwrap();
+        for entry in frontmatter::parse_file(doc).unwrap().unwrap().entries {
+            let hash = Propagation::new()
+                .hash_pattern(&resolver, &entry.pattern)
+                .unwrap();
+            content = frontmatter::update_entry(doc, &content, &entry.pattern, &hash).unwrap();
+        }
+        fs::write(doc, content).unwrap();
+    }
+
+    #[test]
+    fn test_history_of_entry_watching_a_doc() {
+        let dir = tempfile::Builder::new().prefix("report").tempdir().unwrap();
+        let root = dir.path();
+        assert!(Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(root)
+            .status()
+            .unwrap()
+            .success());
+        fs::write(root.join("lib.rs"), "fn a() {}\n").unwrap();
+        fs::write(
+            root.join("ref.md"),
+            "---\ndriftwatcher:\n  - \"lib.rs\": x\n---\n# Ref\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("tut.md"),
+            "---\ndriftwatcher:\n  - \"ref.md\": x\n---\n# Tut\n",
+        )
+        .unwrap();
+        acknowledge(&root.join("ref.md"));
+        acknowledge(&root.join("tut.md"));
+        commit(root, "acknowledge");
+
+        fs::write(
+            root.join("ref.md"),
+            fs::read_to_string(root.join("ref.md")).unwrap() + "More\n",
+        )
+        .unwrap();
+        commit(root, "edit ref");
+
+        let reports = collect(Some(root), true, true).unwrap();
+        let tut = reports
+            .iter()
+            .find(|r| r.doc_path.ends_with("tut.md"))
+            .unwrap();
+        assert_eq!(tut.results[0].status, Status::Drifted);
+        let acknowledged = tut.results[0].acknowledged.as_ref().unwrap();
+        assert_eq!(acknowledged.summary, "acknowledge");
+    }
+}
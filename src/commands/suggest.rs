@@ -0,0 +1,118 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::document::Format;
+use crate::frontmatter;
+use crate::paths::{self, PathResolver};
+use crate::references::{self, Reference};
+
+/// An entry proposed for a reference in the doc
+struct Suggestion {
+    pattern: String,
+    reference: Reference,
+}
+
+pub fn run(doc_file: &Path, apply: bool) -> Result<()> {
+    // References are found with Markdown syntax
+    if Format::from_path(doc_file) != Some(Format::Markdown) {
+        return Err(anyhow!(
+            "{} is not a Markdown doc; suggest only reads Markdown references",
+            doc_file.display()
+        ));
+    }
+    let content = frontmatter::read_file(doc_file)?;
+    let fm = frontmatter::parse_document(doc_file, &content)?;
+    let resolver = PathResolver::new(doc_file)?;
+
+    // Files the doc watches already, and the doc itself
+    let mut watched: BTreeSet<PathBuf> = fm
+        .iter()
+        .flat_map(|fm| &fm.entries)
+        .flat_map(|e| resolver.resolve_files(&e.pattern).unwrap_or_default())
+        .map(|f| paths::absolute(&f))
+        .collect();
+    watched.insert(paths::absolute(doc_file));
+
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+    for reference in references::extract_body(doc_file, &content) {
+        let Some(pattern) = resolve(&resolver, &reference.path) else {
+            continue;
+        };
+        let files: Vec<PathBuf> = resolver
+            .resolve_files(&pattern)
+            .unwrap_or_default()
+            .iter()
+            .map(|f| paths::absolute(f))
+            .collect();
+        if files.is_empty() || files.iter().all(|f| watched.contains(f)) {
+            continue;
+        }
+        watched.extend(files);
+        suggestions.push(Suggestion { pattern, reference });
+    }
+
+    if suggestions.is_empty() {
+        println!("No new entries to suggest for {}.", doc_file.display());
+        return Ok(());
+    }
+
+    if apply {
+        // Added in reverse, since each goes in as the first entry
+        for suggestion in suggestions.iter().rev() {
+            super::add::run(doc_file, &suggestion.pattern, false, false)?;
+        }
+        return Ok(());
+    }
+
+    println!("Suggested entries for {}:", doc_file.display());
+    for suggestion in &suggestions {
+        let reference = &suggestion.reference;
+        println!(
+            "  {}  (line {}, {} '{}')",
+            suggestion.pattern,
+            reference.line,
+            reference.kind.name(),
+            reference.path
+        );
+    }
+    println!(
+        "\nRun 'drifty suggest --apply {}' to add them.",
+        doc_file.display()
+    );
+    Ok(())
+}
+
+/// The pattern watching a referenced path. Other docs are not suggested,
+/// since links between docs are mostly navigation.
+fn resolve(resolver: &PathResolver, path: &str) -> Option<String> {
+    let pattern = resolver.mention_pattern(path)?;
+
+    let is_doc = resolver
+        .resolve(&pattern)
+        .ok()?
+        .iter()
+        .any(|p| Format::from_path(p).is_some_and(|f| f != Format::Rust));
+    (!is_doc).then_some(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_only_reads_markdown() {
+        let dir = tempfile::Builder::new()
+            .prefix("suggest")
+            .tempdir()
+            .unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        let doc = dir.path().join("guide.rst");
+        fs::write(&doc, "See ``src/a.rs``.\n").unwrap();
+
+        let err = run(&doc, true).unwrap_err().to_string();
+        assert!(err.contains("not a Markdown doc"));
+        assert_eq!(fs::read_to_string(&doc).unwrap(), "See ``src/a.rs``.\n");
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::document::Format;
+use crate::frontmatter;
+use crate::lock::ProjectLock;
+use crate::paths::PathResolver;
+use crate::scanner;
+use crate::snippets;
+
+pub fn run(target: Option<&Path>, dry_run: bool) -> Result<()> {
+    let docs = scanner::find_documents(target)?;
+    let mut synced = 0;
+
+    for doc_path in docs {
+        // Snippets are Markdown fenced code blocks
+        if Format::from_path(&doc_path) != Some(Format::Markdown) {
+            continue;
+        }
+
+        let _lock = ProjectLock::acquire(&doc_path)?;
+        let (content, resolver) = match (
+            frontmatter::read_file(&doc_path),
+            PathResolver::new(&doc_path),
+        ) {
+            (Ok(content), Ok(resolver)) => (content, resolver),
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                continue;
+            }
+        };
+
+        for snippet in snippets::find(&content) {
+            if let Err(e) = snippet.source(&resolver) {
+                eprintln!("Warning: {}:{}: {}", doc_path.display(), snippet.line, e);
+            }
+        }
+
+        let (new_content, changed) = snippets::sync(&content, &resolver);
+        if changed == 0 {
+            continue;
+        }
+        if !dry_run {
+            frontmatter::write_file(&doc_path, &new_content)?;
+        }
+        synced += changed;
+        println!(
+            "{} {} snippet(s) in {}",
+            if dry_run { "Would update" } else { "Updated" },
+            changed,
+            doc_path.display()
+        );
+    }
+
+    if synced == 0 {
+        println!("All code snippets match their source.");
+    }
+    Ok(())
+}
@@ -1,23 +1,85 @@
 use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
 use std::process;
 
+use crate::document::Format;
 use crate::frontmatter;
-use crate::paths::PathResolver;
+use crate::manifest::Manifest;
+use crate::paths::{self, PathResolver};
+use crate::references::{self, Kind, Reference};
 use crate::scanner;
+use crate::status::Propagation;
 
-pub fn run() -> Result<()> {
-    let docs = scanner::find_markdown_files(None)?;
-    let mut all_valid = true;
+use super::report::OutputFormat;
+
+/// Something wrong in a doc or the manifest
+#[derive(Debug, Serialize)]
+struct Problem {
+    file: String,
+    /// 1-based position, for problems in a doc's body
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<usize>,
+    message: String,
+}
+
+impl Problem {
+    fn new(file: &Path, message: String) -> Self {
+        Self {
+            file: file.display().to_string(),
+            line: None,
+            column: None,
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Validation {
+    valid: bool,
+    /// Docs whose driftwatcher entries were checked
+    checked: usize,
+    /// Docs whose links were checked (with `--links`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    links_checked: Option<usize>,
+    problems: Vec<Problem>,
+}
+
+pub fn run(links: bool, format: OutputFormat) -> Result<()> {
+    let docs = scanner::find_documents(None)?;
+    let mut problems = Vec::new();
     let mut checked_count = 0;
+    let mut links_checked = 0;
+    let mut propagation = Propagation::new();
+
+    // Docs listed in the manifest must still exist
+    if let Ok(root) = paths::find_project_root(Path::new(".")) {
+        let manifest = Manifest::load(&root)?;
+        for doc in manifest.docs().iter().filter(|doc| !doc.is_file()) {
+            problems.push(Problem::new(
+                manifest.path(),
+                format!("Lists '{}', which does not exist", doc.display()),
+            ));
+        }
+    }
 
     for doc_path in docs {
+        if links && Format::from_path(&doc_path) == Some(Format::Markdown) {
+            links_checked += 1;
+            problems.extend(broken_links(&doc_path));
+        }
+
         // Try to parse frontmatter
         let fm = match frontmatter::parse_file(&doc_path) {
             Ok(Some(fm)) => fm,
             Ok(None) => continue, // No frontmatter, skip
             Err(e) => {
-                eprintln!("{}: Invalid YAML - {}", doc_path.display(), e);
-                all_valid = false;
+                problems.push(Problem::new(
+                    &doc_path,
+                    format!("Invalid frontmatter - {}", e),
+                ));
                 continue;
             }
         };
@@ -30,8 +92,7 @@ pub fn run() -> Result<()> {
         let resolver = match PathResolver::new(&doc_path) {
             Ok(r) => r,
             Err(e) => {
-                eprintln!("{}: {}", doc_path.display(), e);
-                all_valid = false;
+                problems.push(Problem::new(&doc_path, e.to_string()));
                 continue;
             }
         };
@@ -39,50 +100,231 @@ pub fn run() -> Result<()> {
         for entry in &fm.entries {
             // Check has hash (INVALID status check)
             if entry.hash.is_none() {
-                eprintln!(
-                    "{}: Entry '{}' has no hash",
-                    doc_path.display(),
-                    entry.pattern
-                );
-                all_valid = false;
+                problems.push(Problem::new(
+                    &doc_path,
+                    format!("Entry '{}' has no hash", entry.pattern),
+                ));
             }
 
             // Check paths exist / pattern matches files
             match resolver.resolve(&entry.pattern) {
-                Ok(paths) if paths.is_empty() => {
-                    eprintln!(
-                        "{}: Pattern '{}' matches no files",
-                        doc_path.display(),
-                        entry.pattern
-                    );
-                    all_valid = false;
-                }
-                Err(e) => {
-                    eprintln!(
-                        "{}: Pattern '{}' - {}",
-                        doc_path.display(),
-                        entry.pattern,
-                        e
-                    );
-                    all_valid = false;
-                }
+                Ok(paths) if paths.is_empty() => problems.push(Problem::new(
+                    &doc_path,
+                    format!("Pattern '{}' matches no files", entry.pattern),
+                )),
+                Err(e) => problems.push(Problem::new(
+                    &doc_path,
+                    format!("Pattern '{}' - {}", entry.pattern, e),
+                )),
                 Ok(_) => {} // Valid
             }
+
+            // Follows watched docs, meeting any cycles between them
+            propagation.drifted_docs(&resolver, &entry.pattern);
         }
     }
 
-    if checked_count == 0 {
-        println!("No driftwatcher entries found.");
-        return Ok(());
+    // Cycles still terminate, so they are only worth a warning
+    let cwd = paths::absolute(Path::new("."));
+    for cycle in propagation.cycles() {
+        let names: Vec<String> = cycle
+            .iter()
+            .map(|doc| doc.strip_prefix(&cwd).unwrap_or(doc).display().to_string())
+            .collect();
+        eprintln!(
+            "Warning: Docs watch each other in a cycle: {}",
+            names.join(" -> ")
+        );
+    }
+
+    let validation = Validation {
+        valid: problems.is_empty(),
+        checked: checked_count,
+        links_checked: links.then_some(links_checked),
+        problems,
+    };
+    match format {
+        OutputFormat::Plaintext => print_plaintext(&validation),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&validation)?),
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&validation)?),
     }
 
-    if all_valid {
+    if !validation.valid {
+        process::exit(1)
+    }
+    Ok(())
+}
+
+fn print_plaintext(validation: &Validation) {
+    for problem in &validation.problems {
+        match (problem.line, problem.column) {
+            (Some(line), Some(column)) => {
+                eprintln!("{}:{}:{}: {}", problem.file, line, column, problem.message)
+            }
+            _ => eprintln!("{}: {}", problem.file, problem.message),
+        }
+    }
+    if !validation.valid {
+        return;
+    }
+
+    if let Some(count) = validation.links_checked {
+        println!("No broken links ({} doc(s) checked).", count);
+    }
+    if validation.checked == 0 {
+        println!("No driftwatcher entries found.");
+    } else {
         println!(
             "All driftwatcher entries are valid ({} file(s) checked).",
-            checked_count
+            validation.checked
         );
-        Ok(())
-    } else {
-        process::exit(1)
+    }
+}
+
+/// Links, images and repository paths in code spans of a Markdown doc's body
+/// that point at files that don't exist
+fn broken_links(doc_path: &Path) -> Vec<Problem> {
+    let (content, resolver) = match (
+        frontmatter::read_file(doc_path),
+        PathResolver::new(doc_path),
+    ) {
+        (Ok(content), Ok(resolver)) => (content, resolver),
+        (Err(e), _) | (_, Err(e)) => return vec![Problem::new(doc_path, e.to_string())],
+    };
+
+    let mut refs: Vec<Reference> = references::extract_body(doc_path, &content)
+        .into_iter()
+        .filter(|reference| is_broken(&resolver, reference))
+        .collect();
+    refs.sort_by_key(|reference| (reference.line, reference.column));
+
+    refs.into_iter()
+        .map(|reference| {
+            let what = match reference.kind {
+                Kind::Link => "Link target",
+                Kind::Image => "Image",
+                _ => "Path",
+            };
+            Problem {
+                file: doc_path.display().to_string(),
+                line: Some(reference.line),
+                column: Some(reference.column),
+                message: format!("{} '{}' does not exist", what, reference.path),
+            }
+        })
+        .collect()
+}
+
+/// Whether a reference points at a missing local file. Links and images are
+/// relative to the doc, or to the project root with `$ROOT/` or a leading
+/// `/`. Code spans only count when their first directory exists, so they
+/// read as repository paths rather than MIME types or paths elsewhere.
+fn is_broken(resolver: &PathResolver, reference: &Reference) -> bool {
+    let path = percent_decode(&reference.path);
+    match reference.kind {
+        Kind::Link | Kind::Image => {
+            // Targets are plain paths, so `[` and `*` are not glob syntax
+            let path = match path.strip_prefix('/') {
+                Some(rooted) => format!("$ROOT/{}", rooted),
+                None => path,
+            };
+            !resolver.literal_path(&path).exists()
+        }
+        Kind::Code => {
+            let Some((first, _)) = path.split_once('/') else {
+                return false;
+            };
+            if path.starts_with(['/', '~']) || first.is_empty() {
+                return false;
+            }
+            let in_repo = resolver.mention_pattern(first).is_some() || first == "$ROOT";
+            in_repo && resolver.mention_pattern(&path).is_none()
+        }
+        Kind::FenceHeader => false,
+    }
+}
+
+/// Decode `%20`-style escapes in a link target
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).unwrap_or_else(|_| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_broken_links() {
+        let dir = tempfile::Builder::new()
+            .prefix("validate")
+            .tempdir()
+            .unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("src/a.rs"), "").unwrap();
+        fs::write(root.join("docs/my file.md"), "").unwrap();
+        fs::write(root.join("docs/file[1].md"), "").unwrap();
+
+        let doc = root.join("docs/guide.md");
+        let content = "\
+---
+driftwatcher:
+  - \"../src/a.rs\": x
+---
+# Guide
+[ok](../src/a.rs) and [bad](missing.md)
+![img](img/none.png)
+[root]($ROOT/src/a.rs) [slash](/src/a.rs) [gone](/src/none.rs)
+[space](my%20file.md) [brackets](file[1].md)
+`nodir/a.rs` and `src/none.rs`
+";
+        fs::write(&doc, content).unwrap();
+
+        let problems: Vec<(usize, usize, String)> = broken_links(&doc)
+            .into_iter()
+            .map(|p| (p.line.unwrap(), p.column.unwrap(), p.message))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                (6, 29, "Link target 'missing.md' does not exist".to_string()),
+                (7, 8, "Image 'img/none.png' does not exist".to_string()),
+                (
+                    8,
+                    50,
+                    "Link target '/src/none.rs' does not exist".to_string()
+                ),
+                (10, 19, "Path 'src/none.rs' does not exist".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("my%20file.md"), "my file.md");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("a%zz"), "a%zz");
     }
 }
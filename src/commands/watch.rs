@@ -0,0 +1,261 @@
+use anyhow::Result;
+use notify::RecursiveMode;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::frontmatter::{self, WatchEntry};
+use crate::manifest::MANIFEST_FILE;
+use crate::paths::{self, PathResolver};
+use crate::scanner;
+use crate::status::{Propagation, Status};
+use crate::watch::{Changes, WatchSet, Watcher};
+
+/// Last known state of one watch entry
+#[derive(Debug)]
+struct EntryState {
+    status: Status,
+    /// Absolute paths of the files the entry currently covers
+    files: BTreeSet<PathBuf>,
+    /// Absolute directory in which new files could start matching the entry
+    scope: Option<PathBuf>,
+    /// Whether files in subdirectories of the scope can match (`**`)
+    recursive: bool,
+    /// Whether any covered file is a doc with entries, whose own watched files
+    /// can make the entry transitively drifted
+    watches_docs: bool,
+}
+
+/// Entries are keyed by (doc path, pattern)
+type Entries = BTreeMap<(PathBuf, String), EntryState>;
+
+pub fn run(target: Option<&Path>, json: bool, poll: bool, interval: Duration) -> Result<()> {
+    let mut watcher = Watcher::new(poll, interval);
+    let mut entries = Entries::new();
+    let mut docs = scanner::find_documents(target)?;
+
+    let mut propagation = Propagation::new();
+    for doc in &docs {
+        for (key, state) in evaluate_doc(doc, &mut propagation) {
+            emit(json, &key, None, Some(state.status));
+            entries.insert(key, state);
+        }
+    }
+    watcher.watch(&watched_dirs(&docs, &entries))?;
+
+    if !json {
+        let problems = entries
+            .values()
+            .filter(|s| s.status != Status::Current)
+            .count();
+        println!(
+            "Watching {} entries in {} docs ({}), {} need attention. Press Ctrl-C to stop.",
+            entries.len(),
+            docs.len(),
+            watcher.backend(),
+            problems
+        );
+    }
+
+    loop {
+        // When events were lost, every doc is evaluated again
+        let (changed, rescan) = match watcher.wait()? {
+            Changes::Paths(changed) => (changed, false),
+            Changes::Rescan => (BTreeSet::new(), true),
+        };
+        docs = scanner::find_documents(target)?;
+
+        // Any doc may have entries in the manifest
+        let manifest_changed = changed
+            .iter()
+            .any(|path| path.file_name().is_some_and(|name| name == MANIFEST_FILE));
+
+        let mut propagation = Propagation::new();
+        let mut updated = Entries::new();
+        let mut reevaluated_docs = BTreeSet::new();
+        for doc in &docs {
+            let is_new = !entries.keys().any(|(d, _)| d == doc);
+            if is_new || rescan || manifest_changed || changed.contains(&paths::absolute(doc)) {
+                updated.extend(evaluate_doc(doc, &mut propagation));
+                reevaluated_docs.insert(doc.clone());
+            }
+        }
+
+        // Entries of removed or re-parsed docs that no longer exist
+        let removed: Vec<(PathBuf, String)> = entries
+            .keys()
+            .filter(|(doc, _)| reevaluated_docs.contains(doc) || !docs.contains(doc))
+            .filter(|key| !updated.contains_key(*key))
+            .cloned()
+            .collect();
+        for key in removed {
+            if let Some(old) = entries.remove(&key) {
+                emit(json, &key, Some(old.status), None);
+            }
+        }
+
+        // Re-evaluate only entries whose files or scope were touched, and
+        // entries watching docs, which drift along with them
+        let mut affected: BTreeMap<&PathBuf, BTreeSet<&String>> = BTreeMap::new();
+        for ((doc, pattern), state) in entries.iter() {
+            if !reevaluated_docs.contains(doc)
+                && (state.watches_docs || is_affected(state, &changed))
+            {
+                affected.entry(doc).or_default().insert(pattern);
+            }
+        }
+        for (doc, patterns) in affected {
+            updated.extend(
+                evaluate_doc(doc, &mut propagation)
+                    .into_iter()
+                    .filter(|((_, pattern), _)| patterns.contains(pattern)),
+            );
+        }
+
+        for (key, state) in updated {
+            let old_status = entries.get(&key).map(|s| s.status);
+            if old_status != Some(state.status) {
+                emit(json, &key, old_status, Some(state.status));
+            }
+            entries.insert(key, state);
+        }
+
+        watcher.watch(&watched_dirs(&docs, &entries))?;
+    }
+}
+
+fn is_affected(state: &EntryState, changed: &BTreeSet<PathBuf>) -> bool {
+    changed.iter().any(|path| {
+        state.files.contains(path)
+            || state
+                .scope
+                .as_ref()
+                .map(|scope| path.starts_with(scope))
+                .unwrap_or(false)
+    })
+}
+
+fn evaluate_doc(doc: &Path, propagation: &mut Propagation) -> Vec<((PathBuf, String), EntryState)> {
+    let fm = match frontmatter::parse_file(doc) {
+        Ok(Some(fm)) if fm.has_driftwatcher() => fm,
+        Ok(_) => return Vec::new(),
+        Err(e) => {
+            eprintln!("Warning: {}: {}", doc.display(), e);
+            return Vec::new();
+        }
+    };
+    let Ok(resolver) = PathResolver::new(doc) else {
+        return Vec::new();
+    };
+
+    fm.entries
+        .iter()
+        .map(|entry| {
+            let state = evaluate_entry(&resolver, propagation, entry);
+            ((doc.to_path_buf(), entry.pattern.clone()), state)
+        })
+        .collect()
+}
+
+fn evaluate_entry(
+    resolver: &PathResolver,
+    propagation: &mut Propagation,
+    entry: &WatchEntry,
+) -> EntryState {
+    let pattern = entry.pattern.as_str();
+    let (status, _) = propagation.check_entry(resolver, entry);
+    let files: BTreeSet<PathBuf> = resolver
+        .resolve_files(pattern)
+        .unwrap_or_default()
+        .iter()
+        .map(|f| paths::absolute(f))
+        .collect();
+    let watches_docs = !propagation.watched_docs(resolver, pattern).is_empty();
+    let scope = resolver
+        .pathspec(pattern)
+        .map(|rel| paths::absolute(&resolver.project_root().join(rel)));
+
+    EntryState {
+        status,
+        files,
+        scope,
+        recursive: pattern.contains("**"),
+        watches_docs,
+    }
+}
+
+/// Directories to watch: every doc's directory, the directory of every
+/// covered file, and each entry's scope, with its subdirectories when new
+/// files can match anywhere below it
+fn watched_dirs(docs: &[PathBuf], entries: &Entries) -> WatchSet {
+    let mut dirs = WatchSet::new();
+    let mut add = |dir: &Path, mode: RecursiveMode| {
+        let current = dirs
+            .entry(dir.to_path_buf())
+            .or_insert(RecursiveMode::NonRecursive);
+        if mode == RecursiveMode::Recursive {
+            *current = mode;
+        }
+    };
+    for doc in docs {
+        if let Some(parent) = paths::absolute(doc).parent() {
+            add(parent, RecursiveMode::NonRecursive);
+        }
+        // The project root, for the manifest
+        if let Ok(root) = PathResolver::new(doc) {
+            add(
+                &paths::absolute(root.project_root()),
+                RecursiveMode::NonRecursive,
+            );
+        }
+    }
+    for state in entries.values() {
+        for file in &state.files {
+            if let Some(parent) = file.parent() {
+                add(parent, RecursiveMode::NonRecursive);
+            }
+        }
+        if let Some(scope) = &state.scope {
+            if scope.is_dir() {
+                let mode = if state.recursive {
+                    RecursiveMode::Recursive
+                } else {
+                    RecursiveMode::NonRecursive
+                };
+                add(scope, mode);
+            } else if let Some(parent) = scope.parent() {
+                add(parent, RecursiveMode::NonRecursive);
+            }
+        }
+    }
+    dirs
+}
+
+/// Print a status transition. `None` means the entry did not exist before
+/// (or no longer exists).
+fn emit(json: bool, key: &(PathBuf, String), from: Option<Status>, to: Option<Status>) {
+    let (doc, pattern) = key;
+    if json {
+        println!(
+            "{}",
+            json!({
+                "doc": doc.display().to_string(),
+                "pattern": pattern,
+                "from": from,
+                "to": to,
+            })
+        );
+        return;
+    }
+
+    match (from, to) {
+        (None, Some(Status::Current)) => {}
+        (None, Some(to)) => println!("{:8} {}: {}", to, doc.display(), pattern),
+        (Some(from), Some(to)) => {
+            println!("{} -> {} {}: {}", from, to, doc.display(), pattern)
+        }
+        (Some(_), None) => println!("REMOVED  {}: {}", doc.display(), pattern),
+        (None, None) => {}
+    }
+}
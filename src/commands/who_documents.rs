@@ -0,0 +1,156 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::frontmatter::{self, WatchEntry};
+use crate::paths::{self, PathResolver};
+use crate::scanner;
+use crate::status::{Propagation, Status};
+
+use super::report::OutputFormat;
+
+/// A driftwatcher entry that covers a source file
+#[derive(Debug, Clone, Serialize)]
+pub struct Coverage {
+    pub doc: String,
+    pub pattern: String,
+    pub status: Status,
+}
+
+pub fn run(files: &[PathBuf], format: OutputFormat) -> Result<()> {
+    if files.is_empty() {
+        return Err(anyhow!("No source files given"));
+    }
+
+    // Map each requested file (absolute) back to how the user spelled it
+    let wanted: BTreeMap<PathBuf, String> = files
+        .iter()
+        .map(|f| {
+            if !f.exists() {
+                eprintln!("Warning: {} does not exist", f.display());
+            }
+            (paths::absolute(f), f.display().to_string())
+        })
+        .collect();
+    let mut results: BTreeMap<String, Vec<Coverage>> = wanted
+        .values()
+        .map(|name| (name.clone(), Vec::new()))
+        .collect();
+
+    let absolute: Vec<PathBuf> = wanted.keys().cloned().collect();
+    for (path, coverage) in find_coverage(None, &absolute)? {
+        results.insert(wanted[&path].clone(), coverage);
+    }
+
+    match format {
+        OutputFormat::Plaintext => print_plaintext(&results),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        OutputFormat::Yaml => println!("{}", serde_yaml::to_string(&results)?),
+    }
+
+    Ok(())
+}
+
+fn print_plaintext(results: &BTreeMap<String, Vec<Coverage>>) {
+    for (i, (file, docs)) in results.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", file);
+        if docs.is_empty() {
+            println!("  not covered by any documentation");
+        }
+        for doc in docs {
+            println!("  {:8} {} ({})", doc.status, doc.doc, doc.pattern);
+        }
+    }
+}
+
+/// Find the entries of all docs under `target` that cover each of `files`.
+/// Files are matched and keyed by their absolute, normalized path.
+pub fn find_coverage(
+    target: Option<&Path>,
+    files: &[PathBuf],
+) -> Result<BTreeMap<PathBuf, Vec<Coverage>>> {
+    let wanted: BTreeSet<PathBuf> = files.iter().map(|f| paths::absolute(f)).collect();
+    let mut results: BTreeMap<PathBuf, Vec<Coverage>> = BTreeMap::new();
+    let mut propagation = Propagation::new();
+
+    for doc_path in scanner::find_documents(target)? {
+        if let Some(doc) = DocCoverage::load(&doc_path) {
+            doc.add_coverage(&wanted, &mut propagation, &mut results);
+        }
+    }
+
+    Ok(results)
+}
+
+/// A doc's entries with the files each one covers, resolved once so the
+/// doc can be matched against many source files
+pub struct DocCoverage {
+    path: PathBuf,
+    resolver: PathResolver,
+    entries: Vec<(WatchEntry, BTreeSet<PathBuf>)>,
+}
+
+impl DocCoverage {
+    /// Resolve the entries of a doc; None when it has none
+    pub fn load(doc_path: &Path) -> Option<Self> {
+        let fm = match frontmatter::parse_file(doc_path) {
+            Ok(Some(fm)) if fm.has_driftwatcher() => fm,
+            Ok(_) => return None,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                return None;
+            }
+        };
+
+        let resolver = match PathResolver::new(doc_path) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", doc_path.display(), e);
+                return None;
+            }
+        };
+
+        let entries = fm
+            .entries
+            .into_iter()
+            .filter_map(|entry| {
+                let files = resolver.resolve_files(&entry.pattern).ok()?;
+                let files = files.iter().map(|p| paths::absolute(p)).collect();
+                Some((entry, files))
+            })
+            .collect();
+        Some(Self {
+            path: doc_path.to_path_buf(),
+            resolver,
+            entries,
+        })
+    }
+
+    /// Add this doc's coverage of the `wanted` files (absolute) to `results`
+    pub fn add_coverage(
+        &self,
+        wanted: &BTreeSet<PathBuf>,
+        propagation: &mut Propagation,
+        results: &mut BTreeMap<PathBuf, Vec<Coverage>>,
+    ) {
+        for (entry, files) in &self.entries {
+            let covered: Vec<&PathBuf> = files.intersection(wanted).collect();
+            if covered.is_empty() {
+                continue;
+            }
+
+            let (status, _) = propagation.check_entry(&self.resolver, entry);
+            for path in covered {
+                results.entry(path.clone()).or_default().push(Coverage {
+                    doc: self.path.display().to_string(),
+                    pattern: entry.pattern.clone(),
+                    status,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::Path;
+
+/// Documentation formats drifty can track. Most keep their driftwatcher
+/// entries in a block at the top of the file:
+///
+/// - Markdown: YAML, TOML or JSON frontmatter
+/// - AsciiDoc: YAML inside a leading `////` comment block
+/// - reStructuredText: YAML inside a leading `.. driftwatcher:` comment
+/// - Jupyter notebooks: the notebook-level `metadata.driftwatcher` object
+/// - Rust source: `//! drifty:` or `/// drifty:` lines in doc comments
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Markdown,
+    AsciiDoc,
+    Rst,
+    Notebook,
+    Rust,
+}
+
+impl Format {
+    /// Detect the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        match ext.as_str() {
+            "md" | "markdown" => Some(Format::Markdown),
+            "adoc" | "asciidoc" | "asc" => Some(Format::AsciiDoc),
+            "rst" | "rest" => Some(Format::Rst),
+            "ipynb" => Some(Format::Notebook),
+            "rs" => Some(Format::Rust),
+            _ => None,
+        }
+    }
+
+    /// An empty driftwatcher block to put at the top of a file that has none,
+    /// using the given line ending. Notebooks always have metadata to add the
+    /// entries to, and Rust entries are single comment lines, so there is no
+    /// block for them.
+    pub fn empty_block(self, eol: &str) -> Option<String> {
+        match self {
+            Format::Markdown => Some(format!("---{eol}driftwatcher:{eol}---{eol}")),
+            Format::AsciiDoc => Some(format!("////{eol}driftwatcher:{eol}////{eol}")),
+            // The blank line ends the comment before the document starts
+            Format::Rst => Some(format!(".. driftwatcher:{eol}{eol}")),
+            Format::Notebook | Format::Rust => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            Format::from_path(Path::new("a/README.md")),
+            Some(Format::Markdown)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("guide.ADOC")),
+            Some(Format::AsciiDoc)
+        );
+        assert_eq!(Format::from_path(Path::new("index.rst")), Some(Format::Rst));
+        assert_eq!(
+            Format::from_path(Path::new("etl.ipynb")),
+            Some(Format::Notebook)
+        );
+        assert_eq!(Format::from_path(Path::new("main.rs")), Some(Format::Rust));
+        assert_eq!(Format::from_path(Path::new("Makefile")), None);
+    }
+}
@@ -1,8 +1,18 @@
 use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::collections::HashMap;
-use std::fs;
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::document::Format;
+use crate::lock::ProjectLock;
+use crate::manifest;
+use crate::notebook;
+use crate::paths;
 
 /// A single watch entry (pattern -> hash)
 #[derive(Debug, Clone)]
@@ -11,13 +21,69 @@ pub struct WatchEntry {
     pub hash: Option<String>,
 }
 
+/// Frontmatter syntax, recognized by how the file starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    /// `---` delimited YAML; `driftwatcher` is a list of single-key maps
+    Yaml,
+    /// `+++` delimited TOML (Hugo, Zola); `driftwatcher` is a table
+    Toml,
+    /// A JSON object at the top of the file; `driftwatcher` is an object
+    Json,
+    /// YAML in a leading AsciiDoc `////` comment block
+    AsciiDoc,
+    /// YAML in a leading reStructuredText `.. driftwatcher:` comment
+    Rst,
+    /// A Jupyter notebook; entries are in `metadata.driftwatcher`
+    Notebook,
+    /// Rust source; each entry is a `//! drifty:` or `/// drifty:` doc comment
+    /// line anywhere in the file
+    RustDoc,
+    /// Not in the doc at all: the entries are listed in the project manifest
+    Manifest,
+}
+
+impl Style {
+    fn delimiter(self) -> &'static str {
+        match self {
+            Style::Yaml => "---",
+            Style::Toml => "+++",
+            Style::AsciiDoc => "////",
+            Style::Json | Style::Rst | Style::Notebook | Style::RustDoc | Style::Manifest => "",
+        }
+    }
+
+    /// Whether a (trimmed) line holds the driftwatcher key that entries follow
+    fn is_key_line(self, trimmed: &str) -> bool {
+        match self {
+            Style::Yaml | Style::AsciiDoc => trimmed.starts_with("driftwatcher:"),
+            Style::Toml => trimmed.starts_with("[driftwatcher]"),
+            Style::Rst => trimmed.starts_with(RST_KEY),
+            Style::Json | Style::Notebook | Style::RustDoc | Style::Manifest => false,
+        }
+    }
+
+    /// Whether entries are a JSON object (edited as JSON, not line by line)
+    fn is_json(self) -> bool {
+        matches!(self, Style::Json | Style::Notebook)
+    }
+}
+
+/// First line of the reStructuredText comment holding the entries
+const RST_KEY: &str = ".. driftwatcher:";
+
 /// Parsed driftwatcher frontmatter
 #[derive(Debug)]
 pub struct Frontmatter {
     pub entries: Vec<WatchEntry>,
-    /// Raw YAML content between --- delimiters (for preservation)
-    raw_yaml: String,
-    /// Character position where frontmatter ends (after closing ---)
+    pub style: Style,
+    /// Source doc this doc translates (`translation_of`), relative to the doc
+    pub translation_of: Option<String>,
+    /// Whether the driftwatcher key is present, even without entries
+    has_key: bool,
+    /// Byte position where frontmatter starts (after a BOM)
+    start_pos: usize,
+    /// Byte position where frontmatter ends (after the closing delimiter)
     end_pos: usize,
 }
 
@@ -27,36 +93,125 @@ struct YamlFrontmatter {
     #[serde(default)]
     driftwatcher: Option<Vec<HashMap<String, Option<String>>>>,
 
+    #[serde(default)]
+    translation_of: Option<String>,
+
     #[serde(flatten)]
     other: HashMap<String, serde_yaml::Value>,
 }
 
 impl Frontmatter {
+    /// Entries of a doc listed in the project manifest
+    pub fn from_manifest(entries: Vec<WatchEntry>) -> Self {
+        Self {
+            entries,
+            style: Style::Manifest,
+            translation_of: None,
+            has_key: true,
+            start_pos: 0,
+            end_pos: 0,
+        }
+    }
+
     /// Check if driftwatcher is already configured
     pub fn has_driftwatcher(&self) -> bool {
-        !self.entries.is_empty() || self.raw_yaml.contains("driftwatcher:")
+        !self.entries.is_empty() || self.has_key
+    }
+
+    /// Pattern of the entry recording which version of the translation
+    /// source this doc was translated from (only the source's body is hashed)
+    pub fn translation_pattern(&self) -> Option<String> {
+        let source = self.translation_of.as_deref()?;
+        Some(format!("{}{}", source, paths::BODY_SUFFIX))
+    }
+
+    /// Byte offset where the document body starts (after the closing `---` line)
+    pub fn body_start(&self, content: &str) -> usize {
+        if self.style == Style::Manifest {
+            return 0;
+        }
+        match content[self.end_pos..].find('\n') {
+            Some(p) => self.end_pos + p + 1,
+            None => content.len(),
+        }
+    }
+}
+
+/// UTF-8 byte order mark, which some Windows editors put at the start of files
+const BOM: char = '\u{feff}';
+
+/// Line ending used by the content (the ending of its first line), so edits
+/// can match it
+pub fn line_ending(content: &str) -> &'static str {
+    match content.find('\n') {
+        Some(p) if content[..p].ends_with('\r') => "\r\n",
+        _ => "\n",
     }
 }
 
 /// Parse frontmatter from file content
 pub fn parse(content: &str) -> Result<Option<Frontmatter>> {
-    // Check for frontmatter delimiters
-    if !content.starts_with("---") {
+    // Positions are kept relative to the full content, BOM included
+    let start = if content.starts_with(BOM) {
+        BOM.len_utf8()
+    } else {
+        0
+    };
+
+    // Check for frontmatter delimiters. `{{` is a Hugo shortcode, and other
+    // openers that are not a JSON object (`{% include %}`, `{:toc}`) are not
+    // frontmatter either.
+    let head = &content[start..];
+    let style = if head.starts_with("---") {
+        Style::Yaml
+    } else if head.starts_with("+++") {
+        Style::Toml
+    } else if head.starts_with("////") {
+        Style::AsciiDoc
+    } else if head.starts_with('{') && !head.starts_with("{{") {
+        return parse_json(content, start);
+    } else if head.starts_with(RST_KEY) {
+        return parse_rst(content, start).map(Some);
+    } else {
         return Ok(None);
-    }
+    };
 
-    // Find the closing ---
-    let rest = &content[3..];
+    // Find the closing delimiter
+    let delimiter = style.delimiter();
+    let rest = &content[start + delimiter.len()..];
     let close_pos = rest
-        .find("\n---")
-        .ok_or_else(|| anyhow!("Frontmatter not closed (missing closing ---)"))?;
+        .find(&format!("\n{}", delimiter))
+        .ok_or_else(|| anyhow!("Frontmatter not closed (missing closing {})", delimiter))?;
+
+    let raw = &rest[1..close_pos]; // Skip initial newline
+    let end_pos = start + close_pos + 1 + 2 * delimiter.len(); // BOM + "---" + content + "\n---"
+
+    // A leading AsciiDoc comment is only ours if it has the driftwatcher key
+    if style == Style::AsciiDoc && !raw.lines().any(|l| style.is_key_line(l.trim())) {
+        return Ok(None);
+    }
 
-    let yaml_content = &rest[1..close_pos]; // Skip initial newline
-    let end_pos = 3 + close_pos + 4; // "---" + content + "\n---"
+    let (entries, has_key, translation_of) = match style {
+        Style::Toml => parse_toml(raw)?,
+        _ => parse_yaml(raw)?,
+    };
+
+    Ok(Some(Frontmatter {
+        entries,
+        style,
+        translation_of,
+        has_key,
+        start_pos: start,
+        end_pos,
+    }))
+}
+
+/// Entries, whether the driftwatcher key is present, and the translation source
+type Keys = (Vec<WatchEntry>, bool, Option<String>);
 
-    // Parse as YAML
+fn parse_yaml(raw: &str) -> Result<Keys> {
     let parsed: YamlFrontmatter =
-        serde_yaml::from_str(yaml_content).with_context(|| "Failed to parse YAML frontmatter")?;
+        serde_yaml::from_str(raw).with_context(|| "Failed to parse YAML frontmatter")?;
 
     // Extract driftwatcher entries
     let entries = if let Some(dw_entries) = parsed.driftwatcher {
@@ -73,118 +228,834 @@ pub fn parse(content: &str) -> Result<Option<Frontmatter>> {
         Vec::new()
     };
 
+    Ok((
+        entries,
+        raw.contains("driftwatcher:"),
+        parsed.translation_of,
+    ))
+}
+
+fn parse_toml(raw: &str) -> Result<Keys> {
+    let table: toml::Table =
+        toml::from_str(raw).with_context(|| "Failed to parse TOML frontmatter")?;
+    let translation_of = table
+        .get("translation_of")
+        .and_then(|v| v.as_str())
+        .map(String::from);
+
+    match table.get("driftwatcher") {
+        None => Ok((Vec::new(), false, translation_of)),
+        Some(toml::Value::Table(dw)) => {
+            // TOML has no null, so an empty string marks an entry without a hash
+            let entries = dw
+                .iter()
+                .map(|(pattern, hash)| WatchEntry {
+                    pattern: pattern.clone(),
+                    hash: hash.as_str().filter(|h| !h.is_empty()).map(String::from),
+                })
+                .collect();
+            Ok((entries, true, translation_of))
+        }
+        Some(_) => Err(anyhow!("driftwatcher must be a table in TOML frontmatter")),
+    }
+}
+
+fn parse_rst(content: &str, start: usize) -> Result<Frontmatter> {
+    // The comment continues over indented and blank lines; it ends with its
+    // last non-blank line
+    let mut pos = start;
+    let mut end_pos = start;
+    for (i, line) in content[start..].split_inclusive('\n').enumerate() {
+        let text = line.trim_end_matches(['\r', '\n']);
+        if i > 0 && !text.is_empty() && !text.starts_with([' ', '\t']) {
+            break;
+        }
+        if !text.trim().is_empty() {
+            end_pos = pos + text.len();
+        }
+        pos += line.len();
+    }
+
+    // Without the leading ".. " the comment is a YAML document
+    let raw = &content[start + 3..end_pos];
+    let (entries, _, translation_of) = parse_yaml(raw)?;
+
+    Ok(Frontmatter {
+        entries,
+        style: Style::Rst,
+        translation_of,
+        has_key: true,
+        start_pos: start,
+        end_pos,
+    })
+}
+
+fn parse_json(content: &str, start: usize) -> Result<Option<Frontmatter>> {
+    // The frontmatter is the first JSON value; the body follows it
+    let mut values =
+        serde_json::Deserializer::from_str(&content[start..]).into_iter::<Map<String, Value>>();
+    let Some(Ok(object)) = values.next() else {
+        return Ok(None);
+    };
+    let end_pos = start + values.byte_offset();
+
+    // A notebook is one JSON document; its entries live in the metadata
+    let (style, dw) = if notebook::is_notebook(&object) {
+        (Style::Notebook, notebook::entries(&object))
+    } else {
+        (Style::Json, object.get("driftwatcher"))
+    };
+
+    let entries = match dw {
+        None => Vec::new(),
+        Some(Value::Object(dw)) => dw
+            .iter()
+            .map(|(pattern, hash)| WatchEntry {
+                pattern: pattern.clone(),
+                hash: hash.as_str().map(String::from),
+            })
+            .collect(),
+        Some(_) => {
+            return Err(anyhow!(
+                "driftwatcher must be an object in JSON frontmatter"
+            ))
+        }
+    };
+
+    let translation_of = match style {
+        Style::Json => object.get("translation_of"),
+        _ => None,
+    };
+
     Ok(Some(Frontmatter {
         entries,
-        raw_yaml: yaml_content.to_string(),
+        style,
+        translation_of: translation_of.and_then(Value::as_str).map(String::from),
+        has_key: dw.is_some(),
+        start_pos: start,
         end_pos,
     }))
 }
 
-/// Parse frontmatter from a file path
+/// Parse the entries of a document: from the project manifest when it lists
+/// the doc, or else from the doc's content
+pub fn parse_document(path: &Path, content: &str) -> Result<Option<Frontmatter>> {
+    if let Some(entries) = manifest::doc_entries(path)? {
+        return Ok(Some(Frontmatter::from_manifest(entries)));
+    }
+    parse_content(path, content)
+}
+
+/// Parse the entries in a document's content, knowing its path. Rust files
+/// hold their entries in doc comment directives rather than frontmatter.
+pub fn parse_content(path: &Path, content: &str) -> Result<Option<Frontmatter>> {
+    match Format::from_path(path) {
+        Some(Format::Rust) => Ok(parse_rust(content)),
+        _ => parse(content),
+    }
+}
+
+/// Parse the entries of a document from its path. Docs listed in the manifest
+/// are not read, so they can be in any format or encoding.
 pub fn parse_file(path: &Path) -> Result<Option<Frontmatter>> {
-    let content = fs::read_to_string(path)
-        .with_context(|| format!("Failed to read file: {}", path.display()))?;
-    parse(&content)
+    if let Some(entries) = manifest::doc_entries(path)? {
+        return Ok(Some(Frontmatter::from_manifest(entries)));
+    }
+    parse_content(path, &read_file(path)?)
+}
+
+/// Parse the `drifty:` directives of Rust source; None when it has none
+fn parse_rust(content: &str) -> Option<Frontmatter> {
+    let entries: Vec<WatchEntry> = content
+        .lines()
+        .filter_map(rust_directive)
+        .map(|(_, rest)| rust_entry(rest))
+        .collect();
+    if entries.is_empty() {
+        return None;
+    }
+
+    Some(Frontmatter {
+        entries,
+        style: Style::RustDoc,
+        translation_of: None,
+        has_key: true,
+        start_pos: 0,
+        end_pos: content.len(),
+    })
+}
+
+/// Split a `//! drifty: pattern = hash` (or `///`) line into its prefix, up to
+/// and including `drifty:` and the space after it, and the `pattern = hash` rest
+fn rust_directive(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.strip_prefix(BOM).unwrap_or(line).trim_start();
+    let comment = trimmed
+        .strip_prefix("//!")
+        .or_else(|| trimmed.strip_prefix("///"))?;
+    // Four or more slashes make a plain comment, not a doc comment
+    if comment.starts_with('/') {
+        return None;
+    }
+    let rest = comment.trim_start().strip_prefix("drifty:")?.trim_start();
+    Some((&line[..line.len() - rest.len()], rest.trim_end()))
+}
+
+/// The entry of a directive's `pattern = hash` text; the hash may be missing
+fn rust_entry(rest: &str) -> WatchEntry {
+    match rest.rsplit_once('=') {
+        Some((pattern, hash)) => WatchEntry {
+            pattern: pattern.trim().to_string(),
+            hash: Some(hash.trim().to_string()).filter(|h| !h.is_empty()),
+        },
+        None => WatchEntry {
+            pattern: rest.to_string(),
+            hash: None,
+        },
+    }
+}
+
+/// Read a doc, explaining where the problem is when it is not UTF-8
+pub fn read_file(path: &Path) -> Result<String> {
+    let bytes =
+        fs::read(path).with_context(|| format!("Failed to read file: {}", path.display()))?;
+
+    String::from_utf8(bytes).map_err(|e| {
+        let bytes = e.as_bytes();
+        if bytes.starts_with(&[0xff, 0xfe]) || bytes.starts_with(&[0xfe, 0xff]) {
+            return anyhow!(
+                "{} is UTF-16 encoded; drifty only reads UTF-8 docs, re-save it as UTF-8",
+                path.display()
+            );
+        }
+        let valid = e.utf8_error().valid_up_to();
+        let line_start = bytes[..valid]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |p| p + 1);
+        let line = bytes[..valid].iter().filter(|&&b| b == b'\n').count() + 1;
+        let column = String::from_utf8_lossy(&bytes[line_start..valid]).chars().count() + 1;
+        anyhow!(
+            "{} is not valid UTF-8 (byte 0x{:02x} at line {}, column {}); drifty only reads UTF-8 docs, re-save it as UTF-8",
+            path.display(),
+            bytes[valid],
+            line,
+            column
+        )
+    })
 }
 
-/// Add empty driftwatcher frontmatter to content that has no frontmatter
-pub fn add_empty_frontmatter(content: &str) -> String {
-    format!("---\ndriftwatcher:\n---\n{}", content)
+/// Add an empty driftwatcher block, in the document format's style, to content
+/// that has none
+pub fn add_empty_frontmatter(content: &str, format: Format) -> Result<String> {
+    let block = format
+        .empty_block(line_ending(content))
+        .ok_or_else(|| match format {
+            Format::Rust => anyhow!("Rust files need no init; add entries with 'drifty add'"),
+            _ => anyhow!("Not a valid notebook (expected a JSON object)"),
+        })?;
+    Ok(match content.strip_prefix(BOM) {
+        Some(rest) => format!("{}{}{}", BOM, block, rest),
+        None => format!("{}{}", block, content),
+    })
 }
 
-/// Add driftwatcher key to existing frontmatter
-pub fn add_driftwatcher_to_existing(content: &str) -> Result<String> {
-    let fm = parse(content)?.ok_or_else(|| anyhow!("No frontmatter found"))?;
+/// Add driftwatcher key to existing frontmatter, in the frontmatter's own style
+pub fn add_driftwatcher_to_existing(path: &Path, content: &str) -> Result<String> {
+    let fm = parse_content(path, content)?.ok_or_else(|| anyhow!("No frontmatter found"))?;
+
+    let key = match fm.style {
+        Style::Yaml | Style::AsciiDoc => "driftwatcher:",
+        Style::Toml => "[driftwatcher]",
+        // The comment is only recognized when it has the key, and directives
+        // are only recognized with an entry
+        Style::Rst | Style::RustDoc | Style::Manifest => return Ok(content.to_string()),
+        // Editing creates the object when it is missing
+        Style::Json | Style::Notebook => return edit_json_entries(content, &fm, |_| Ok(())),
+    };
 
-    // Insert driftwatcher: before the closing ---
-    let before_close = &content[..content[..fm.end_pos].rfind("\n---").unwrap()];
+    // Insert the key before the closing delimiter
+    let delimiter = fm.style.delimiter();
+    let close = content[..fm.end_pos]
+        .rfind(&format!("\n{}", delimiter))
+        .unwrap();
+    let before_close = &content[..close];
+    let before_close = before_close.strip_suffix('\r').unwrap_or(before_close);
     let after_close = &content[fm.end_pos..];
+    let eol = line_ending(content);
 
     Ok(format!(
-        "{}\ndriftwatcher:\n---{}",
+        "{}{eol}{key}{eol}{delimiter}{}",
         before_close, after_close
     ))
 }
 
-/// Add a watch entry to the frontmatter
-pub fn add_entry(content: &str, pattern: &str, hash: &str) -> Result<String> {
-    let fm = parse(content)?.ok_or_else(|| anyhow!("No frontmatter found"))?;
+/// Add a watch entry to the frontmatter (as the first entry)
+pub fn add_entry(path: &Path, content: &str, pattern: &str, hash: &str) -> Result<String> {
+    let fm = parse_content(path, content)?.ok_or_else(|| anyhow!("No frontmatter found"))?;
 
     if !fm.has_driftwatcher() {
         // Add driftwatcher section first
-        let with_dw = add_driftwatcher_to_existing(content)?;
-        return add_entry(&with_dw, pattern, hash);
+        let with_dw = add_driftwatcher_to_existing(path, content)?;
+        return add_entry(path, &with_dw, pattern, hash);
     }
 
-    // Find where to insert the new entry (after "driftwatcher:" line)
-    let dw_pos = content
-        .find("driftwatcher:")
-        .ok_or_else(|| anyhow!("driftwatcher key not found"))?;
+    if fm.style.is_json() {
+        return edit_json_entries(content, &fm, |dw| {
+            let old = std::mem::take(dw);
+            dw.insert(pattern.to_string(), Value::String(hash.to_string()));
+            dw.extend(old.into_iter().filter(|(p, _)| p != pattern));
+            Ok(())
+        });
+    }
 
-    // Find the end of that line
-    let line_end = content[dw_pos..]
-        .find('\n')
-        .map(|p| dw_pos + p)
-        .unwrap_or(content.len());
+    if fm.style == Style::RustDoc {
+        return Ok(add_directive(content, pattern, hash));
+    }
 
-    let before = &content[..=line_end];
-    let after = &content[line_end + 1..];
+    // Insert the new entry after the "driftwatcher:" or "[driftwatcher]" line
+    let line_end = entries_start(content, &fm)?;
+    let before = &content[..line_end];
+    let after = &content[line_end..];
 
     // Format the new entry
-    let entry_line = format!("  - \"{}\": {}\n", pattern, hash);
+    let indent = match fm.style {
+        Style::Toml => "",
+        // Indented past the ".. " so the entry stays inside the comment
+        Style::Rst => "   ",
+        _ => "  ",
+    };
+    let entry_line = format!(
+        "{}{}",
+        format_entry(fm.style, indent, pattern, hash),
+        line_ending(content)
+    );
 
     Ok(format!("{}{}{}", before, entry_line, after))
 }
 
+/// Add a `drifty:` directive to Rust source: before the first existing one,
+/// with the same comment prefix, or else at the end of the leading `//!`
+/// module docs
+pub fn add_directive(content: &str, pattern: &str, hash: &str) -> String {
+    let eol = line_ending(content);
+
+    let mut pos = 0;
+    for line in content.split_inclusive('\n') {
+        if let Some((prefix, _)) = rust_directive(line.trim_end_matches(['\r', '\n'])) {
+            let bom = if pos == 0 && content.starts_with(BOM) {
+                BOM.len_utf8()
+            } else {
+                0
+            };
+            return format!(
+                "{}{}{eol}{}",
+                &content[..pos + bom],
+                format_entry(
+                    Style::RustDoc,
+                    prefix.trim_start_matches(BOM),
+                    pattern,
+                    hash
+                ),
+                &content[pos + bom..]
+            );
+        }
+        pos += line.len();
+    }
+
+    let start = if content.starts_with(BOM) {
+        BOM.len_utf8()
+    } else {
+        0
+    };
+    let mut end = start;
+    for line in content[start..].split_inclusive('\n') {
+        if !line.trim_start().starts_with("//!") {
+            break;
+        }
+        end += line.len();
+    }
+    // Module docs without a final newline are the whole file
+    let separator = if end > start && !content[..end].ends_with('\n') {
+        eol
+    } else {
+        ""
+    };
+
+    format!(
+        "{}{separator}{}{eol}{}",
+        &content[..end],
+        format_entry(Style::RustDoc, "//! drifty: ", pattern, hash),
+        &content[end..]
+    )
+}
+
 /// Update a hash for an existing entry
-pub fn update_entry(content: &str, pattern: &str, new_hash: &str) -> Result<String> {
-    // Find the entry line and replace the hash
-    // This is a bit tricky because we need to handle different formats
+pub fn update_entry(path: &Path, content: &str, pattern: &str, new_hash: &str) -> Result<String> {
+    replace_entry(path, content, pattern, pattern, new_hash)
+}
+
+/// Replace an existing entry with a new pattern and hash
+pub fn replace_entry(
+    path: &Path,
+    content: &str,
+    pattern: &str,
+    new_pattern: &str,
+    new_hash: &str,
+) -> Result<String> {
+    let fm = parse_content(path, content)?.ok_or_else(|| anyhow!("No frontmatter found"))?;
+
+    if fm.style.is_json() {
+        return edit_json_entries(content, &fm, |dw| {
+            if !dw.contains_key(pattern) {
+                return Err(anyhow!("Entry not found: {}", pattern));
+            }
+            *dw = std::mem::take(dw)
+                .into_iter()
+                .filter(|(p, _)| p != new_pattern || new_pattern == pattern)
+                .map(|(p, hash)| {
+                    if p == pattern {
+                        (new_pattern.to_string(), Value::String(new_hash.to_string()))
+                    } else {
+                        (p, hash)
+                    }
+                })
+                .collect();
+            Ok(())
+        });
+    }
 
-    let lines: Vec<&str> = content.lines().collect();
-    let mut result = Vec::new();
-    let mut found = false;
+    edit_entry_line(content, &fm, pattern, |indent_str| {
+        Some(format_entry(fm.style, indent_str, new_pattern, new_hash))
+    })
+}
 
-    for line in lines {
-        if !found && line.trim().starts_with("- ") && line.contains(pattern) {
+/// Remove an entry from the frontmatter
+pub fn remove_entry(path: &Path, content: &str, pattern: &str) -> Result<String> {
+    let fm = parse_content(path, content)?.ok_or_else(|| anyhow!("No frontmatter found"))?;
+
+    if fm.style.is_json() {
+        return edit_json_entries(content, &fm, |dw| {
+            dw.shift_remove(pattern)
+                .map(|_| ())
+                .ok_or_else(|| anyhow!("Entry not found: {}", pattern))
+        });
+    }
+
+    edit_entry_line(content, &fm, pattern, |_| None)
+}
+
+/// Find the (zero-based) line number of an entry
+pub fn find_entry_line(path: &Path, content: &str, pattern: &str) -> Option<usize> {
+    let fm = parse_content(path, content).ok().flatten()?;
+    content[..fm.end_pos]
+        .lines()
+        .position(|line| is_entry_line(fm.style, line, pattern))
+}
+
+/// Check whether a line is the entry for `pattern`
+fn is_entry_line(style: Style, line: &str, pattern: &str) -> bool {
+    if !line.contains(pattern) {
+        return false;
+    }
+    let trimmed = line.trim();
+
+    match style {
+        Style::Yaml | Style::AsciiDoc | Style::Rst => {
             // This might be our entry - parse it to be sure
-            let trimmed = line.trim().strip_prefix("- ").unwrap_or(line);
+            let Some(trimmed) = trimmed.strip_prefix("- ") else {
+                return false;
+            };
 
             // Handle both quoted and unquoted patterns
-            let is_match = trimmed.starts_with(&format!("\"{}\":", pattern))
+            trimmed.starts_with(&format!("\"{}\":", pattern))
                 || trimmed.starts_with(&format!("'{}':", pattern))
-                || trimmed.starts_with(&format!("{}:", pattern));
+                || trimmed.starts_with(&format!("{}:", pattern))
+        }
+        Style::Toml => [
+            toml_key(pattern),
+            format!("'{}'", pattern),
+            pattern.to_string(),
+        ]
+        .iter()
+        .any(|key| {
+            trimmed
+                .strip_prefix(key.as_str())
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+        }),
+        Style::Json | Style::Notebook => serde_json::to_string(pattern)
+            .is_ok_and(|key| trimmed.starts_with(&format!("{}:", key))),
+        Style::Manifest => false,
+        Style::RustDoc => {
+            rust_directive(line).is_some_and(|(_, rest)| rust_entry(rest).pattern == pattern)
+        }
+    }
+}
 
-            if is_match {
-                // Determine the indentation
-                let indent = line.len() - line.trim_start().len();
-                let indent_str = &line[..indent];
+/// Entry line in the given style (without line ending)
+fn format_entry(style: Style, indent: &str, pattern: &str, hash: &str) -> String {
+    match style {
+        Style::Toml => format!("{}{} = \"{}\"", indent, toml_key(pattern), hash),
+        Style::RustDoc => format!("{}{} = {}", indent, pattern, hash),
+        _ => format!("{}- \"{}\": {}", indent, pattern, hash),
+    }
+}
 
-                // Reconstruct with new hash
-                result.push(format!("{}- \"{}\": {}", indent_str, pattern, new_hash));
-                found = true;
-                continue;
+/// A pattern as a quoted TOML key
+fn toml_key(pattern: &str) -> String {
+    format!("\"{}\"", pattern.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Byte offset just after the `driftwatcher:` (YAML), `[driftwatcher]` (TOML)
+/// or `.. driftwatcher:` (reStructuredText) line, where entries start
+fn entries_start(content: &str, fm: &Frontmatter) -> Result<usize> {
+    let mut pos = fm.start_pos;
+    for line in content[fm.start_pos..].split_inclusive('\n') {
+        if pos >= fm.end_pos {
+            break;
+        }
+        pos += line.len();
+        if fm.style.is_key_line(line.trim()) {
+            return Ok(pos);
+        }
+    }
+
+    match fm.style {
+        Style::Toml => Err(anyhow!(
+            "driftwatcher entries can only be edited in a [driftwatcher] table"
+        )),
+        _ => Err(anyhow!("driftwatcher key not found")),
+    }
+}
+
+/// Byte range of the lines that may hold entries: the whole YAML block, or the
+/// `[driftwatcher]` table up to the next table header in TOML
+fn entry_region(content: &str, fm: &Frontmatter) -> Result<Range<usize>> {
+    if fm.style != Style::Toml {
+        return Ok(fm.start_pos..fm.end_pos);
+    }
+
+    let start = entries_start(content, fm)?;
+    let mut end = start;
+    for line in content[start..fm.end_pos].split_inclusive('\n') {
+        if line.trim_start().starts_with('[') {
+            break;
+        }
+        end += line.len();
+    }
+    Ok(start..end.min(fm.end_pos))
+}
+
+/// Replace the first entry line for `pattern` with the result of `edit`
+/// (called with the line's indentation, or a directive's comment prefix), or drop it when `edit` returns None
+fn edit_entry_line<F>(content: &str, fm: &Frontmatter, pattern: &str, edit: F) -> Result<String>
+where
+    F: FnOnce(&str) -> Option<String>,
+{
+    let region = entry_region(content, fm)?;
+    let mut result = String::with_capacity(content.len());
+    let mut edit = Some(edit);
+    let mut pos = 0;
+
+    // Work on whole lines so each keeps its own ending (and the last line
+    // keeps its lack of one)
+    for line in content.split_inclusive('\n') {
+        let start = pos;
+        pos += line.len();
+
+        let text = line.trim_end_matches(['\r', '\n']);
+        if edit.is_some() && region.contains(&start) && is_entry_line(fm.style, text, pattern) {
+            // Determine the indentation; directives keep their whole comment prefix
+            let indent_str = match rust_directive(text) {
+                Some((prefix, _)) if fm.style == Style::RustDoc => prefix,
+                _ => &text[..text.len() - text.trim_start().len()],
+            };
+
+            if let Some(new_line) = edit.take().and_then(|f| f(indent_str)) {
+                result.push_str(&new_line);
+                result.push_str(&line[text.len()..]);
             }
+            continue;
         }
-        result.push(line.to_string());
+        result.push_str(line);
     }
 
-    if !found {
+    if edit.is_some() {
         return Err(anyhow!("Entry not found: {}", pattern));
     }
 
-    Ok(result.join("\n") + "\n")
+    Ok(result)
+}
+
+/// Apply `edit` to the driftwatcher object of JSON frontmatter or a
+/// notebook, creating the object if it is missing
+fn edit_json_entries<F>(content: &str, fm: &Frontmatter, edit: F) -> Result<String>
+where
+    F: FnOnce(&mut Map<String, Value>) -> Result<()>,
+{
+    if fm.style == Style::Notebook {
+        return notebook::edit_entries(content, edit);
+    }
+
+    edit_json(content, fm, |object| {
+        let dw = object
+            .entry("driftwatcher")
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("driftwatcher must be an object in JSON frontmatter"))?;
+        edit(dw)
+    })
+}
+
+/// Rewrite JSON frontmatter after applying `edit` to it. Key order is kept and
+/// the output uses the indentation of the original.
+fn edit_json<F>(content: &str, fm: &Frontmatter, edit: F) -> Result<String>
+where
+    F: FnOnce(&mut Map<String, Value>) -> Result<()>,
+{
+    let raw = &content[fm.start_pos..fm.end_pos];
+    let mut object: Map<String, Value> = serde_json::from_str(raw)?;
+    edit(&mut object)?;
+
+    let indent = raw
+        .lines()
+        .skip(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+    let mut json = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    object.serialize(&mut serde_json::Serializer::with_formatter(
+        &mut json, formatter,
+    ))?;
+    let json = String::from_utf8(json)?.replace('\n', line_ending(content));
+
+    Ok(format!(
+        "{}{}{}",
+        &content[..fm.start_pos],
+        json,
+        &content[fm.end_pos..]
+    ))
+}
+
+/// Remove all watch entries from the driftwatcher list, keeping the
+/// `driftwatcher:` key itself and everything else in the file untouched
+pub fn strip_entries(path: &Path, content: &str) -> Result<String> {
+    let fm = parse_content(path, content)?.ok_or_else(|| anyhow!("No frontmatter found"))?;
+    if !fm.has_driftwatcher() {
+        return Ok(content.to_string());
+    }
+
+    match fm.style {
+        Style::Json | Style::Notebook => {
+            return edit_json_entries(content, &fm, |dw| {
+                dw.clear();
+                Ok(())
+            })
+        }
+        Style::RustDoc => {
+            return Ok(content
+                .split_inclusive('\n')
+                .filter(|line| rust_directive(line.trim_end_matches(['\r', '\n'])).is_none())
+                .collect())
+        }
+        Style::Toml => {
+            let region = entry_region(content, &fm)?;
+            let kept: String = content[region.clone()]
+                .split_inclusive('\n')
+                .filter(|line| line.trim_start().starts_with('#') || !line.contains('='))
+                .collect();
+            return Ok(format!(
+                "{}{}{}",
+                &content[..region.start],
+                kept,
+                &content[region.end..]
+            ));
+        }
+        _ => {}
+    }
+
+    let mut result = String::with_capacity(content.len());
+    let mut pos = 0;
+    let mut in_list = false;
+
+    for line in content.split_inclusive('\n') {
+        let start = pos;
+        pos += line.len();
+
+        if start >= fm.end_pos {
+            result.push_str(line);
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if in_list {
+            if trimmed.starts_with("- ") {
+                continue;
+            }
+            in_list = false;
+        } else if fm.style.is_key_line(trimmed) {
+            in_list = true;
+        }
+        result.push_str(line);
+    }
+
+    Ok(result)
 }
 
-/// Write updated content to a file
+/// Remove the driftwatcher key along with its entries, and the whole block
+/// when nothing else is left in it, so tools reading the doc never see the
+/// key once its entries have moved to the manifest
+pub fn remove_driftwatcher(path: &Path, content: &str) -> Result<String> {
+    let stripped = strip_entries(path, content)?;
+    let fm = match parse_content(path, &stripped)? {
+        Some(fm) if fm.has_driftwatcher() => fm,
+        _ => return Ok(stripped),
+    };
+    let (block_start, block_end) = (fm.start_pos, fm.body_start(&stripped));
+    let without_block = || format!("{}{}", &stripped[..block_start], &stripped[block_end..]);
+
+    let kept: String = match fm.style {
+        // Notebook metadata is namespaced, so an empty object is harmless
+        Style::Notebook | Style::RustDoc | Style::Manifest => return Ok(stripped),
+        Style::Json => {
+            let object: Map<String, Value> =
+                serde_json::from_str(&stripped[fm.start_pos..fm.end_pos])?;
+            if object.keys().all(|key| key == "driftwatcher") {
+                return Ok(without_block());
+            }
+            return edit_json(&stripped, &fm, |object| {
+                object.shift_remove("driftwatcher");
+                Ok(())
+            });
+        }
+        _ => stripped[block_start..block_end]
+            .split_inclusive('\n')
+            .filter(|line| !fm.style.is_key_line(line.trim()))
+            .collect(),
+    };
+
+    if fm.style == Style::Rst {
+        // The key line opens the comment, so only a comment holding nothing
+        // else can go; it takes the blank line after it along
+        if !kept.trim().is_empty() {
+            return Ok(stripped);
+        }
+        let rest = &stripped[block_end..];
+        let rest = rest
+            .strip_prefix("\r\n")
+            .or_else(|| rest.strip_prefix('\n'))
+            .unwrap_or(rest);
+        return Ok(format!("{}{}", &stripped[..block_start], rest));
+    }
+
+    // Only the delimiters left: drop the block
+    let lines: Vec<&str> = kept.lines().collect();
+    if lines.len() <= 2
+        || lines[1..lines.len() - 1]
+            .iter()
+            .all(|l| l.trim().is_empty())
+    {
+        return Ok(without_block());
+    }
+    Ok(format!(
+        "{}{}{}",
+        &stripped[..block_start],
+        kept,
+        &stripped[block_end..]
+    ))
+}
+
+/// A doc's bytes with its driftwatcher entries taken out (unchanged when it
+/// has none, or is not UTF-8)
+pub fn without_entries(path: &Path, contents: Vec<u8>) -> Vec<u8> {
+    let stripped = match std::str::from_utf8(&contents) {
+        Ok(text) => match parse_content(path, text) {
+            Ok(Some(fm)) if fm.has_driftwatcher() => remove_driftwatcher(path, text).ok(),
+            _ => None,
+        },
+        Err(_) => None,
+    };
+    stripped.map_or(contents, String::into_bytes)
+}
+
+/// A doc's bytes after its frontmatter, so edits to the frontmatter alone
+/// (a title, tags, entries) leave it unchanged. Notebooks and Rust files,
+/// whose entries are mixed in with their content, only lose their entries.
+pub fn body(path: &Path, contents: Vec<u8>) -> Vec<u8> {
+    let start = match std::str::from_utf8(&contents) {
+        Ok(text) => match parse_content(path, text) {
+            Ok(Some(fm)) if matches!(fm.style, Style::Notebook | Style::RustDoc) => None,
+            Ok(Some(fm)) => Some(fm.body_start(text)),
+            _ => Some(0),
+        },
+        Err(_) => Some(0),
+    };
+    match start {
+        Some(start) => contents[start..].to_vec(),
+        None => without_entries(path, contents),
+    }
+}
+
+/// Write updated content to a file.
+///
+/// The content goes to a temporary file next to the doc, which is synced and
+/// renamed over it, so the doc is never left half-written. The project lock is
+/// held while writing; callers that read, modify and write a doc should hold
+/// it from before the read.
 pub fn write_file(path: &Path, content: &str) -> Result<()> {
-    fs::write(path, content).with_context(|| format!("Failed to write file: {}", path.display()))
+    let _lock = ProjectLock::acquire(path)?;
+    write_atomic(path, content.as_bytes())
+        .with_context(|| format!("Failed to write file: {}", path.display()))
+}
+
+fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    // Replace the file a symlink points to, not the link itself
+    let path = match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => fs::canonicalize(path)?,
+        _ => path.to_path_buf(),
+    };
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let tmp = dir.join(format!(
+        ".{}.drifty-{}.tmp",
+        name.to_string_lossy(),
+        process::id()
+    ));
+
+    let result = (|| {
+        let mut file = File::create(&tmp)?;
+        file.write_all(content)?;
+        if let Ok(meta) = fs::metadata(&path) {
+            file.set_permissions(meta.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result?;
+
+    // Make the rename itself durable
+    if let Ok(dir) = File::open(&dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn doc() -> &'static Path {
+        Path::new("doc.md")
+    }
+
     #[test]
     fn test_parse_empty_frontmatter() {
         let content = "---\ndriftwatcher:\n---\n# Doc";
@@ -217,11 +1088,299 @@ driftwatcher:
     #[test]
     fn test_add_empty_frontmatter() {
         let content = "# My Doc\nSome content.";
-        let result = add_empty_frontmatter(content);
+        let result = add_empty_frontmatter(content, Format::Markdown).unwrap();
         assert!(result.starts_with("---\ndriftwatcher:\n---\n"));
         assert!(result.contains("# My Doc"));
     }
 
+    #[test]
+    fn test_update_and_remove_entry() {
+        let content = "---\ndriftwatcher:\n  - \"a.rs\": 111\n  - b.rs: 222\n---\n# Doc\n";
+
+        let updated = update_entry(doc(), content, "b.rs", "333").unwrap();
+        assert!(updated.contains("  - \"b.rs\": 333\n"));
+        assert_eq!(find_entry_line(doc(), &updated, "b.rs"), Some(3));
+
+        let removed = remove_entry(doc(), content, "a.rs").unwrap();
+        let fm = parse(&removed).unwrap().unwrap();
+        assert_eq!(fm.entries.len(), 1);
+        assert_eq!(fm.entries[0].pattern, "b.rs");
+
+        assert!(remove_entry(doc(), content, "c.rs").is_err());
+    }
+
+    #[test]
+    fn test_toml_frontmatter() {
+        let content = "+++\ntitle = \"Doc\"\n\n[extra]\nkey = 1\n+++\n# Doc\nkey = 2\n";
+        let fm = parse(content).unwrap().unwrap();
+        assert_eq!(fm.style, Style::Toml);
+        assert!(!fm.has_driftwatcher());
+
+        let added = add_entry(doc(), content, "src/a.rs", "111").unwrap();
+        assert!(added.contains("key = 1\n[driftwatcher]\n\"src/a.rs\" = \"111\"\n+++\n"));
+        let added = add_entry(doc(), &added, "b.rs", "222").unwrap();
+        let fm = parse(&added).unwrap().unwrap();
+        let patterns: Vec<_> = fm.entries.iter().map(|e| e.pattern.as_str()).collect();
+        assert_eq!(patterns, vec!["b.rs", "src/a.rs"]);
+
+        let updated = update_entry(doc(), &added, "src/a.rs", "333").unwrap();
+        assert!(updated.contains("\"src/a.rs\" = \"333\"\n"));
+        assert_eq!(find_entry_line(doc(), &updated, "b.rs"), Some(6));
+
+        let removed = remove_entry(doc(), &updated, "b.rs").unwrap();
+        assert_eq!(parse(&removed).unwrap().unwrap().entries.len(), 1);
+        assert!(remove_entry(doc(), &updated, "key").is_err());
+
+        let stripped = strip_entries(doc(), &updated).unwrap();
+        let fm = parse(&stripped).unwrap().unwrap();
+        assert!(fm.entries.is_empty() && fm.has_driftwatcher());
+        assert!(stripped.ends_with("key = 1\n[driftwatcher]\n+++\n# Doc\nkey = 2\n"));
+    }
+
+    #[test]
+    fn test_asciidoc_comment_block() {
+        let content = "////\nCopyright notice\n////\n= Title\n";
+        assert!(parse(content).unwrap().is_none());
+
+        let content = add_empty_frontmatter(content, Format::AsciiDoc).unwrap();
+        let added = add_entry(doc(), &content, "a.rs", "111").unwrap();
+        assert!(added.starts_with("////\ndriftwatcher:\n  - \"a.rs\": 111\n////\n////\nCopyright"));
+
+        let fm = parse(&added).unwrap().unwrap();
+        assert_eq!(fm.style, Style::AsciiDoc);
+        assert_eq!(fm.entries[0].hash.as_deref(), Some("111"));
+        assert!(added[fm.body_start(&added)..].starts_with("////\nCopyright"));
+    }
+
+    #[test]
+    fn test_rst_comment() {
+        let content = add_empty_frontmatter("Title\n=====\n\nText\n", Format::Rst).unwrap();
+        assert_eq!(content, ".. driftwatcher:\n\nTitle\n=====\n\nText\n");
+        assert!(parse(&content).unwrap().unwrap().has_driftwatcher());
+
+        let added = add_entry(doc(), &content, "b.rs", "222").unwrap();
+        let added = add_entry(doc(), &added, "a.rs", "111").unwrap();
+        assert!(
+            added.starts_with(".. driftwatcher:\n   - \"a.rs\": 111\n   - \"b.rs\": 222\n\nTitle")
+        );
+
+        let fm = parse(&added).unwrap().unwrap();
+        assert_eq!(fm.style, Style::Rst);
+        assert_eq!(fm.entries.len(), 2);
+        assert_eq!(&added[fm.body_start(&added)..], "\nTitle\n=====\n\nText\n");
+
+        let updated = update_entry(doc(), &added, "b.rs", "333").unwrap();
+        assert!(updated.contains("   - \"b.rs\": 333\n"));
+        let stripped = strip_entries(doc(), &updated).unwrap();
+        assert_eq!(stripped, content);
+    }
+
+    #[test]
+    fn test_rust_doc_directives() {
+        // Kept on single lines so this file is not itself read as a doc
+        let content = "//! Backend\n//! drifty: src/backend/*.rs = aaa\nuse std::io;\n\n    /// drifty: src/io.rs\n    pub fn f() {}\n";
+        let path = Path::new("src/lib.rs");
+        assert!(parse(content).unwrap().is_none());
+        assert!(parse_document(Path::new("README.md"), content)
+            .unwrap()
+            .is_none());
+
+        let fm = parse_document(path, content).unwrap().unwrap();
+        assert_eq!(fm.style, Style::RustDoc);
+        assert_eq!(fm.entries.len(), 2);
+        assert_eq!(fm.entries[0].pattern, "src/backend/*.rs");
+        assert_eq!(fm.entries[0].hash.as_deref(), Some("aaa"));
+        assert_eq!(fm.entries[1].pattern, "src/io.rs");
+        assert!(fm.entries[1].hash.is_none());
+        assert_eq!(find_entry_line(path, content, "src/io.rs"), Some(4));
+
+        // Directives are rewritten in place, keeping their comment prefix
+        let updated = update_entry(path, content, "src/io.rs", "bbb").unwrap();
+        assert!(updated.contains("\n    /// drifty: src/io.rs = bbb\n    pub fn f() {}"));
+        let updated = remove_entry(path, &updated, "src/backend/*.rs").unwrap();
+        assert!(updated.starts_with("//! Backend\nuse std::io;"));
+        let added = add_directive(&updated, "src/a.rs", "ccc");
+        assert!(
+            added.contains("\n    /// drifty: src/a.rs = ccc\n    /// drifty: src/io.rs = bbb\n")
+        );
+
+        // Without directives, a new one goes at the end of the module docs
+        let added = add_directive("//! Backend\n\nfn main() {}", "src/a.rs", "ccc");
+        assert_eq!(
+            added,
+            "//! Backend\n//! drifty: src/a.rs = ccc\n\nfn main() {}"
+        );
+        assert_eq!(
+            add_directive("fn main() {}\r\n", "a.rs", "c"),
+            "//! drifty: a.rs = c\r\nfn main() {}\r\n"
+        );
+
+        // Four slashes make a plain comment
+        assert!(parse_document(path, "//// drifty: a.rs = c\n")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_rust_doc_banner() {
+        // A `////` banner would open an AsciiDoc block in any other doc
+        let content =
+            "//////////////////// Banner\n// Copyright\n\n//! drifty: src/io.rs = aaa\nfn f() {}\n";
+        let path = Path::new("src/lib.rs");
+        assert!(parse(content).is_err());
+        assert_eq!(find_entry_line(path, content, "src/io.rs"), Some(3));
+
+        let updated = update_entry(path, content, "src/io.rs", "bbb").unwrap();
+        assert!(updated.contains("\n//! drifty: src/io.rs = bbb\nfn f() {}"));
+        let added = add_entry(path, &updated, "src/a.rs", "ccc").unwrap();
+        assert!(added.contains("//! drifty: src/a.rs = ccc\n//! drifty: src/io.rs = bbb\n"));
+        let stripped = strip_entries(path, &added).unwrap();
+        assert!(stripped.starts_with("//////////////////// Banner\n// Copyright\n"));
+        assert!(!stripped.contains("drifty:"));
+        assert!(remove_entry(path, &updated, "src/io.rs").is_ok());
+    }
+
+    #[test]
+    fn test_remove_driftwatcher() {
+        // Only the key in the block: the block goes
+        let content = "---\ndriftwatcher:\n  - \"src/a.rs\": abc\n---\n# Doc\n";
+        assert_eq!(remove_driftwatcher(doc(), content).unwrap(), "# Doc\n");
+
+        // Other keys stay
+        let content = "---\ntitle: Doc\ndriftwatcher:\n  - \"src/a.rs\": abc\n---\n# Doc\n";
+        assert_eq!(
+            remove_driftwatcher(doc(), content).unwrap(),
+            "---\ntitle: Doc\n---\n# Doc\n"
+        );
+
+        let content = "+++\ntitle = \"Doc\"\n\n[driftwatcher]\n\"src/a.rs\" = \"abc\"\n+++\nBody\n";
+        assert_eq!(
+            remove_driftwatcher(doc(), content).unwrap(),
+            "+++\ntitle = \"Doc\"\n\n+++\nBody\n"
+        );
+
+        let content = ".. driftwatcher:\n   - \"src/a.rs\": abc\n\nTitle\n=====\n";
+        assert_eq!(
+            remove_driftwatcher(doc(), content).unwrap(),
+            "Title\n=====\n"
+        );
+
+        let content = "{\n  \"driftwatcher\": {\"src/a.rs\": \"abc\"}\n}\n# Doc\n";
+        assert_eq!(remove_driftwatcher(doc(), content).unwrap(), "# Doc\n");
+    }
+
+    #[test]
+    fn test_strip_entries() {
+        // The key and the rest of the file stay
+        let content = "---\ntitle: Doc\ndriftwatcher:\n  - \"a.rs\": 111\n  - b.rs: 222\ntags: [x]\n---\n# Doc\n  - c.rs: 333\n";
+        assert_eq!(
+            strip_entries(doc(), content).unwrap(),
+            "---\ntitle: Doc\ndriftwatcher:\ntags: [x]\n---\n# Doc\n  - c.rs: 333\n"
+        );
+
+        let content =
+            "{\n  \"title\": \"Doc\",\n  \"driftwatcher\": {\"a.rs\": \"111\"}\n}\n# Doc\n";
+        let stripped = strip_entries(doc(), content).unwrap();
+        let fm = parse(&stripped).unwrap().unwrap();
+        assert!(fm.entries.is_empty() && fm.has_driftwatcher());
+        assert!(stripped.ends_with("}\n# Doc\n"));
+
+        // Without a driftwatcher key there is nothing to strip
+        let content = "---\ntitle: Doc\n---\n# Doc\n";
+        assert_eq!(strip_entries(doc(), content).unwrap(), content);
+        assert!(strip_entries(doc(), "# Doc\n").is_err());
+    }
+
+    #[test]
+    fn test_json_frontmatter() {
+        let content = "{\n    \"title\": \"Doc\",\n    \"draft\": false\n}\n# Doc\n";
+        let fm = parse(content).unwrap().unwrap();
+        assert_eq!(fm.style, Style::Json);
+        assert_eq!(&content[fm.body_start(content)..], "# Doc\n");
+
+        let with_dw = add_driftwatcher_to_existing(doc(), content).unwrap();
+        assert!(with_dw.starts_with(
+            "{\n    \"title\": \"Doc\",\n    \"draft\": false,\n    \"driftwatcher\": {}\n}\n# Doc"
+        ));
+
+        let added = add_entry(doc(), &with_dw, "a.rs", "111").unwrap();
+        let added = add_entry(doc(), &added, "b.rs", "222").unwrap();
+        let updated = update_entry(doc(), &added, "a.rs", "333").unwrap();
+        let fm = parse(&updated).unwrap().unwrap();
+        assert_eq!(fm.entries[0].pattern, "b.rs");
+        assert_eq!(fm.entries[1].hash.as_deref(), Some("333"));
+        assert_eq!(find_entry_line(doc(), &updated, "a.rs"), Some(5));
+
+        let removed = remove_entry(doc(), &updated, "b.rs").unwrap();
+        assert_eq!(parse(&removed).unwrap().unwrap().entries.len(), 1);
+        assert!(removed.ends_with("}\n# Doc\n"));
+
+        // Hugo shortcodes are not frontmatter
+        assert!(parse("{{< note >}}\n").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_liquid_and_kramdown_openers() {
+        // Only a leading JSON object is frontmatter
+        assert!(parse("{% include header.html %}\n# Doc\n")
+            .unwrap()
+            .is_none());
+        assert!(parse("{:toc}\n\n# Doc\n").unwrap().is_none());
+        assert!(parse("{: .note}\nText\n").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_bom_crlf_and_trailing_newline_preserved() {
+        let content = "\u{feff}---\r\ntitle: Doc\r\n---\r\n# Doc\r\nNo newline";
+        let fm = parse(content).unwrap().unwrap();
+        assert!(!fm.has_driftwatcher());
+        assert_eq!(&content[fm.body_start(content)..], "# Doc\r\nNo newline");
+
+        let added = add_entry(doc(), content, "a.rs", "111").unwrap();
+        assert_eq!(
+            added,
+            "\u{feff}---\r\ntitle: Doc\r\ndriftwatcher:\r\n  - \"a.rs\": 111\r\n---\r\n# Doc\r\nNo newline"
+        );
+
+        let updated = update_entry(doc(), &added, "a.rs", "222").unwrap();
+        assert_eq!(updated, added.replace("111", "222"));
+        let removed = remove_entry(doc(), &updated, "a.rs").unwrap();
+        assert!(removed.ends_with("driftwatcher:\r\n---\r\n# Doc\r\nNo newline"));
+
+        let initialized = add_empty_frontmatter("\u{feff}# Doc\r\n", Format::Markdown).unwrap();
+        assert_eq!(
+            initialized,
+            "\u{feff}---\r\ndriftwatcher:\r\n---\r\n# Doc\r\n"
+        );
+        assert!(parse(&initialized).unwrap().unwrap().has_driftwatcher());
+    }
+
+    #[test]
+    fn test_read_file_reports_invalid_utf8() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("doc.md");
+        fs::write(&path, b"---\ntitle: caf\xe9\n---\n").unwrap();
+
+        let err = read_file(&path).unwrap_err().to_string();
+        assert!(err.contains("byte 0xe9 at line 2, column 11"), "{}", err);
+    }
+
+    #[test]
+    fn test_write_file_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("doc.md");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        write_file(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
     #[test]
     fn test_parse_with_other_frontmatter() {
         let content = r#"---
@@ -234,4 +1393,19 @@ driftwatcher:
         let fm = parse(content).unwrap().unwrap();
         assert_eq!(fm.entries.len(), 1);
     }
+
+    #[test]
+    fn test_translation_body() {
+        let content = "+++\ntitle = \"Anleitung\"\ntranslation_of = \"../en/guide.md\"\n[driftwatcher]\n+++\n# Anleitung\n";
+        let fm = parse(content).unwrap().unwrap();
+        assert_eq!(fm.translation_of.as_deref(), Some("../en/guide.md"));
+        assert_eq!(
+            fm.translation_pattern().as_deref(),
+            Some("../en/guide.md#body")
+        );
+
+        let path = Path::new("guide.md");
+        assert_eq!(body(path, content.as_bytes().to_vec()), b"# Anleitung\n");
+        assert_eq!(body(path, b"# Plain\n".to_vec()), b"# Plain\n");
+    }
 }
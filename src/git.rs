@@ -0,0 +1,134 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Metadata of a single commit
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitInfo {
+    pub id: String,
+    pub author: String,
+    pub email: String,
+    pub date: String,
+    pub summary: String,
+}
+
+/// Run a git command in `root` and return its stdout
+fn git(root: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Resolve a revision to a full commit id
+pub fn rev_parse(root: &Path, rev: &str) -> Result<String> {
+    let spec = format!("{}^{{commit}}", rev);
+    let out = git(root, &["rev-parse", "--verify", "--quiet", &spec])
+        .with_context(|| format!("Unknown revision: {}", rev))?;
+    Ok(String::from_utf8_lossy(&out).trim().to_string())
+}
+
+/// List all files (repository-relative) in the tree of a revision
+pub fn list_files(root: &Path, rev: &str) -> Result<Vec<PathBuf>> {
+    let out = git(root, &["ls-tree", "-r", "-z", "--name-only", rev])?;
+    Ok(out
+        .split(|b| *b == 0)
+        .filter(|p| !p.is_empty())
+        .map(|p| PathBuf::from(String::from_utf8_lossy(p).into_owned()))
+        .collect())
+}
+
+/// Read a file's contents as of a revision
+pub fn read_file(root: &Path, rev: &str, path: &Path) -> Result<Vec<u8>> {
+    let spec = format!("{}:{}", rev, path.to_string_lossy());
+    git(root, &["cat-file", "blob", &spec])
+}
+
+/// Commits reachable from HEAD that touched any of `paths`, newest first
+pub fn commits_touching(root: &Path, paths: &[PathBuf]) -> Result<Vec<String>> {
+    let mut args = vec![
+        "log".to_string(),
+        "--format=%H".to_string(),
+        "HEAD".to_string(),
+    ];
+    args.push("--".to_string());
+    args.extend(paths.iter().map(|p| {
+        if p.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            p.to_string_lossy().into_owned()
+        }
+    }));
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let out = git(root, &args)?;
+    Ok(String::from_utf8_lossy(&out)
+        .lines()
+        .map(|l| l.to_string())
+        .collect())
+}
+
+/// Look up author, date and subject of a commit
+pub fn commit_info(root: &Path, rev: &str) -> Result<CommitInfo> {
+    let out = git(
+        root,
+        &["show", "-s", "--format=%H%x00%an%x00%ae%x00%aI%x00%s", rev],
+    )?;
+    let text = String::from_utf8_lossy(&out);
+    let mut fields = text.trim_end().splitn(5, '\0').map(|f| f.to_string());
+    let mut next = || fields.next().unwrap_or_default();
+    Ok(CommitInfo {
+        id: next(),
+        author: next(),
+        email: next(),
+        date: next(),
+        summary: next(),
+    })
+}
+
+/// Diff between a revision and the work tree for a pathspec
+/// (`--stat` summary when `stat` is set)
+pub fn diff(root: &Path, rev: &str, pathspec: &str, stat: bool) -> Result<String> {
+    let mut args = vec!["diff", "--no-color"];
+    if stat {
+        args.push("--stat");
+    }
+    args.extend([rev, "--", pathspec]);
+    let out = git(root, &args)?;
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// Three-way merge of plain text files using `git merge-file`.
+///
+/// The result is written to `current`. Returns true when the merge was clean
+/// and false when conflict markers were left in the file. Conflict markers
+/// are labelled ours, base and theirs rather than with the file names, which
+/// are temporary.
+pub fn merge_file(current: &Path, base: &Path, other: &Path) -> Result<bool> {
+    let status = Command::new("git")
+        .arg("merge-file")
+        .args(["-L", "ours", "-L", "base", "-L", "theirs"])
+        .arg(current)
+        .arg(base)
+        .arg(other)
+        .status()
+        .context("Failed to run git merge-file")?;
+
+    // git merge-file exits with the number of conflicts, or a negative value on error
+    match status.code() {
+        Some(0) => Ok(true),
+        Some(code) if code > 0 && code < 128 => Ok(false),
+        _ => Err(anyhow!("git merge-file failed ({})", status)),
+    }
+}
@@ -7,15 +7,31 @@ use std::path::{Path, PathBuf};
 pub fn hash_file(path: &Path) -> Result<String> {
     let contents =
         fs::read(path).with_context(|| format!("Failed to read file: {}", path.display()))?;
+    Ok(hash_bytes(&contents))
+}
+
+/// Hash an in-memory file's contents, matching `hash_file`
+pub fn hash_bytes(contents: &[u8]) -> String {
     let mut hasher = Sha256::new();
-    hasher.update(&contents);
+    hasher.update(contents);
     let result = hasher.finalize();
-    Ok(format!("{:x}", result))
+    format!("{:x}", result)
 }
 
 /// Hash multiple files together (for glob patterns)
 /// Files are sorted alphabetically for deterministic output
 pub fn hash_files(paths: &[PathBuf]) -> Result<String> {
+    hash_files_with(paths, |path| {
+        fs::read(path).with_context(|| format!("Failed to read file: {}", path.display()))
+    })
+}
+
+/// Hash multiple files together, reading contents through `read`.
+/// Used to hash files that are not on disk, e.g. from a git revision.
+pub fn hash_files_with<F>(paths: &[PathBuf], mut read: F) -> Result<String>
+where
+    F: FnMut(&Path) -> Result<Vec<u8>>,
+{
     let mut hasher = Sha256::new();
     let mut sorted_paths = paths.to_vec();
     sorted_paths.sort();
@@ -24,8 +40,7 @@ pub fn hash_files(paths: &[PathBuf]) -> Result<String> {
         // Include relative path in hash for structure sensitivity
         hasher.update(path.to_string_lossy().as_bytes());
         hasher.update(b"\n");
-        let contents =
-            fs::read(path).with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let contents = read(path)?;
         hasher.update(&contents);
         hasher.update(b"\n");
     }
@@ -105,4 +120,26 @@ mod tests {
         let hash2 = hash_file(&file_path).unwrap();
         assert_eq!(hash1, hash2);
     }
+
+    #[test]
+    fn test_hash_files_with_matches_disk() {
+        let dir = TempDir::new().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        fs::write(&a, b"one").unwrap();
+        fs::write(&b, b"two").unwrap();
+
+        let paths = vec![b.clone(), a.clone()];
+        let from_disk = hash_files(&paths).unwrap();
+        let from_memory = hash_files_with(&paths, |p| {
+            Ok(if p == a {
+                b"one".to_vec()
+            } else {
+                b"two".to_vec()
+            })
+        })
+        .unwrap();
+        assert_eq!(from_disk, from_memory);
+        assert_eq!(hash_file(&a).unwrap(), hash_bytes(b"one"));
+    }
 }
@@ -0,0 +1,80 @@
+use anyhow::Result;
+
+use crate::git;
+use crate::paths::PathResolver;
+
+/// Where a drifted entry's acknowledged state sits in git history
+#[derive(Debug)]
+pub struct DriftHistory {
+    /// Commit whose files match the stored hash, if one was found
+    pub acknowledged: Option<String>,
+    /// Commits that touched the entry's files after the acknowledged state,
+    /// newest first (all walked commits when no acknowledged state was found)
+    pub changes: Vec<String>,
+    /// Whether the work tree differs from HEAD for this entry
+    pub uncommitted: bool,
+}
+
+impl DriftHistory {
+    /// The first commit after the acknowledged state that is not skipped
+    pub fn first_change<F>(&self, skip: F) -> Option<&str>
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.acknowledged.as_ref()?;
+        self.changes
+            .iter()
+            .rev()
+            .map(String::as_str)
+            .find(|c| !skip(c))
+    }
+}
+
+/// Walk the commits touching an entry's files, newest first, hashing the
+/// entry at each one until the state matching `stored_hash` is found.
+pub fn trace(
+    resolver: &PathResolver,
+    pattern: &str,
+    stored_hash: &str,
+    current_hash: &str,
+) -> Result<DriftHistory> {
+    let uncommitted =
+        resolver.hash_pattern_at(pattern, "HEAD").ok().as_deref() != Some(current_hash);
+
+    let mut history = DriftHistory {
+        acknowledged: None,
+        changes: Vec::new(),
+        uncommitted,
+    };
+
+    let pathspec = match resolver.pathspec(pattern) {
+        Some(p) => p,
+        None => return Ok(history),
+    };
+
+    for commit in git::commits_touching(resolver.project_root(), &[pathspec])? {
+        if resolver.hash_pattern_at(pattern, &commit).ok().as_deref() == Some(stored_hash) {
+            history.acknowledged = Some(commit);
+            break;
+        }
+        history.changes.push(commit);
+    }
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_change_skips_ignored() {
+        let history = DriftHistory {
+            acknowledged: Some("base".to_string()),
+            changes: vec!["c3".to_string(), "c2".to_string(), "c1".to_string()],
+            uncommitted: false,
+        };
+        assert_eq!(history.first_change(|_| false), Some("c1"));
+        assert_eq!(history.first_change(|c| c == "c1"), Some("c2"));
+        assert_eq!(history.first_change(|_| true), None);
+    }
+}
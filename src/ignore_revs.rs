@@ -0,0 +1,72 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::git;
+use crate::history;
+use crate::paths::PathResolver;
+
+/// File at the project root listing commits whose changes do not count as drift
+pub const IGNORE_REVS_FILE: &str = ".drifty-ignore-revs";
+
+/// Parse an ignore-revs file: one revision per line, `#` starts a comment
+pub fn parse(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// Load the ignore-revs file from the project root, resolving every entry to
+/// a full commit id. A missing file yields an empty set.
+pub fn load(project_root: &Path) -> Result<HashSet<String>> {
+    let path = project_root.join(IGNORE_REVS_FILE);
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+
+    let mut revs = HashSet::new();
+    for rev in parse(&fs::read_to_string(&path)?) {
+        match git::rev_parse(project_root, &rev) {
+            Ok(id) => {
+                revs.insert(id);
+            }
+            Err(_) => eprintln!("Warning: {}: unknown revision '{}'", IGNORE_REVS_FILE, rev),
+        }
+    }
+    Ok(revs)
+}
+
+/// Check whether an entry drifted only because of ignored commits: the
+/// acknowledged state must be in history, every commit since then must be
+/// ignored, and the work tree must match HEAD.
+pub fn only_ignored_changes(
+    resolver: &PathResolver,
+    pattern: &str,
+    stored_hash: &str,
+    current_hash: &str,
+    ignored: &HashSet<String>,
+) -> Result<bool> {
+    if ignored.is_empty() {
+        return Ok(false);
+    }
+
+    let history = history::trace(resolver, pattern, stored_hash, current_hash)?;
+    Ok(history.acknowledged.is_some()
+        && !history.uncommitted
+        && history.changes.iter().all(|c| ignored.contains(c)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let content = "# rustfmt everything\nabc123\n\n  def456  # license headers\n";
+        assert_eq!(parse(content), vec!["abc123", "def456"]);
+    }
+}
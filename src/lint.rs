@@ -0,0 +1,441 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::frontmatter::{self, Frontmatter, Style};
+use crate::merge::REVIEW_HASH;
+use crate::paths::{self, PathResolver};
+use crate::status::{check_entry, keep_docs_state, split_hash, Status};
+
+/// File at the project root setting the severity of lint rules:
+///
+/// ```toml
+/// [rules]
+/// overlapping-patterns = "off"
+/// unquoted-glob = "error"
+/// ```
+pub const LINT_CONFIG_FILE: &str = ".drifty-lint.toml";
+
+/// A named check on a doc's driftwatcher entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Rule {
+    DuplicatePattern,
+    OverlappingPatterns,
+    EscapesRoot,
+    AbsolutePath,
+    MalformedHash,
+    UnquotedGlob,
+    EmptyDriftwatcher,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 7] = [
+        Rule::DuplicatePattern,
+        Rule::OverlappingPatterns,
+        Rule::EscapesRoot,
+        Rule::AbsolutePath,
+        Rule::MalformedHash,
+        Rule::UnquotedGlob,
+        Rule::EmptyDriftwatcher,
+    ];
+
+    /// Name used in output and in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::DuplicatePattern => "duplicate-pattern",
+            Rule::OverlappingPatterns => "overlapping-patterns",
+            Rule::EscapesRoot => "escapes-root",
+            Rule::AbsolutePath => "absolute-path",
+            Rule::MalformedHash => "malformed-hash",
+            Rule::UnquotedGlob => "unquoted-glob",
+            Rule::EmptyDriftwatcher => "empty-driftwatcher",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Rule::DuplicatePattern
+            | Rule::EscapesRoot
+            | Rule::AbsolutePath
+            | Rule::MalformedHash => Severity::Error,
+            Rule::OverlappingPatterns | Rule::UnquotedGlob | Rule::EmptyDriftwatcher => {
+                Severity::Warning
+            }
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    #[serde(alias = "warn")]
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Off => "off",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// Severity of each rule, from the defaults and the project's config file
+#[derive(Debug, Clone)]
+pub struct Config {
+    severities: HashMap<Rule, Severity>,
+}
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    rules: BTreeMap<String, Severity>,
+}
+
+impl Config {
+    /// Load the lint config from the project root. A missing file yields the
+    /// default severities; unknown rules are an error.
+    pub fn load(project_root: &Path) -> Result<Self> {
+        let path = project_root.join(LINT_CONFIG_FILE);
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                Self::parse(&content).with_context(|| format!("Invalid config: {}", path.display()))
+            }
+            Err(_) if !path.exists() => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read file: {}", path.display())),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let file: ConfigFile = toml::from_str(content)?;
+        let mut config = Self::default();
+        for (name, severity) in file.rules {
+            let rule = Rule::from_name(&name).ok_or_else(|| anyhow!("Unknown rule '{}'", name))?;
+            config.severities.insert(rule, severity);
+        }
+        Ok(config)
+    }
+
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.severities[&rule]
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            severities: Rule::ALL
+                .into_iter()
+                .map(|rule| (rule, rule.default_severity()))
+                .collect(),
+        }
+    }
+}
+
+/// How a finding can be fixed automatically
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+    /// Remove one copy of a duplicated entry
+    RemoveEntry { pattern: String },
+    /// Rewrite an entry with a new pattern and hash
+    ReplaceEntry {
+        pattern: String,
+        new_pattern: String,
+        hash: String,
+    },
+    /// Rewrite an entry as it is, which quotes its pattern
+    QuotePattern { pattern: String, hash: String },
+    /// Remove a `driftwatcher` key that holds no entries
+    RemoveDriftwatcher,
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub rule: Rule,
+    /// 1-based line of the entry in the doc, when it has one
+    pub line: Option<usize>,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+/// Run every rule over a doc's frontmatter. `content` is the doc's text,
+/// used to locate entries and see how they are written.
+pub fn check(
+    doc_path: &Path,
+    fm: &Frontmatter,
+    content: &str,
+    resolver: &PathResolver,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let line = |pattern: &str| {
+        if fm.style == Style::Manifest {
+            return None;
+        }
+        frontmatter::find_entry_line(doc_path, content, pattern).map(|l| l + 1)
+    };
+
+    if fm.has_driftwatcher() && fm.entries.is_empty() {
+        findings.push(Finding {
+            rule: Rule::EmptyDriftwatcher,
+            line: None,
+            message: "driftwatcher has no entries".to_string(),
+            fix: (fm.style != Style::Manifest).then_some(Fix::RemoveDriftwatcher),
+        });
+    }
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for entry in &fm.entries {
+        let pattern = entry.pattern.as_str();
+        let count = seen.entry(pattern).or_default();
+        *count += 1;
+        if *count == 2 {
+            findings.push(Finding {
+                rule: Rule::DuplicatePattern,
+                line: line(pattern),
+                message: format!("Pattern '{}' is listed more than once", pattern),
+                fix: Some(Fix::RemoveEntry {
+                    pattern: pattern.to_string(),
+                }),
+            });
+        }
+        if *count > 1 {
+            continue;
+        }
+
+        let (path, _) = paths::split_part(pattern);
+        if is_absolute(path) {
+            let rooted = resolver
+                .repo_relative(Path::new(path))
+                .map(|rel| format!("$ROOT/{}", rel.to_string_lossy().replace('\\', "/")));
+            findings.push(Finding {
+                rule: Rule::AbsolutePath,
+                line: line(pattern),
+                message: format!("Pattern '{}' is an absolute path", pattern),
+                fix: rooted.and_then(|rooted| {
+                    let new_pattern = format!("{}{}", rooted, &pattern[path.len()..]);
+                    repoint(resolver, entry, &new_pattern)
+                }),
+            });
+        } else if resolver.pathspec(pattern).is_none() {
+            findings.push(Finding {
+                rule: Rule::EscapesRoot,
+                line: line(pattern),
+                message: format!("Pattern '{}' points outside the project root", pattern),
+                fix: None,
+            });
+        }
+
+        if let Some(hash) = &entry.hash {
+            if !is_valid_hash(hash) {
+                let lowercase = hash.to_ascii_lowercase();
+                findings.push(Finding {
+                    rule: Rule::MalformedHash,
+                    line: line(pattern),
+                    message: format!(
+                        "Hash '{}' of '{}' is not 64 lowercase hex digits",
+                        hash, pattern
+                    ),
+                    fix: is_valid_hash(&lowercase).then(|| Fix::ReplaceEntry {
+                        pattern: pattern.to_string(),
+                        new_pattern: pattern.to_string(),
+                        hash: lowercase,
+                    }),
+                });
+            }
+        }
+
+        if paths::is_glob_pattern(pattern) && is_unquoted(doc_path, fm.style, content, pattern) {
+            findings.push(Finding {
+                rule: Rule::UnquotedGlob,
+                line: line(pattern),
+                message: format!("Glob pattern '{}' is not quoted", pattern),
+                fix: entry.hash.as_ref().map(|hash| Fix::QuotePattern {
+                    pattern: pattern.to_string(),
+                    hash: hash.clone(),
+                }),
+            });
+        }
+    }
+
+    findings.extend(overlaps(fm, resolver, line));
+    findings.sort_by_key(|finding| finding.line);
+    findings
+}
+
+/// Pairs of distinct patterns that watch some of the same files
+fn overlaps<F>(fm: &Frontmatter, resolver: &PathResolver, line: F) -> Vec<Finding>
+where
+    F: Fn(&str) -> Option<usize>,
+{
+    let mut patterns: Vec<&str> = Vec::new();
+    let mut covered: BTreeMap<PathBuf, Vec<usize>> = BTreeMap::new();
+    for entry in &fm.entries {
+        // Parts of a file (`#body`, `#code-cells`) are a different watch
+        let (_, part) = paths::split_part(&entry.pattern);
+        if part != paths::Part::Whole || patterns.contains(&entry.pattern.as_str()) {
+            continue;
+        }
+        for file in resolver.resolve_files(&entry.pattern).unwrap_or_default() {
+            covered
+                .entry(paths::absolute(&file))
+                .or_default()
+                .push(patterns.len());
+        }
+        patterns.push(&entry.pattern);
+    }
+
+    let mut shared: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+    for indexes in covered.values() {
+        for (i, &a) in indexes.iter().enumerate() {
+            for &b in &indexes[i + 1..] {
+                *shared.entry((a, b)).or_default() += 1;
+            }
+        }
+    }
+
+    shared
+        .into_iter()
+        .map(|((a, b), count)| Finding {
+            rule: Rule::OverlappingPatterns,
+            line: line(patterns[b]),
+            message: format!(
+                "Patterns '{}' and '{}' both watch {} file(s)",
+                patterns[a], patterns[b], count
+            ),
+            fix: None,
+        })
+        .collect()
+}
+
+/// A replacement for an entry that moves it to a new pattern. The new hash
+/// is only computed when the entry is current, so a drifted entry stays
+/// drifted.
+fn repoint(
+    resolver: &PathResolver,
+    entry: &frontmatter::WatchEntry,
+    new_pattern: &str,
+) -> Option<Fix> {
+    let hash = match (check_entry(resolver, entry), &entry.hash) {
+        ((Status::Current, _), Some(stored)) => {
+            keep_docs_state(resolver.hash_pattern(new_pattern).ok()?, stored)
+        }
+        _ => entry.hash.clone()?,
+    };
+    Some(Fix::ReplaceEntry {
+        pattern: entry.pattern.clone(),
+        new_pattern: new_pattern.to_string(),
+        hash,
+    })
+}
+
+fn is_absolute(path: &str) -> bool {
+    let drive = path.as_bytes().get(1) == Some(&b':') && path[..1].chars().all(char::is_alphabetic);
+    path.starts_with('/') || path.starts_with('\\') || drive
+}
+
+/// Whether a hash is 64 lowercase hex digits, followed by as many for the
+/// state of watched docs when it has one, or the merge driver's review marker
+fn is_valid_hash(hash: &str) -> bool {
+    if hash == REVIEW_HASH {
+        return true;
+    }
+    let is_hex =
+        |hash: &str| hash.len() == 64 && hash.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'));
+    let (files, docs) = split_hash(hash);
+    is_hex(files) && docs.is_none_or(is_hex)
+}
+
+/// Whether a YAML entry writes its pattern as a bare key. TOML and JSON keys
+/// with glob characters must be quoted, and directives are never quoted.
+fn is_unquoted(doc_path: &Path, style: Style, content: &str, pattern: &str) -> bool {
+    if !matches!(style, Style::Yaml | Style::AsciiDoc | Style::Rst) {
+        return false;
+    }
+    frontmatter::find_entry_line(doc_path, content, pattern)
+        .and_then(|i| content.lines().nth(i))
+        .and_then(|line| line.trim().strip_prefix("- "))
+        .is_some_and(|key| key.starts_with(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    #[test]
+    fn test_config() {
+        let config =
+            Config::parse("[rules]\nunquoted-glob = \"error\"\nescapes-root = \"off\"\n").unwrap();
+        assert_eq!(config.severity(Rule::UnquotedGlob), Severity::Error);
+        assert_eq!(config.severity(Rule::EscapesRoot), Severity::Off);
+        assert_eq!(config.severity(Rule::DuplicatePattern), Severity::Error);
+        assert!(Config::parse("[rules]\nno-such-rule = \"off\"\n").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        // Globs skip hidden paths, so the directory can't be named `.tmp...`
+        let dir = Builder::new().prefix("lint").tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/a.rs"), "a").unwrap();
+        let doc = root.join("doc.md");
+        let resolver = PathResolver::new(&doc).unwrap();
+        let hash = resolver.hash_pattern("src/a.rs").unwrap();
+
+        let content = format!(
+            "---\ndriftwatcher:\n  - src/*.rs: {hash}\n  - \"src/a.rs\": {hash}\n  - \"src/a.rs\": {hash}\n  - \"../outside.rs\": {upper}\n---\n",
+            hash = hash,
+            upper = hash.to_ascii_uppercase()
+        );
+        let fm = frontmatter::parse(&content).unwrap().unwrap();
+        let findings: Vec<(Rule, Option<usize>, bool)> = check(&doc, &fm, &content, &resolver)
+            .into_iter()
+            .map(|f| (f.rule, f.line, f.fix.is_some()))
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                (Rule::UnquotedGlob, Some(3), true),
+                (Rule::DuplicatePattern, Some(4), true),
+                (Rule::OverlappingPatterns, Some(4), false),
+                (Rule::EscapesRoot, Some(6), false),
+                (Rule::MalformedHash, Some(6), true),
+            ]
+        );
+
+        let empty = "---\ntitle: A\ndriftwatcher: []\n---\n";
+        let fm = frontmatter::parse(empty).unwrap().unwrap();
+        let findings = check(&doc, &fm, empty, &resolver);
+        assert_eq!(findings[0].rule, Rule::EmptyDriftwatcher);
+        assert_eq!(findings[0].fix, Some(Fix::RemoveDriftwatcher));
+    }
+
+    #[test]
+    fn test_is_valid_hash() {
+        let hash = "0".repeat(64);
+        assert!(is_valid_hash(&hash));
+        assert!(is_valid_hash(&format!("{}+{}", hash, hash)));
+        assert!(!is_valid_hash(&format!("{}+", hash)));
+        assert!(!is_valid_hash(&hash.to_ascii_uppercase().replace('0', "A")));
+        assert!(is_valid_hash(REVIEW_HASH));
+    }
+}
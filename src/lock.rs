@@ -0,0 +1,164 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::paths;
+
+/// How long to wait for another drifty process to finish before giving up
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Locks held by this process, by project root, with a count of the guards
+/// sharing each one. File locks conflict between two descriptors of the same
+/// process, so nested acquisitions reuse the first lock.
+static HELD: Mutex<BTreeMap<PathBuf, (File, usize)>> = Mutex::new(BTreeMap::new());
+
+/// Advisory lock serializing doc updates within a project. Other drifty
+/// processes wait for it (up to [`LOCK_TIMEOUT`]) and then fail. The lock is
+/// released when the guard is dropped.
+pub struct ProjectLock {
+    root: Option<PathBuf>,
+}
+
+impl ProjectLock {
+    /// Lock the project containing `path`. Files outside a project are not
+    /// locked.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        Self::acquire_with_timeout(path, LOCK_TIMEOUT)
+    }
+
+    pub fn acquire_with_timeout(path: &Path, timeout: Duration) -> Result<Self> {
+        let dir = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let Ok(root) = paths::find_project_root(dir) else {
+            return Ok(Self { root: None });
+        };
+
+        let lock_path = lock_file(&root);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open lock file: {}", lock_path.display()))?;
+
+        let start = Instant::now();
+        let mut waiting = false;
+        loop {
+            // Only hold HELD while trying, so other acquirers in this process
+            // (the LSP) aren't blocked while we wait
+            {
+                let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some((_, count)) = held.get_mut(&root) {
+                    *count += 1;
+                    return Ok(Self { root: Some(root) });
+                }
+                match file.try_lock() {
+                    Ok(()) => {
+                        held.insert(root.clone(), (file, 1));
+                        return Ok(Self { root: Some(root) });
+                    }
+                    Err(TryLockError::WouldBlock) => {}
+                    Err(TryLockError::Error(e)) => {
+                        return Err(e)
+                            .with_context(|| format!("Failed to lock: {}", lock_path.display()));
+                    }
+                }
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(anyhow!(
+                    "Another drifty process is updating docs in {} (lock file: {})",
+                    root.display(),
+                    lock_path.display()
+                ));
+            }
+            if !waiting {
+                eprintln!("Waiting for another drifty process to finish...");
+                waiting = true;
+            }
+            thread::sleep(RETRY_INTERVAL);
+        }
+    }
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        let Some(root) = &self.root else {
+            return;
+        };
+        let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, count)) = held.get_mut(root) {
+            *count -= 1;
+            if *count == 0 {
+                // Closing the file releases the lock
+                held.remove(root);
+            }
+        }
+    }
+}
+
+/// Like the snooze file, the lock lives inside `.git` so it is never committed
+fn lock_file(project_root: &Path) -> PathBuf {
+    let git_dir = project_root.join(".git");
+    if git_dir.is_dir() {
+        git_dir.join("drifty-lock")
+    } else {
+        project_root.join(".drifty-lock")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_nested_acquire_and_contention() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        let doc = dir.path().join("doc.md");
+
+        let outer = ProjectLock::acquire(&doc).unwrap();
+        let inner = ProjectLock::acquire(&doc).unwrap();
+        drop(inner);
+
+        // A second descriptor (as another process would have) cannot lock
+        let other = File::open(lock_file(dir.path())).unwrap();
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+
+        drop(outer);
+        assert!(other.try_lock().is_ok());
+        assert!(ProjectLock::acquire_with_timeout(&doc, Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn test_waiting_does_not_block_other_projects() {
+        let busy = TempDir::new().unwrap();
+        let free = TempDir::new().unwrap();
+        std::fs::create_dir(busy.path().join(".git")).unwrap();
+        std::fs::create_dir(free.path().join(".git")).unwrap();
+
+        // Another process holds the lock on the busy project
+        let other = File::create(lock_file(busy.path())).unwrap();
+        other.try_lock().unwrap();
+
+        let busy_doc = busy.path().join("doc.md");
+        let waiter = thread::spawn(move || {
+            ProjectLock::acquire_with_timeout(&busy_doc, Duration::from_secs(1)).is_err()
+        });
+        thread::sleep(Duration::from_millis(100));
+
+        let start = Instant::now();
+        let _free = ProjectLock::acquire(&free.path().join("doc.md")).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(waiter.join().unwrap());
+    }
+}
@@ -1,13 +1,31 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
 
+mod annotate;
 mod commands;
+mod document;
 mod frontmatter;
+mod git;
 mod hash;
+mod history;
+mod ignore_revs;
+mod lint;
+mod lock;
+mod manifest;
+mod merge;
+mod notebook;
 mod paths;
+mod plan;
+mod references;
 mod scanner;
+mod snippets;
+mod snooze;
 mod status;
+mod store;
+mod watch;
 
 #[derive(Parser)]
 #[command(name = "drifty")]
@@ -20,7 +38,7 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Initialize a markdown file with empty driftwatcher frontmatter
+    /// Initialize a document with empty driftwatcher frontmatter
     Init {
         /// The documentation file to initialize
         doc_file: PathBuf,
@@ -33,28 +51,246 @@ enum Commands {
 
         /// The file, directory, or glob pattern to watch
         watch_pattern: String,
+
+        /// Hash only the code cells of watched notebooks (appends `#code`)
+        #[arg(long)]
+        code_only: bool,
+
+        /// Keep the entry in the drifty.lock manifest instead of the doc
+        #[arg(long)]
+        manifest: bool,
+    },
+
+    /// Propose entries for the files a doc mentions but doesn't watch
+    Suggest {
+        /// The documentation file
+        doc_file: PathBuf,
+
+        /// Add the proposed entries with fresh hashes
+        #[arg(long)]
+        apply: bool,
     },
 
     /// Check all documentation for drift (interactive)
     Check {
         /// Specific file or directory to check (default: current directory)
         target: Option<PathBuf>,
+
+        /// Write the proposed updates to a plan file instead of prompting
+        #[arg(long)]
+        plan: Option<PathBuf>,
+    },
+
+    /// Apply a plan written by `check --plan`
+    Apply {
+        /// The plan file
+        plan: PathBuf,
     },
 
     /// Report status of all tracked files
     Report {
         /// Output format
         #[arg(long, short, default_value = "plaintext")]
-        format: OutputFormat,
+        format: ReportFormat,
+    },
+
+    /// Write a static HTML dashboard of documentation drift
+    ExportHtml {
+        /// Directory to write index.html into
+        dir: PathBuf,
     },
 
     /// Validate all driftwatcher frontmatter
-    Validate,
+    Validate {
+        /// Also report links, images and repository paths to missing files
+        #[arg(long)]
+        links: bool,
+
+        /// Output format
+        #[arg(long, short, default_value = "plaintext")]
+        format: OutputFormat,
+    },
+
+    /// Report which source files are watched by documentation
+    Coverage {
+        /// Glob pattern of source files to include (repeatable)
+        #[arg(long, short, required = true)]
+        include: Vec<String>,
+
+        /// Output format
+        #[arg(long, short, default_value = "plaintext")]
+        format: CoverageFormat,
+    },
+
+    /// List the docs that watch the given source files
+    WhoDocuments {
+        /// Source files to look up
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Output format
+        #[arg(long, short, default_value = "plaintext")]
+        format: OutputFormat,
+    },
+
+    /// Find the commit where each drifted entry started drifting
+    Blame {
+        /// Specific file or directory to inspect (default: current directory)
+        target: Option<PathBuf>,
+
+        /// Output format
+        #[arg(long, short, default_value = "plaintext")]
+        format: OutputFormat,
+    },
+
+    /// Export the graph of docs and the files they watch
+    Graph {
+        /// Specific file or directory of docs to include (default: current directory)
+        target: Option<PathBuf>,
+
+        /// Output format
+        #[arg(long, short, default_value = "dot")]
+        format: GraphFormat,
+
+        /// Show each glob as one node for the directory it matches in
+        #[arg(long)]
+        collapse_globs: bool,
+
+        /// Only keep watched files and directories inside this path
+        #[arg(long)]
+        subtree: Option<PathBuf>,
+    },
+
+    /// Show which translations lag behind their source docs
+    I18n {
+        /// Specific file or directory to inspect (default: current directory)
+        target: Option<PathBuf>,
+
+        /// Output format
+        #[arg(long, short, default_value = "plaintext")]
+        format: OutputFormat,
+    },
+
+    /// Re-acknowledge entries whose only changes come from ignored revisions
+    Rebaseline {
+        /// Specific file or directory to rebaseline (default: current directory)
+        target: Option<PathBuf>,
+
+        /// Only list the entries that would be updated
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Watch docs and watched files, printing status changes as they happen
+    Watch {
+        /// Specific file or directory to watch (default: current directory)
+        target: Option<PathBuf>,
+
+        /// Emit newline-delimited JSON events
+        #[arg(long)]
+        json: bool,
+
+        /// Poll for changes instead of using filesystem notifications
+        #[arg(long)]
+        poll: bool,
+
+        /// Polling interval in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        interval: u64,
+    },
+
+    /// Check driftwatcher entries against named, configurable lint rules
+    Lint {
+        /// Specific file or directory to lint (default: current directory)
+        target: Option<PathBuf>,
+
+        /// Fix the problems that can be fixed automatically
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Rewrite code snippets (```lang file=... lines=...) from their source
+    SyncSnippets {
+        /// Specific file or directory to update (default: current directory)
+        target: Option<PathBuf>,
+
+        /// Only list the docs that would be updated
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Add or remove an "out of date" notice in the body of drifted docs
+    Annotate {
+        /// Specific file or directory to annotate (default: current directory)
+        target: Option<PathBuf>,
+
+        /// Remove all notices instead
+        #[arg(long)]
+        remove: bool,
+
+        /// Text of the notice
+        #[arg(long)]
+        message: Option<String>,
+    },
+
+    /// mdBook preprocessor that flags outdated chapters
+    MdbookPreprocessor {
+        #[command(subcommand)]
+        supports: Option<MdbookSupports>,
+    },
+
+    /// Run a language server over stdio for live drift diagnostics
+    Lsp,
+
+    /// Git merge driver that merges driftwatcher frontmatter structurally
+    MergeDriver {
+        /// Common ancestor version (%O)
+        base: PathBuf,
+
+        /// Current branch version, receives the result (%A)
+        current: PathBuf,
+
+        /// Other branch version (%B)
+        other: PathBuf,
+
+        /// Path of the document in the work tree (%P)
+        path: Option<PathBuf>,
+
+        /// Recompute conflicting hashes from the work tree instead of
+        /// marking them for review
+        #[arg(long)]
+        recompute: bool,
+    },
+
+    /// Move driftwatcher entries between docs and the drifty.lock manifest
+    #[command(group(
+        ArgGroup::new("direction")
+            .required(true)
+            .args(["to_manifest", "to_frontmatter"])
+    ))]
+    Migrate {
+        /// Specific file or directory to migrate (default: current directory)
+        target: Option<PathBuf>,
+
+        /// Move frontmatter entries into the manifest
+        #[arg(long)]
+        to_manifest: bool,
+
+        /// Move manifest entries into the docs' frontmatter
+        #[arg(long)]
+        to_frontmatter: bool,
+    },
 
     /// Show this help message
     Help,
 }
 
+#[derive(Subcommand)]
+enum MdbookSupports {
+    /// Check whether a renderer is supported (all are)
+    Supports { renderer: String },
+}
+
 #[derive(Clone, Copy, ValueEnum)]
 enum OutputFormat {
     Plaintext,
@@ -62,6 +298,28 @@ enum OutputFormat {
     Yaml,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Plaintext,
+    Json,
+    Yaml,
+    Html,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CoverageFormat {
+    Plaintext,
+    Json,
+    Lcov,
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
@@ -74,10 +332,95 @@ fn main() -> Result<()> {
         Some(Commands::Add {
             doc_file,
             watch_pattern,
-        }) => commands::add::run(&doc_file, &watch_pattern),
-        Some(Commands::Check { target }) => commands::check::run(target.as_deref()),
+            code_only,
+            manifest,
+        }) => commands::add::run(&doc_file, &watch_pattern, code_only, manifest),
+        Some(Commands::Suggest { doc_file, apply }) => commands::suggest::run(&doc_file, apply),
+        Some(Commands::Check { target, plan }) => {
+            commands::check::run(target.as_deref(), plan.as_deref())
+        }
+        Some(Commands::Apply { plan }) => commands::apply::run(&plan),
         Some(Commands::Report { format }) => commands::report::run(format.into()),
-        Some(Commands::Validate) => commands::validate::run(),
+        Some(Commands::ExportHtml { dir }) => commands::export_html::run(&dir),
+        Some(Commands::Validate { links, format }) => commands::validate::run(links, format.into()),
+        Some(Commands::Coverage { include, format }) => {
+            commands::coverage::run(&include, format.into())
+        }
+        Some(Commands::WhoDocuments { files, format }) => {
+            commands::who_documents::run(&files, format.into())
+        }
+        Some(Commands::Blame { target, format }) => {
+            commands::blame::run(target.as_deref(), format.into())
+        }
+        Some(Commands::Graph {
+            target,
+            format,
+            collapse_globs,
+            subtree,
+        }) => commands::graph::run(
+            target.as_deref(),
+            format.into(),
+            collapse_globs,
+            subtree.as_deref(),
+        ),
+        Some(Commands::I18n { target, format }) => {
+            commands::i18n::run(target.as_deref(), format.into())
+        }
+        Some(Commands::Rebaseline { target, dry_run }) => {
+            commands::rebaseline::run(target.as_deref(), dry_run)
+        }
+        Some(Commands::Watch {
+            target,
+            json,
+            poll,
+            interval,
+        }) => commands::watch::run(
+            target.as_deref(),
+            json,
+            poll,
+            Duration::from_millis(interval),
+        ),
+        Some(Commands::Lint { target, fix }) => commands::lint::run(target.as_deref(), fix),
+        Some(Commands::SyncSnippets { target, dry_run }) => {
+            commands::sync_snippets::run(target.as_deref(), dry_run)
+        }
+        Some(Commands::Annotate {
+            target,
+            remove,
+            message,
+        }) => commands::annotate::run(target.as_deref(), remove, message.as_deref()),
+        Some(Commands::MdbookPreprocessor { supports }) => {
+            let renderer = supports.map(|MdbookSupports::Supports { renderer }| renderer);
+            commands::mdbook::run(renderer.as_deref())
+        }
+        Some(Commands::Lsp) => commands::lsp::run(),
+        Some(Commands::MergeDriver {
+            base,
+            current,
+            other,
+            path,
+            recompute,
+        }) => {
+            let clean =
+                commands::merge_driver::run(&base, &current, &other, path.as_deref(), recompute)?;
+            // Exit only now, so the driver's temporary files are gone
+            if !clean {
+                process::exit(1);
+            }
+            Ok(())
+        }
+        Some(Commands::Migrate {
+            target,
+            to_manifest,
+            to_frontmatter: _,
+        }) => {
+            let direction = if to_manifest {
+                commands::migrate::Direction::ToManifest
+            } else {
+                commands::migrate::Direction::ToFrontmatter
+            };
+            commands::migrate::run(target.as_deref(), direction)
+        }
     }
 }
 
@@ -90,21 +433,112 @@ Usage:
   drifty init <doc-file>
       Initializes the doc file with an empty driftwatcher table.
 
-  drifty add <doc-file> <file-to-watch>
+  drifty add <doc-file> <file-to-watch> [--code-only] [--manifest]
       Adds a file to watch to the doc file's frontmatter and computes its
-      initial hash.
+      initial hash. A doc's translation_of source is hashed by its body
+      only. With --code-only, watched notebooks are hashed by their
+      code cells only, ignoring outputs and execution counts. With
+      --manifest, the entry goes in the drifty.lock manifest instead.
+
+  drifty suggest [--apply] <doc-file>
+      Finds files a Markdown doc mentions in code spans (`src/paths.rs`),
+      relative links and fenced code block headers, and lists the ones no
+      entry watches yet. With --apply, adds them with fresh hashes.
 
   drifty check [<filename>]
       Checks all documentation in the current directory (recursively) and
-      checks if there are any updates. Provides an interactive update system.
+      checks if there are any updates. Reviews each drifted or missing entry
+      interactively: see the changed files and diff, then acknowledge, skip,
+      snooze, remove, re-point or edit the doc. Lists entries without making
+      changes when not run in a terminal.
       Optionally specify a specific file or directory to check.
 
-  drifty report [--format json|yaml|plaintext]
+  drifty check --plan <plan.json> [<filename>]
+      Writes every proposed update (doc, pattern, old hash, new hash) to a
+      plan file for review instead of prompting.
+
+  drifty apply <plan.json>
+      Applies a reviewed plan. Refuses to write anything if a stored hash or
+      the watched files changed since the plan was made.
+
+  drifty report [--format json|yaml|html|plaintext]
       Reports status of all tracked files. Useful for CI.
 
-  drifty validate
-      Verifies that all driftwatcher YAML front matter is valid, including
-      file paths.
+  drifty export-html <dir>
+      Writes a self-contained HTML dashboard (index.html) of documentation
+      drift, grouped by directory.
+
+  drifty validate [--links] [--format json|yaml|plaintext]
+      Verifies that all driftwatcher front matter is valid, including
+      file paths. With --links, also reports Markdown links, images and
+      repository paths in code spans that point at missing files, with
+      their line and column.
+
+  drifty who-documents [--format json|yaml|plaintext] <source-file>...
+      Lists every doc and pattern that covers the given source files, with
+      the entry's status.
+
+  drifty coverage --include <glob>... [--format json|lcov|plaintext]
+      Counts how many docs watch each source file matching the include
+      globs, and reports uncovered files and per-directory percentages.
+
+  drifty blame [--format json|yaml|plaintext] [<filename>]
+      For each drifted entry, shows the first commit after the acknowledged
+      hash that changed the watched files, with its author, date and message.
+
+  drifty graph [--format dot|mermaid|json] [--collapse-globs]
+               [--subtree <dir>] [<filename>]
+      Prints the graph of docs and the files they watch, with edges colored
+      by status. --collapse-globs shows each glob as one directory node, and
+      --subtree keeps only watched files inside a directory.
+
+  drifty i18n [--format json|yaml|plaintext] [<filename>]
+      Lists translations (docs with translation_of in their frontmatter) by
+      source doc and locale, and shows the diff of each lagging translation's
+      source since the version it was translated from.
+
+  drifty rebaseline [--dry-run] [<filename>]
+      Updates hashes of drifted entries whose files were only changed by
+      commits listed in .drifty-ignore-revs (formatting, license headers),
+      and hashes of watched docs stored with their entries by older versions.
+
+  drifty watch [--json] [--poll] [--interval <ms>] [<filename>]
+      Keeps running and re-checks affected entries whenever a doc or a
+      watched file changes, printing transitions such as CURRENT -> DRIFTED.
+
+  drifty lint [--fix] [<filename>]
+      Checks driftwatcher entries for duplicate or overlapping patterns,
+      patterns outside the project root or written as absolute paths,
+      malformed hashes, unquoted globs and empty driftwatcher lists. Rule
+      severities are set in .drifty-lint.toml; --fix fixes what it can.
+
+  drifty sync-snippets [--dry-run] [<filename>]
+      Rewrites fenced code blocks that declare their source, such as
+      ```rust file=src/hash.rs lines=7-14, with the current source text.
+      Snippets that differ are reported as SNIPPET_DRIFTED by check and
+      report.
+
+  drifty annotate [--remove] [--message <text>] [<filename>]
+      Inserts a "this page may be out of date" notice at the top of the
+      body of every drifted doc, and removes it from docs that are current.
+
+  drifty mdbook-preprocessor
+      mdBook preprocessor: strips driftwatcher frontmatter from chapters and
+      adds the notice to drifted ones.
+
+  drifty lsp
+      Runs a language server over stdio. Shows drifted, missing and invalid
+      entries as diagnostics in docs, hovers with file lists and hashes,
+      quick fixes, and hints in source files listing the covering docs.
+
+  drifty merge-driver [--recompute] %O %A %B [%P]
+      Git merge driver for documentation files. Merges driftwatcher entries
+      structurally; entries acknowledged differently on both branches are
+      recomputed (--recompute) or marked for review.
+
+  drifty migrate --to-manifest|--to-frontmatter [<filename>]
+      Moves driftwatcher entries out of docs into the drifty.lock manifest
+      at the project root, or back into the docs' frontmatter.
 
   drifty help
       Show this help message."#
@@ -120,3 +554,34 @@ impl From<OutputFormat> for commands::report::OutputFormat {
         }
     }
 }
+
+impl From<ReportFormat> for commands::report::ReportFormat {
+    fn from(f: ReportFormat) -> Self {
+        match f {
+            ReportFormat::Plaintext => commands::report::ReportFormat::Plaintext,
+            ReportFormat::Json => commands::report::ReportFormat::Json,
+            ReportFormat::Yaml => commands::report::ReportFormat::Yaml,
+            ReportFormat::Html => commands::report::ReportFormat::Html,
+        }
+    }
+}
+
+impl From<GraphFormat> for commands::graph::GraphFormat {
+    fn from(f: GraphFormat) -> Self {
+        match f {
+            GraphFormat::Dot => commands::graph::GraphFormat::Dot,
+            GraphFormat::Mermaid => commands::graph::GraphFormat::Mermaid,
+            GraphFormat::Json => commands::graph::GraphFormat::Json,
+        }
+    }
+}
+
+impl From<CoverageFormat> for commands::coverage::CoverageFormat {
+    fn from(f: CoverageFormat) -> Self {
+        match f {
+            CoverageFormat::Plaintext => commands::coverage::CoverageFormat::Plaintext,
+            CoverageFormat::Json => commands::coverage::CoverageFormat::Json,
+            CoverageFormat::Lcov => commands::coverage::CoverageFormat::Lcov,
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::frontmatter::{self, WatchEntry};
+use crate::paths::{self, PathResolver};
+
+/// Name of the manifest at the project root
+pub const MANIFEST_FILE: &str = "drifty.lock";
+
+/// Version written into manifests; other versions are refused
+pub const MANIFEST_VERSION: i64 = 1;
+
+/// Modification time and size of a manifest file, None when there is none
+type Stamp = Option<(SystemTime, u64)>;
+
+/// Manifests read by [`doc_entries`], by project root, with the stamp of the
+/// file each was read from. A scan parses every doc, so this saves reading
+/// the manifest again for each one.
+static CACHE: Mutex<BTreeMap<PathBuf, (Stamp, Arc<Manifest>)>> = Mutex::new(BTreeMap::new());
+
+/// Watch entries for docs that can't carry frontmatter (generated docs,
+/// vendored READMEs), kept in `drifty.lock` at the project root. Docs are
+/// keyed by their path relative to the root, and their patterns resolve
+/// relative to the doc, just like frontmatter entries:
+///
+/// ```toml
+/// version = 1
+///
+/// ["docs/generated/api.md"]
+/// "../../src/api.rs" = "9f2c4e..."
+/// ```
+#[derive(Debug)]
+pub struct Manifest {
+    path: PathBuf,
+    root: PathBuf,
+    docs: BTreeMap<String, Vec<WatchEntry>>,
+}
+
+impl Manifest {
+    /// Load the manifest of a project (empty when it has none)
+    pub fn load(project_root: &Path) -> Result<Self> {
+        let path = project_root.join(MANIFEST_FILE);
+        let docs = match fs::read_to_string(&path) {
+            Ok(content) => {
+                parse(&content).with_context(|| format!("Invalid manifest: {}", path.display()))?
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read file: {}", path.display()))
+            }
+        };
+
+        Ok(Self {
+            path,
+            root: paths::normalize(project_root),
+            docs,
+        })
+    }
+
+    /// Load the manifest of the project containing `doc`; None outside a project
+    pub fn for_doc(doc: &Path) -> Result<Option<Self>> {
+        match PathResolver::new(doc) {
+            Ok(resolver) => Self::load(resolver.project_root()).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Entries of a doc, if the manifest lists it
+    pub fn entries(&self, doc: &Path) -> Option<&Vec<WatchEntry>> {
+        self.docs.get(&self.key(doc)?)
+    }
+
+    pub fn entries_mut(&mut self, doc: &Path) -> Option<&mut Vec<WatchEntry>> {
+        let key = self.key(doc)?;
+        self.docs.get_mut(&key)
+    }
+
+    /// List a doc with the given entries, replacing any it had
+    pub fn set_entries(&mut self, doc: &Path, entries: Vec<WatchEntry>) -> Result<()> {
+        let key = self.key(doc).ok_or_else(|| {
+            anyhow!(
+                "{} is outside the project at {}",
+                doc.display(),
+                self.root.display()
+            )
+        })?;
+        self.docs.insert(key, entries);
+        Ok(())
+    }
+
+    /// Stop listing a doc, returning its entries
+    pub fn remove(&mut self, doc: &Path) -> Option<Vec<WatchEntry>> {
+        let key = self.key(doc)?;
+        self.docs.remove(&key)
+    }
+
+    /// Absolute paths of the listed docs
+    pub fn docs(&self) -> Vec<PathBuf> {
+        self.docs.keys().map(|key| self.root.join(key)).collect()
+    }
+
+    /// Write the manifest back, deleting it once it lists no docs. Callers
+    /// should hold the project lock from before loading it.
+    pub fn save(&self) -> Result<()> {
+        // The stamp may not change if the file is rewritten quickly
+        CACHE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.root);
+        if self.docs.is_empty() {
+            return match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e)
+                    .with_context(|| format!("Failed to remove file: {}", self.path.display())),
+                _ => Ok(()),
+            };
+        }
+        frontmatter::write_file(&self.path, &serialize(&self.docs))
+    }
+
+    /// A doc's key: its path relative to the project root, with `/` separators
+    fn key(&self, doc: &Path) -> Option<String> {
+        let relative = paths::absolute(doc)
+            .strip_prefix(&self.root)
+            .ok()?
+            .to_path_buf();
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+}
+
+/// The entries of a doc, if its project's manifest lists it
+pub fn doc_entries(doc: &Path) -> Result<Option<Vec<WatchEntry>>> {
+    let Ok(resolver) = PathResolver::new(doc) else {
+        return Ok(None);
+    };
+    Ok(cached(resolver.project_root())?.entries(doc).cloned())
+}
+
+/// The manifest of a project, read again only when its file changed
+fn cached(project_root: &Path) -> Result<Arc<Manifest>> {
+    let root = paths::normalize(project_root);
+    let stamp = fs::metadata(root.join(MANIFEST_FILE)).ok().map(|meta| {
+        (
+            meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            meta.len(),
+        )
+    });
+
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached_stamp, manifest)) = cache.get(&root) {
+        if *cached_stamp == stamp {
+            return Ok(manifest.clone());
+        }
+    }
+    let manifest = Arc::new(Manifest::load(&root)?);
+    cache.insert(root, (stamp, manifest.clone()));
+    Ok(manifest)
+}
+
+fn parse(content: &str) -> Result<BTreeMap<String, Vec<WatchEntry>>> {
+    let table: toml::Table = content.parse()?;
+    let mut docs = BTreeMap::new();
+
+    for (doc, value) in table {
+        if doc == "version" {
+            if value.as_integer() != Some(MANIFEST_VERSION) {
+                return Err(anyhow!(
+                    "Unsupported manifest version {} (expected {})",
+                    value,
+                    MANIFEST_VERSION
+                ));
+            }
+            continue;
+        }
+
+        let watches = value
+            .as_table()
+            .ok_or_else(|| anyhow!("'{}' must be a table of pattern = hash entries", doc))?;
+        let entries = watches
+            .iter()
+            .map(|(pattern, hash)| {
+                let hash = hash.as_str().ok_or_else(|| {
+                    anyhow!("Hash of '{}' in '{}' must be a string", pattern, doc)
+                })?;
+                Ok(WatchEntry {
+                    pattern: pattern.clone(),
+                    hash: Some(hash.to_string()).filter(|h| !h.is_empty()),
+                })
+            })
+            .collect::<Result<_>>()?;
+        docs.insert(doc, entries);
+    }
+    Ok(docs)
+}
+
+fn serialize(docs: &BTreeMap<String, Vec<WatchEntry>>) -> String {
+    let mut out = format!(
+        "# Watch entries for docs without frontmatter, maintained by drifty\nversion = {}\n",
+        MANIFEST_VERSION
+    );
+    for (doc, entries) in docs {
+        out.push_str(&format!("\n[{}]\n", quote(doc)));
+        for entry in entries {
+            out.push_str(&format!(
+                "{} = {}\n",
+                quote(&entry.pattern),
+                quote(entry.hash.as_deref().unwrap_or(""))
+            ));
+        }
+    }
+    out
+}
+
+/// A string as a quoted TOML key or value
+fn quote(s: &str) -> String {
+    toml::Value::String(s.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        let doc = root.join("docs/vendored README.md");
+
+        let mut manifest = Manifest::load(root).unwrap();
+        assert!(manifest.entries(&doc).is_none());
+        manifest
+            .set_entries(
+                &doc,
+                vec![
+                    WatchEntry {
+                        pattern: "../src/*.rs".to_string(),
+                        hash: Some("abc".to_string()),
+                    },
+                    WatchEntry {
+                        pattern: "$ROOT/Cargo.toml".to_string(),
+                        hash: None,
+                    },
+                ],
+            )
+            .unwrap();
+        manifest.save().unwrap();
+
+        let content = fs::read_to_string(root.join(MANIFEST_FILE)).unwrap();
+        assert!(content.contains("[\"docs/vendored README.md\"]\n\"../src/*.rs\" = \"abc\"\n"));
+
+        let entries = doc_entries(&doc).unwrap().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].pattern, "$ROOT/Cargo.toml");
+        assert!(entries[1].hash.is_none());
+
+        // The manifest goes away with its last doc
+        let mut manifest = Manifest::load(root).unwrap();
+        manifest.remove(&doc);
+        manifest.save().unwrap();
+        assert!(!root.join(MANIFEST_FILE).exists());
+    }
+
+    #[test]
+    fn test_doc_entries_follow_changes() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        let doc = root.join("api.md");
+        let entry = |hash: &str| WatchEntry {
+            pattern: "a.rs".to_string(),
+            hash: Some(hash.to_string()),
+        };
+        let stored = || {
+            doc_entries(&doc)
+                .unwrap()
+                .map(|entries| entries[0].hash.clone())
+        };
+
+        let mut manifest = Manifest::load(root).unwrap();
+        manifest.set_entries(&doc, vec![entry("abc")]).unwrap();
+        manifest.save().unwrap();
+        assert_eq!(stored(), Some(Some("abc".to_string())));
+
+        // Saved by this process, with the same size
+        manifest.set_entries(&doc, vec![entry("def")]).unwrap();
+        manifest.save().unwrap();
+        assert_eq!(stored(), Some(Some("def".to_string())));
+
+        // Edited outside drifty
+        fs::write(root.join(MANIFEST_FILE), "version = 1\n").unwrap();
+        assert_eq!(stored(), None);
+    }
+
+    #[test]
+    fn test_rejects_unknown_version() {
+        assert!(parse("version = 2\n").is_err());
+        assert!(parse("version = 1\n[\"a.md\"]\n\"b.rs\" = 3\n").is_err());
+    }
+}
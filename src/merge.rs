@@ -0,0 +1,134 @@
+use crate::frontmatter::WatchEntry;
+
+/// Hash written for entries whose two sides disagree and could not be
+/// recomputed. It never matches a real hash, so the entry shows up as
+/// DRIFTED until someone reviews it with `drifty check`. `drifty lint`
+/// accepts it as well-formed.
+pub const REVIEW_HASH: &str = "needs-review";
+
+/// Result of merging the driftwatcher entries of two branches
+#[derive(Debug)]
+pub struct MergedEntries {
+    pub entries: Vec<WatchEntry>,
+    /// Patterns where both sides acknowledged different hashes
+    pub conflicts: Vec<String>,
+}
+
+/// Three-way merge of watch entries.
+///
+/// Entries added on either side are kept (ours first, then theirs), entries
+/// deleted on one side and untouched on the other are dropped, and a hash
+/// changed on only one side wins. When both sides changed the hash of the
+/// same pattern to different values the pattern is reported as a conflict and
+/// its hash is left as `None` for the caller to resolve.
+pub fn merge_entries(
+    base: &[WatchEntry],
+    ours: &[WatchEntry],
+    theirs: &[WatchEntry],
+) -> MergedEntries {
+    let find = |entries: &[WatchEntry], pattern: &str| {
+        entries
+            .iter()
+            .find(|e| e.pattern == pattern)
+            .map(|e| e.hash.clone())
+    };
+
+    let mut entries = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for entry in ours {
+        let base_hash = find(base, &entry.pattern);
+        match find(theirs, &entry.pattern) {
+            Some(their_hash) => {
+                if their_hash == entry.hash || base_hash.as_ref() == Some(&their_hash) {
+                    entries.push(entry.clone());
+                } else if base_hash.as_ref() == Some(&entry.hash) {
+                    entries.push(WatchEntry {
+                        pattern: entry.pattern.clone(),
+                        hash: their_hash,
+                    });
+                } else {
+                    conflicts.push(entry.pattern.clone());
+                    entries.push(WatchEntry {
+                        pattern: entry.pattern.clone(),
+                        hash: None,
+                    });
+                }
+            }
+            None => {
+                // Deleted on their side: honour it unless we changed the entry
+                if base_hash.as_ref() != Some(&entry.hash) {
+                    entries.push(entry.clone());
+                }
+            }
+        }
+    }
+
+    for entry in theirs {
+        if find(ours, &entry.pattern).is_some() {
+            continue;
+        }
+        let base_hash = find(base, &entry.pattern);
+        if base_hash.as_ref() != Some(&entry.hash) {
+            entries.push(entry.clone());
+        }
+    }
+
+    MergedEntries { entries, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn e(pattern: &str, hash: &str) -> WatchEntry {
+        WatchEntry {
+            pattern: pattern.to_string(),
+            hash: Some(hash.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_one_side_changed() {
+        let base = vec![e("a.rs", "1"), e("b.rs", "1")];
+        let ours = vec![e("a.rs", "2"), e("b.rs", "1")];
+        let theirs = vec![e("a.rs", "1"), e("b.rs", "3")];
+
+        let merged = merge_entries(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.entries[0].hash.as_deref(), Some("2"));
+        assert_eq!(merged.entries[1].hash.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_union_of_added_entries() {
+        let base = vec![e("a.rs", "1")];
+        let ours = vec![e("a.rs", "1"), e("b.rs", "2")];
+        let theirs = vec![e("a.rs", "1"), e("c.rs", "3")];
+
+        let merged = merge_entries(&base, &ours, &theirs);
+        let patterns: Vec<_> = merged.entries.iter().map(|e| e.pattern.as_str()).collect();
+        assert_eq!(patterns, vec!["a.rs", "b.rs", "c.rs"]);
+    }
+
+    #[test]
+    fn test_deleted_entry_stays_deleted() {
+        let base = vec![e("a.rs", "1"), e("b.rs", "1")];
+        let ours = vec![e("a.rs", "1")];
+        let theirs = vec![e("a.rs", "1"), e("b.rs", "1")];
+
+        let merged = merge_entries(&base, &ours, &theirs);
+        assert_eq!(merged.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_both_changed_is_conflict() {
+        let base = vec![e("a.rs", "1")];
+        let ours = vec![e("a.rs", "2")];
+        let theirs = vec![e("a.rs", "3")];
+
+        let merged = merge_entries(&base, &ours, &theirs);
+        assert_eq!(merged.conflicts, vec!["a.rs".to_string()]);
+        assert_eq!(merged.entries[0].hash, None);
+    }
+}
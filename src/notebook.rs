@@ -0,0 +1,264 @@
+//! Jupyter notebooks, as documents and as watched files.
+//!
+//! A notebook keeps its entries in the notebook-level `metadata.driftwatcher`
+//! object. Edits replace just that value in the file text, so the rest of the
+//! JSON stays byte-for-byte as Jupyter wrote it.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::frontmatter::line_ending;
+
+/// Whether a parsed JSON object is a notebook rather than JSON frontmatter
+pub fn is_notebook(object: &Map<String, Value>) -> bool {
+    object.contains_key("nbformat") && object.contains_key("cells")
+}
+
+/// The `metadata.driftwatcher` object, if present
+pub fn entries(object: &Map<String, Value>) -> Option<&Value> {
+    object.get("metadata")?.get("driftwatcher")
+}
+
+/// Apply `edit` to the `metadata.driftwatcher` object (created if missing)
+/// and write it back into the notebook text
+pub fn edit_entries<F>(content: &str, edit: F) -> Result<String>
+where
+    F: FnOnce(&mut Map<String, Value>) -> Result<()>,
+{
+    let notebook: Map<String, Value> = serde_json::from_str(content)?;
+    let mut dw = match entries(&notebook) {
+        Some(Value::Object(dw)) => dw.clone(),
+        Some(_) => return Err(anyhow!("metadata.driftwatcher must be an object")),
+        None => Map::new(),
+    };
+    edit(&mut dw)?;
+
+    let bytes = content.as_bytes();
+    let root = skip_whitespace(bytes, 0);
+    let metadata = find_member(content, root, "metadata")?
+        .ok_or_else(|| anyhow!("Notebook has no metadata"))?;
+    if bytes.get(metadata.start) != Some(&b'{') {
+        return Err(anyhow!("Notebook metadata is not an object"));
+    }
+
+    // Match the notebook's indentation (Jupyter uses one space)
+    let eol = line_ending(content);
+    let unit = content
+        .lines()
+        .skip(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or(" ");
+    let member_indent = unit.repeat(2);
+
+    let mut json = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(unit.as_bytes());
+    dw.serialize(&mut serde_json::Serializer::with_formatter(
+        &mut json, formatter,
+    ))?;
+    let value = String::from_utf8(json)?.replace('\n', &format!("{}{}", eol, member_indent));
+
+    let (range, replacement) = match find_member(content, metadata.start, "driftwatcher")? {
+        Some(span) => (span, value),
+        None => {
+            let after_brace = metadata.start + 1;
+            let empty = bytes.get(skip_whitespace(bytes, after_brace)) == Some(&b'}');
+            let member = format!("{}{}\"driftwatcher\": {}", eol, member_indent, value);
+            if empty {
+                let close = skip_whitespace(bytes, after_brace);
+                (after_brace..close, format!("{}{}{}", member, eol, unit))
+            } else {
+                (after_brace..after_brace, format!("{},", member))
+            }
+        }
+    };
+
+    Ok(format!(
+        "{}{}{}",
+        &content[..range.start],
+        replacement,
+        &content[range.end..]
+    ))
+}
+
+/// The source of a notebook's code cells, for hashing a notebook without its
+/// outputs, execution counts and markdown cells. Other files pass through.
+pub fn code_cells(path: &Path, contents: Vec<u8>) -> Result<Vec<u8>> {
+    if path.extension().is_none_or(|ext| ext != "ipynb") {
+        return Ok(contents);
+    }
+
+    let notebook: Value = serde_json::from_slice(&contents)
+        .map_err(|e| anyhow!("{} is not a valid notebook: {}", path.display(), e))?;
+    let sources: Vec<String> = notebook
+        .get("cells")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter(|cell| cell.get("cell_type").and_then(Value::as_str) == Some("code"))
+        .map(|cell| match cell.get("source") {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Array(lines)) => lines.iter().filter_map(Value::as_str).collect(),
+            _ => String::new(),
+        })
+        .collect();
+    Ok(serde_json::to_vec(&sources)?)
+}
+
+/// Find the value of `key` in the JSON object whose `{` is at `object_start`
+fn find_member(content: &str, object_start: usize, key: &str) -> Result<Option<Range<usize>>> {
+    let bytes = content.as_bytes();
+    let mut i = object_start + 1;
+    loop {
+        i = skip_whitespace(bytes, i);
+        match bytes.get(i) {
+            Some(b'}') => return Ok(None),
+            Some(b'"') => {}
+            _ => return Err(anyhow!("Malformed notebook JSON at byte {}", i)),
+        }
+
+        let key_end = skip_string(bytes, i)?;
+        let name: String = serde_json::from_str(&content[i..key_end])?;
+        i = skip_whitespace(bytes, key_end);
+        if bytes.get(i) != Some(&b':') {
+            return Err(anyhow!("Malformed notebook JSON at byte {}", i));
+        }
+        let value_start = skip_whitespace(bytes, i + 1);
+        let value_end = skip_value(bytes, value_start)?;
+        if name == key {
+            return Ok(Some(value_start..value_end));
+        }
+
+        i = skip_whitespace(bytes, value_end);
+        if bytes.get(i) == Some(&b',') {
+            i += 1;
+        }
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while bytes.get(i).is_some_and(|b| b.is_ascii_whitespace()) {
+        i += 1;
+    }
+    i
+}
+
+/// End (exclusive) of the string starting at `i`
+fn skip_string(bytes: &[u8], mut i: usize) -> Result<usize> {
+    i += 1;
+    while let Some(&b) = bytes.get(i) {
+        match b {
+            b'\\' => i += 2,
+            b'"' => return Ok(i + 1),
+            _ => i += 1,
+        }
+    }
+    Err(anyhow!("Unterminated string in notebook JSON"))
+}
+
+/// End (exclusive) of the value starting at `i`
+fn skip_value(bytes: &[u8], mut i: usize) -> Result<usize> {
+    match bytes.get(i) {
+        Some(b'"') => skip_string(bytes, i),
+        Some(b'{') | Some(b'[') => {
+            let mut depth = 0;
+            while let Some(&b) = bytes.get(i) {
+                match b {
+                    b'"' => {
+                        i = skip_string(bytes, i)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            Err(anyhow!("Unterminated value in notebook JSON"))
+        }
+        Some(_) => {
+            while bytes
+                .get(i)
+                .is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+            {
+                i += 1;
+            }
+            Ok(i)
+        }
+        None => Err(anyhow!("Unexpected end of notebook JSON")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {
+   "cell_type": "code",
+   "execution_count": 3,
+   "metadata": {},
+   "outputs": [{"text": "} tricky \" output"}],
+   "source": ["import pandas\n", "load()"]
+  },
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": "# Notes"
+  }
+ ],
+ "metadata": {
+  "kernelspec": {"name": "python3"}
+ },
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"##;
+
+    #[test]
+    fn test_edit_entries_only_touches_metadata() {
+        let added = edit_entries(NOTEBOOK, |dw| {
+            dw.insert("etl.py".to_string(), Value::String("abc".to_string()));
+            Ok(())
+        })
+        .unwrap();
+        let expected = NOTEBOOK.replace(
+            " \"metadata\": {\n  \"kernelspec\"",
+            " \"metadata\": {\n  \"driftwatcher\": {\n   \"etl.py\": \"abc\"\n  },\n  \"kernelspec\"",
+        );
+        assert_eq!(added, expected);
+
+        let cleared = edit_entries(&added, |dw| {
+            dw.clear();
+            Ok(())
+        })
+        .unwrap();
+        assert!(cleared.contains("  \"driftwatcher\": {},\n  \"kernelspec\""));
+        assert!(cleared.ends_with("\"nbformat_minor\": 5\n}\n"));
+    }
+
+    #[test]
+    fn test_code_cells_ignore_outputs() {
+        let path = Path::new("a.ipynb");
+        let before = code_cells(path, NOTEBOOK.as_bytes().to_vec()).unwrap();
+        let rerun = NOTEBOOK
+            .replace("\"execution_count\": 3", "\"execution_count\": 7")
+            .replace("tricky", "different")
+            .replace("# Notes", "# Other notes");
+        assert_eq!(before, code_cells(path, rerun.into_bytes()).unwrap());
+        assert_eq!(before, br#"["import pandas\nload()"]"#);
+
+        let plain = code_cells(Path::new("a.py"), b"x".to_vec()).unwrap();
+        assert_eq!(plain, b"x");
+    }
+}
@@ -1,8 +1,41 @@
 use anyhow::{anyhow, Context, Result};
-use glob::glob;
-use std::path::{Path, PathBuf};
+use glob::{glob, MatchOptions, Pattern};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 
+use crate::frontmatter;
+use crate::git;
 use crate::hash;
+use crate::notebook;
+use crate::scanner;
+
+/// Suffix on a pattern that hashes only the code cells of the notebooks it
+/// matches, ignoring outputs, execution counts and markdown cells
+pub const CODE_CELLS_SUFFIX: &str = "#code";
+
+/// Suffix on a pattern that hashes only the body of the docs it matches,
+/// ignoring their frontmatter (used for the source of a translation)
+pub const BODY_SUFFIX: &str = "#body";
+
+/// Which part of the matched files a pattern hashes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    Whole,
+    CodeCells,
+    Body,
+}
+
+/// Split the part suffix off a pattern
+pub fn split_part(pattern: &str) -> (&str, Part) {
+    if let Some(stripped) = pattern.strip_suffix(CODE_CELLS_SUFFIX) {
+        (stripped, Part::CodeCells)
+    } else if let Some(stripped) = pattern.strip_suffix(BODY_SUFFIX) {
+        (stripped, Part::Body)
+    } else {
+        (pattern, Part::Whole)
+    }
+}
 
 /// Handles path resolution relative to a document file
 pub struct PathResolver {
@@ -33,6 +66,7 @@ impl PathResolver {
 
     /// Resolve a pattern from frontmatter to actual file paths
     pub fn resolve(&self, pattern: &str) -> Result<Vec<PathBuf>> {
+        let (pattern, _) = split_part(pattern);
         let (base, relative_pattern) = if let Some(stripped) = pattern.strip_prefix("$ROOT/") {
             (&self.project_root, stripped)
         } else {
@@ -42,8 +76,48 @@ impl PathResolver {
         self.resolve_from(base, relative_pattern)
     }
 
+    /// A path relative to the doc, or to the project root with `$ROOT/`,
+    /// taken literally rather than as a glob
+    pub fn literal_path(&self, path: &str) -> PathBuf {
+        match path.strip_prefix("$ROOT/") {
+            Some(stripped) => self.project_root.join(stripped),
+            None => self.doc_dir.join(path),
+        }
+    }
+
+    /// Resolve a pattern to the individual files it covers, expanding
+    /// directories recursively (hidden files excluded)
+    pub fn resolve_files(&self, pattern: &str) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for path in self.resolve(pattern)? {
+            if path.is_dir() {
+                files.extend(hash::collect_files_recursive(&path)?);
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
     /// Compute the hash for a pattern (handles files, directories, and globs)
     pub fn hash_pattern(&self, pattern: &str) -> Result<String> {
+        self.hash_pattern_with(pattern, true)
+    }
+
+    /// The hash older versions computed for a pattern, with the entries of
+    /// docs among its files included. Used to rebaseline their stored hashes.
+    pub fn legacy_hash_pattern(&self, pattern: &str) -> Result<String> {
+        self.hash_pattern_with(pattern, false)
+    }
+
+    fn hash_pattern_with(&self, pattern: &str, strip_entries: bool) -> Result<String> {
+        let (_, part) = split_part(pattern);
+        let read = |path: &Path| -> Result<Vec<u8>> {
+            let contents = fs::read(path)
+                .with_context(|| format!("Failed to read file: {}", path.display()))?;
+            hashed_contents(path, contents, part, strip_entries)
+        };
+
         let paths = self.resolve(pattern)?;
 
         if paths.is_empty() {
@@ -53,7 +127,13 @@ impl PathResolver {
         if paths.len() == 1 {
             let path = &paths[0];
             if path.is_dir() {
-                hash::hash_directory(path)
+                let files = hash::collect_files_recursive(path)?;
+                if files.is_empty() {
+                    return hash::hash_directory(path);
+                }
+                self.hash_files_named(pattern, &files, read)
+            } else if part != Part::Whole || scanner::is_document(path) {
+                Ok(hash::hash_bytes(&read(path)?))
             } else {
                 hash::hash_file(path)
             }
@@ -63,7 +143,209 @@ impl PathResolver {
             if files.is_empty() {
                 return Err(anyhow!("Pattern '{}' matches no files", pattern));
             }
-            hash::hash_files(&files)
+            self.hash_files_named(pattern, &files, read)
+        }
+    }
+
+    /// Hash files together under the names a run from the project root gives
+    /// them, so the hash doesn't depend on the directory drifty runs in
+    fn hash_files_named<F>(&self, pattern: &str, files: &[PathBuf], mut read: F) -> Result<String>
+    where
+        F: FnMut(&Path) -> Result<Vec<u8>>,
+    {
+        let named: BTreeMap<PathBuf, &PathBuf> = files
+            .iter()
+            .map(|file| (self.hash_name(pattern, file), file))
+            .collect();
+        let names: Vec<PathBuf> = named.keys().cloned().collect();
+        hash::hash_files_with(&names, |name| read(named[name]))
+    }
+
+    /// The path of a resolved file as `drifty` run from the project root
+    /// resolves it (e.g. `./docs/../src/a.rs`). Files under `$ROOT/` are
+    /// resolved from the absolute root and keep their path.
+    fn hash_name(&self, pattern: &str, path: &Path) -> PathBuf {
+        let (pattern, _) = split_part(pattern);
+        let Some(dir) = self.repo_relative(&self.doc_dir) else {
+            return path.to_path_buf();
+        };
+        let doc_dir = self.doc_dir.strip_prefix(".").unwrap_or(&self.doc_dir);
+        let rest = match path.strip_prefix(".").unwrap_or(path).strip_prefix(doc_dir) {
+            Ok(rest) if !pattern.starts_with("$ROOT/") => rest,
+            _ => return path.to_path_buf(),
+        };
+        // `glob` drops the leading `./` of the paths it returns
+        if is_glob_pattern(pattern) {
+            dir.join(rest)
+        } else {
+            Path::new(".").join(dir).join(rest)
+        }
+    }
+
+    /// The pattern for a path mentioned in a doc's text: the path itself when
+    /// it exists relative to the doc, or else rooted at the project root. Paths
+    /// starting with `/` are relative to the site, taken as the project.
+    pub fn mention_pattern(&self, path: &str) -> Option<String> {
+        let rooted = format!(
+            "$ROOT/{}",
+            path.trim_start_matches("./").trim_start_matches('/')
+        );
+        let candidates = [
+            (!path.starts_with('/')).then(|| path.to_string()),
+            Some(rooted),
+        ];
+        candidates
+            .into_iter()
+            .flatten()
+            .find(|p| self.resolve(p).is_ok_and(|found| !found.is_empty()))
+    }
+
+    /// The project root that `$ROOT/` patterns are resolved against
+    pub fn project_root(&self) -> &Path {
+        &self.project_root
+    }
+
+    /// Path of `path` relative to the project root, with `.` and `..` removed
+    pub fn repo_relative(&self, path: &Path) -> Option<PathBuf> {
+        absolute(path)
+            .strip_prefix(normalize(&self.project_root))
+            .ok()
+            .map(Path::to_path_buf)
+    }
+
+    /// Repository-relative path containing every file the pattern can match
+    /// (the pattern up to its first glob component)
+    pub fn pathspec(&self, pattern: &str) -> Option<PathBuf> {
+        let (literal, _) = split_literal_prefix(&self.full_pattern(pattern));
+        self.repo_relative(&literal)
+    }
+
+    /// Git pathspec (relative to the project root) matching the pattern's files
+    pub fn git_pathspec(&self, pattern: &str) -> Option<String> {
+        let (literal, rest) = split_literal_prefix(&self.full_pattern(pattern));
+        let literal = self.repo_relative(&literal)?;
+        if rest.as_os_str().is_empty() {
+            let spec = literal.display().to_string();
+            return Some(if spec.is_empty() {
+                ".".to_string()
+            } else {
+                spec
+            });
+        }
+        Some(format!(":(glob){}", literal.join(rest).display()))
+    }
+
+    /// Compute the hash for a pattern as it was at a git revision.
+    ///
+    /// Mirrors `hash_pattern`, including the path strings fed into multi-file
+    /// hashes, so the result can be compared with hashes stored in frontmatter.
+    pub fn hash_pattern_at(&self, pattern: &str, rev: &str) -> Result<String> {
+        let tree: BTreeSet<PathBuf> = git::list_files(&self.project_root, rev)?
+            .into_iter()
+            .collect();
+        let (_, part) = split_part(pattern);
+        let read = |display: &Path| -> Result<Vec<u8>> {
+            let rel = self
+                .repo_relative(display)
+                .ok_or_else(|| anyhow!("Path outside project: {}", display.display()))?;
+            let contents = git::read_file(&self.project_root, rev, &rel)?;
+            hashed_contents(display, contents, part, true)
+        };
+
+        let matches = self.resolve_in_tree(pattern, &tree);
+        if matches.is_empty() {
+            return Err(anyhow!("Pattern '{}' matches no files at {}", pattern, rev));
+        }
+
+        if matches.len() == 1 {
+            let (display, rel) = &matches[0];
+            if tree.contains(rel) {
+                return Ok(hash::hash_bytes(&read(display)?));
+            }
+            let files: Vec<PathBuf> = tree
+                .iter()
+                .filter_map(|f| f.strip_prefix(rel).ok())
+                .filter(|f| !is_hidden(f))
+                .map(|f| display.join(f))
+                .collect();
+            return self.hash_files_named(pattern, &files, read);
+        }
+
+        let files: Vec<PathBuf> = matches
+            .into_iter()
+            .filter(|(_, rel)| tree.contains(rel))
+            .map(|(display, _)| display)
+            .collect();
+        if files.is_empty() {
+            return Err(anyhow!("Pattern '{}' matches no files at {}", pattern, rev));
+        }
+        self.hash_files_named(pattern, &files, read)
+    }
+
+    /// Resolve a pattern against a list of repository-relative files instead of
+    /// the filesystem. Returns `(path as resolve() would return it, repo path)`.
+    fn resolve_in_tree(&self, pattern: &str, tree: &BTreeSet<PathBuf>) -> Vec<(PathBuf, PathBuf)> {
+        let (pattern, _) = split_part(pattern);
+        let full_pattern = self.full_pattern(pattern);
+
+        if !is_glob_pattern(pattern) {
+            let rel = match self.repo_relative(&full_pattern) {
+                Some(rel) => rel,
+                None => return vec![],
+            };
+            let exists = tree.iter().any(|f| f.starts_with(&rel));
+            return if exists {
+                vec![(full_pattern, rel)]
+            } else {
+                vec![]
+            };
+        }
+
+        let (literal, _) = split_literal_prefix(&full_pattern);
+        let literal_rel = match self.repo_relative(&literal) {
+            Some(rel) => rel,
+            None => return vec![],
+        };
+        let matcher = match Pattern::new(&without_cur_dir(&full_pattern).to_string_lossy()) {
+            Ok(m) => m,
+            Err(_) => return vec![],
+        };
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+
+        // Candidates are all files plus every directory containing one
+        let mut candidates: BTreeSet<&Path> = BTreeSet::new();
+        for file in tree {
+            let mut current = Some(file.as_path());
+            while let Some(path) = current {
+                if path.as_os_str().is_empty() || !candidates.insert(path) {
+                    break;
+                }
+                current = path.parent();
+            }
+        }
+
+        candidates
+            .into_iter()
+            .filter_map(|rel| {
+                let display = literal.join(rel.strip_prefix(&literal_rel).ok()?);
+                if matcher.matches_path_with(&display, options) && !is_hidden(&display) {
+                    Some((display, rel.to_path_buf()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn full_pattern(&self, pattern: &str) -> PathBuf {
+        let (pattern, _) = split_part(pattern);
+        match pattern.strip_prefix("$ROOT/") {
+            Some(stripped) => self.project_root.join(stripped),
+            None => self.doc_dir.join(pattern),
         }
     }
 
@@ -103,8 +385,31 @@ impl PathResolver {
     }
 }
 
+/// The part of a watched file that is hashed: only the code cells of notebooks
+/// with the code-cells suffix, only the body of docs with the body suffix, and
+/// docs without their driftwatcher entries, so re-acknowledging a doc doesn't
+/// drift the docs that watch it (they are flagged through [`Propagation`])
+///
+/// [`Propagation`]: crate::status::Propagation
+fn hashed_contents(
+    path: &Path,
+    contents: Vec<u8>,
+    part: Part,
+    strip_entries: bool,
+) -> Result<Vec<u8>> {
+    if part == Part::CodeCells {
+        notebook::code_cells(path, contents)
+    } else if part == Part::Body {
+        Ok(frontmatter::body(path, contents))
+    } else if strip_entries && scanner::is_document(path) {
+        Ok(frontmatter::without_entries(path, contents))
+    } else {
+        Ok(contents)
+    }
+}
+
 /// Find project root by walking up to find .git directory
-fn find_project_root(start: &Path) -> Result<PathBuf> {
+pub fn find_project_root(start: &Path) -> Result<PathBuf> {
     let start = if start.is_absolute() {
         start.to_path_buf()
     } else {
@@ -131,13 +436,65 @@ fn find_project_root(start: &Path) -> Result<PathBuf> {
     }
 }
 
+/// Absolute, normalized form of a path, for comparing paths that were
+/// reached from different directories
+pub fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        normalize(path)
+    } else {
+        match std::env::current_dir() {
+            Ok(cwd) => normalize(&cwd.join(path)),
+            Err(_) => normalize(path),
+        }
+    }
+}
+
+/// Lexically normalize a path, resolving `.` and `..` components
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !result.pop() {
+                    result.push("..");
+                }
+            }
+            other => result.push(other.as_os_str()),
+        }
+    }
+    result
+}
+
+/// Split a full pattern into its literal leading components and the rest.
+/// Leading `.` components are dropped, matching the paths `glob` returns.
+fn split_literal_prefix(pattern: &Path) -> (PathBuf, PathBuf) {
+    let mut literal = PathBuf::new();
+    let mut rest = PathBuf::new();
+    for component in without_cur_dir(pattern).components() {
+        if rest.as_os_str().is_empty() && !is_glob_pattern(&component.as_os_str().to_string_lossy())
+        {
+            literal.push(component);
+        } else {
+            rest.push(component);
+        }
+    }
+    (literal, rest)
+}
+
+fn without_cur_dir(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
 /// Check if a pattern contains glob characters
-fn is_glob_pattern(s: &str) -> bool {
+pub fn is_glob_pattern(s: &str) -> bool {
     s.contains('*') || s.contains('?') || s.contains('[')
 }
 
 /// Check if a path component is hidden (starts with . but not ..)
-fn is_hidden(path: &Path) -> bool {
+pub fn is_hidden(path: &Path) -> bool {
     path.components().any(|c| {
         let s = c.as_os_str().to_string_lossy();
         s.starts_with('.') && s != "." && s != ".."
@@ -170,4 +527,47 @@ mod tests {
         assert!(!is_hidden(Path::new("./src/main.rs")));
         assert!(!is_hidden(Path::new("foo/../bar/file.rs")));
     }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(Path::new("docs/../src/a.rs")),
+            PathBuf::from("src/a.rs")
+        );
+        assert_eq!(
+            normalize(Path::new("./src/./a.rs")),
+            PathBuf::from("src/a.rs")
+        );
+        assert_eq!(normalize(Path::new("../a.rs")), PathBuf::from("../a.rs"));
+    }
+
+    #[test]
+    fn test_split_literal_prefix() {
+        let (literal, rest) = split_literal_prefix(Path::new("./docs/../src/**/*.rs"));
+        assert_eq!(literal, PathBuf::from("docs/../src"));
+        assert_eq!(rest, PathBuf::from("**/*.rs"));
+    }
+
+    #[test]
+    fn test_hash_name_is_root_relative() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::create_dir(root.join("docs")).unwrap();
+        let resolver = PathResolver::new(&root.join("docs/d.md")).unwrap();
+        let file = root.join("docs/../src/a.rs");
+
+        assert_eq!(
+            resolver.hash_name("../src/*.rs", &file),
+            PathBuf::from("docs/../src/a.rs")
+        );
+        assert_eq!(
+            resolver.hash_name("../src", &file),
+            PathBuf::from("./docs/../src/a.rs")
+        );
+        assert_eq!(
+            resolver.hash_name("$ROOT/src/*.rs", &root.join("src/a.rs")),
+            root.join("src/a.rs")
+        );
+    }
 }
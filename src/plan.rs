@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version written into new plans; `apply` refuses other versions
+pub const PLAN_VERSION: u32 = 2;
+
+/// A reviewable list of hash updates produced by `drifty check --plan`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Plan {
+    pub version: u32,
+    pub updates: Vec<PlannedUpdate>,
+}
+
+/// One proposed hash update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedUpdate {
+    /// The doc, relative to the project root
+    pub doc: PathBuf,
+    pub pattern: String,
+    /// Hash currently stored in the doc's frontmatter
+    pub old_hash: String,
+    /// Hash of the files when the plan was made
+    pub new_hash: String,
+}
+
+impl Plan {
+    pub fn new(updates: Vec<PlannedUpdate>) -> Self {
+        Self {
+            version: PLAN_VERSION,
+            updates,
+        }
+    }
+}
+
+pub fn save(path: &Path, plan: &Plan) -> Result<()> {
+    let json = serde_json::to_string_pretty(plan)?;
+    fs::write(path, json + "\n")
+        .with_context(|| format!("Failed to write file: {}", path.display()))
+}
+
+pub fn load(path: &Path) -> Result<Plan> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    let plan: Plan = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid plan file: {}", path.display()))?;
+    if plan.version != PLAN_VERSION {
+        return Err(anyhow!(
+            "Unsupported plan version {} (expected {})",
+            plan.version,
+            PLAN_VERSION
+        ));
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("plan.json");
+        let plan = Plan::new(vec![PlannedUpdate {
+            doc: PathBuf::from("docs/a.md"),
+            pattern: "src/**/*.rs".to_string(),
+            old_hash: "abc".to_string(),
+            new_hash: "def".to_string(),
+        }]);
+
+        save(&path, &plan).unwrap();
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.updates.len(), 1);
+        assert_eq!(loaded.updates[0].new_hash, "def");
+    }
+
+    #[test]
+    fn test_rejects_unknown_version() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("plan.json");
+        fs::write(&path, r#"{"version": 99, "updates": []}"#).unwrap();
+        assert!(load(&path).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use std::path::Path;
+
+use crate::frontmatter;
+
+/// How a doc mentions a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// An inline code span such as `` `src/paths.rs` ``
+    Code,
+    /// The target of a relative link, inline or reference style
+    Link,
+    /// The source of an image, `![alt](path)`
+    Image,
+    /// The file named in a fenced block's info string
+    /// (`` ```rust title="src/main.rs" ``)
+    FenceHeader,
+}
+
+impl Kind {
+    /// How the reference is named in messages
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Code => "code",
+            Kind::Link => "link",
+            Kind::Image => "image",
+            Kind::FenceHeader => "code block",
+        }
+    }
+}
+
+/// A path mentioned in a doc's text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub path: String,
+    pub kind: Kind,
+    /// 1-based line in the text
+    pub line: usize,
+    /// 1-based column, in characters, where the path starts
+    pub column: usize,
+}
+
+/// Info string attributes naming the file a fenced block comes from
+const FENCE_FILE_KEYS: [&str; 3] = ["title", "file", "filename"];
+
+/// Find the paths mentioned in the body of a Markdown doc, after its
+/// frontmatter. Line numbers count from the top of the file, frontmatter
+/// included.
+pub fn extract_body(path: &Path, content: &str) -> Vec<Reference> {
+    let body_start = match frontmatter::parse_content(path, content) {
+        Ok(Some(fm)) => fm.body_start(content),
+        _ => 0,
+    };
+    let first_line = content[..body_start].lines().count();
+
+    let mut refs = extract(&content[body_start..]);
+    for reference in &mut refs {
+        reference.line += first_line;
+    }
+    refs
+}
+
+/// Find the paths a Markdown text mentions, in order. Code spans and links
+/// inside fenced blocks are not references; only the block's header is.
+pub fn extract(text: &str) -> Vec<Reference> {
+    let mut refs = Vec::new();
+    let mut fence: Option<String> = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let trimmed = line.trim_start();
+
+        if let Some(open) = &fence {
+            if trimmed.starts_with(open.as_str()) && trimmed[open.len()..].trim().is_empty() {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(marker) = fence_marker(trimmed) {
+            let info = &trimmed[marker.len()..];
+            refs.extend(fence_header(info).map(|path| Reference {
+                column: column(line, info.find(path.as_str()).map_or(info, |i| &info[i..])),
+                path,
+                kind: Kind::FenceHeader,
+                line: line_no,
+            }));
+            fence = Some(marker);
+            continue;
+        }
+
+        for path in code_spans(line).into_iter().filter(|s| looks_like_path(s)) {
+            refs.push(Reference {
+                path: path.to_string(),
+                kind: Kind::Code,
+                line: line_no,
+                column: column(line, path),
+            });
+        }
+        for (target, kind) in link_targets(line) {
+            if let Some(path) = local_path(target) {
+                refs.push(Reference {
+                    path,
+                    kind,
+                    line: line_no,
+                    column: column(line, target),
+                });
+            }
+        }
+    }
+    refs
+}
+
+/// 1-based character column of a slice of a line
+fn column(line: &str, part: &str) -> usize {
+    let offset = (part.as_ptr() as usize).saturating_sub(line.as_ptr() as usize);
+    line[..offset.min(line.len())].chars().count() + 1
+}
+
+/// The opening run of backticks or tildes of a fence line
+pub fn fence_marker(trimmed: &str) -> Option<String> {
+    let c = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.chars().take_while(|&x| x == c).count();
+    (len >= 3).then(|| c.to_string().repeat(len))
+}
+
+/// The file named by a fence's info string: a `title=`/`file=`/`filename=`
+/// attribute, or a path-like word after the language
+fn fence_header(info: &str) -> Option<String> {
+    let words: Vec<&str> = info.split_whitespace().collect();
+    for word in &words {
+        if let Some((key, value)) = word.split_once('=') {
+            if FENCE_FILE_KEYS.contains(&key) {
+                let value = value.trim_matches(|c| c == '"' || c == '\'');
+                return Some(value.to_string()).filter(|v| looks_like_path(v));
+            }
+        }
+    }
+    words
+        .iter()
+        .skip(1)
+        .find(|w| !w.contains('=') && looks_like_path(w))
+        .map(|w| w.to_string())
+}
+
+/// Contents of the single-backtick code spans on a line
+fn code_spans(line: &str) -> Vec<&str> {
+    let mut spans = Vec::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('`') {
+        let after = &rest[start + 1..];
+        if after.starts_with('`') {
+            // Longer runs delimit spans that may hold backticks; skip them
+            rest = after.trim_start_matches('`');
+            continue;
+        }
+        match after.find('`') {
+            Some(end) => {
+                spans.push(&after[..end]);
+                rest = &after[end + 1..];
+            }
+            None => break,
+        }
+    }
+    spans
+}
+
+/// Targets of `[text](target "title")` links, `![alt](target)` images and
+/// `[label]: target` definitions on a line
+fn link_targets(line: &str) -> Vec<(&str, Kind)> {
+    let mut targets = Vec::new();
+
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('[') {
+        if let Some(pos) = trimmed.find("]:") {
+            if let Some(target) = trimmed[pos + 2..].split_whitespace().next() {
+                targets.push((strip_angle_brackets(target), Kind::Link));
+            }
+        }
+    }
+
+    let mut rest = line;
+    while let Some(pos) = rest.find("](") {
+        let image = rest[..pos]
+            .rfind('[')
+            .is_some_and(|open| rest[..open].ends_with('!'));
+        let after = &rest[pos + 2..];
+        let end = after.find(')').unwrap_or(after.len());
+        if let Some(target) = after[..end].split_whitespace().next() {
+            let kind = if image { Kind::Image } else { Kind::Link };
+            targets.push((strip_angle_brackets(target), kind));
+        }
+        rest = &after[end..];
+    }
+    targets
+}
+
+fn strip_angle_brackets(target: &str) -> &str {
+    target.trim_start_matches('<').trim_end_matches('>')
+}
+
+/// The local file a link target points at, without its anchor or query;
+/// None for URLs and in-page anchors
+fn local_path(target: &str) -> Option<String> {
+    if target.starts_with('#') || target.contains("://") || target.starts_with("mailto:") {
+        return None;
+    }
+    let path = target.split(['#', '?']).next().unwrap_or(target);
+    Some(path.to_string()).filter(|p| !p.is_empty())
+}
+
+/// Whether a code span reads as a file path rather than code: no spaces or
+/// punctuation, and either a directory separator or a file extension
+fn looks_like_path(s: &str) -> bool {
+    let allowed = |c: char| c.is_alphanumeric() || "/._-$".contains(c);
+    if s.is_empty() || !s.chars().all(allowed) || s.contains("..") && !s.starts_with("..") {
+        return false;
+    }
+    let name = s.rsplit('/').next().unwrap_or(s);
+    s.contains('/') || name.trim_start_matches('.').contains('.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let text = "\
+See `src/paths.rs`, `Cargo.toml` and `PathResolver::new`, not `a b.rs`.
+Read the [resolver](../src/paths.rs#L10 \"Resolver\") or [the site](https://x.org/a.rs).
+
+```rust title=\"src/main.rs\"
+// `src/ignored.rs`
+```
+
+~~~toml config/app.toml
+~~~
+[guide]: ./guide.md
+";
+        let refs: Vec<(String, Kind, usize)> = extract(text)
+            .into_iter()
+            .map(|r| (r.path, r.kind, r.line))
+            .collect();
+        assert_eq!(
+            refs,
+            vec![
+                ("src/paths.rs".to_string(), Kind::Code, 1),
+                ("Cargo.toml".to_string(), Kind::Code, 1),
+                ("../src/paths.rs".to_string(), Kind::Link, 2),
+                ("src/main.rs".to_string(), Kind::FenceHeader, 4),
+                ("config/app.toml".to_string(), Kind::FenceHeader, 8),
+                ("./guide.md".to_string(), Kind::Link, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_columns_and_images() {
+        let refs = extract("Ünï `a/b.rs` and ![logo](img/logo.png) [x](c.md)");
+        let found: Vec<(&str, Kind, usize)> = refs
+            .iter()
+            .map(|r| (r.path.as_str(), r.kind, r.column))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a/b.rs", Kind::Code, 6),
+                ("img/logo.png", Kind::Image, 26),
+                ("c.md", Kind::Link, 44),
+            ]
+        );
+    }
+}
@@ -1,16 +1,25 @@
 use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-/// Find all markdown files in a target path
-pub fn find_markdown_files(target: Option<&Path>) -> Result<Vec<PathBuf>> {
+use crate::document::Format;
+use crate::frontmatter;
+use crate::manifest::Manifest;
+use crate::paths;
+
+/// Find all documents (Markdown, AsciiDoc, reStructuredText, notebooks, Rust
+/// source) in a target path, along with the docs the project manifest lists
+/// there in any format
+pub fn find_documents(target: Option<&Path>) -> Result<Vec<PathBuf>> {
     let start = target.unwrap_or(Path::new("."));
 
     if start.is_file() {
-        if is_markdown(start) {
+        let listed = Manifest::for_doc(start)?.is_some_and(|m| m.entries(start).is_some());
+        if is_document(start) || listed {
             return Ok(vec![start.to_path_buf()]);
         } else {
-            return Err(anyhow!("File is not a markdown file: {}", start.display()));
+            return Err(anyhow!("File is not a document: {}", start.display()));
         }
     }
 
@@ -20,10 +29,30 @@ pub fn find_markdown_files(target: Option<&Path>) -> Result<Vec<PathBuf>> {
 
     let mut files = Vec::new();
     scan_directory(start, &mut files)?;
+    add_manifest_docs(start, &mut files)?;
     files.sort();
     Ok(files)
 }
 
+/// Add the existing docs listed in the project manifest under `dir` that the
+/// scan did not find
+fn add_manifest_docs(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let Ok(root) = paths::find_project_root(dir) else {
+        return Ok(());
+    };
+    let dir_abs = paths::absolute(dir);
+    let found: BTreeSet<PathBuf> = files.iter().map(|f| paths::absolute(f)).collect();
+
+    for doc in Manifest::load(&root)?.docs() {
+        if let Ok(relative) = doc.strip_prefix(&dir_abs) {
+            if doc.is_file() && !found.contains(&doc) {
+                files.push(dir.join(relative));
+            }
+        }
+    }
+    Ok(())
+}
+
 fn scan_directory(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
     let entries = fs::read_dir(dir)
         .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
@@ -42,21 +71,33 @@ fn scan_directory(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
         }
 
         if path.is_dir() {
+            // Skip build output, such as Cargo's target directory
+            if path.join("CACHEDIR.TAG").exists() {
+                continue;
+            }
             scan_directory(&path, files)?;
-        } else if is_markdown(&path) {
+        } else if is_document(&path) {
             files.push(path);
         }
     }
     Ok(())
 }
 
-fn is_markdown(path: &Path) -> bool {
-    path.extension()
-        .map(|ext| {
-            let ext = ext.to_string_lossy().to_lowercase();
-            ext == "md" || ext == "markdown"
-        })
-        .unwrap_or(false)
+pub fn is_document(path: &Path) -> bool {
+    Format::from_path(path).is_some()
+}
+
+/// Whether a file is a doc with entries of its own. Rust source only counts
+/// when it has `drifty:` directives; otherwise it is just source.
+pub fn is_entry_doc(path: &Path) -> bool {
+    match Format::from_path(path) {
+        Some(Format::Rust) => frontmatter::read_file(path)
+            .ok()
+            .and_then(|content| frontmatter::parse_content(path, &content).ok().flatten())
+            .is_some(),
+        Some(_) => true,
+        None => false,
+    }
 }
 
 #[cfg(test)]
@@ -64,12 +105,25 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_is_markdown() {
-        assert!(is_markdown(Path::new("README.md")));
-        assert!(is_markdown(Path::new("doc.markdown")));
-        assert!(is_markdown(Path::new("path/to/file.MD")));
-        assert!(!is_markdown(Path::new("file.txt")));
-        assert!(!is_markdown(Path::new("file.rs")));
-        assert!(!is_markdown(Path::new("noext")));
+    fn test_is_document() {
+        assert!(is_document(Path::new("README.md")));
+        assert!(is_document(Path::new("doc.markdown")));
+        assert!(is_document(Path::new("path/to/file.MD")));
+        assert!(is_document(Path::new("guide.adoc")));
+        assert!(is_document(Path::new("index.rst")));
+        assert!(!is_document(Path::new("file.txt")));
+        assert!(is_document(Path::new("src/lib.rs")));
+        assert!(!is_document(Path::new("noext")));
+    }
+
+    #[test]
+    fn test_is_entry_doc() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let source = dir.path().join("lib.rs");
+        fs::write(&source, "//! Library\nfn f() {}\n").unwrap();
+        assert!(!is_entry_doc(&source));
+        fs::write(&source, "//! drifty: a.rs = aaa\nfn f() {}\n").unwrap();
+        assert!(is_entry_doc(&source));
+        assert!(is_entry_doc(&dir.path().join("README.md")));
     }
 }
@@ -0,0 +1,298 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::document::Format;
+use crate::frontmatter;
+use crate::paths::PathResolver;
+use crate::references;
+use crate::status::Status;
+
+/// A fenced code block copied from a source file, declared in its info
+/// string: `` ```rust file=src/hash.rs lines=7-14 ``
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// Source path as written, resolved like a path mentioned in the doc
+    pub file: String,
+    /// 1-based inclusive line range of the source; the whole file when None
+    pub lines: Option<(usize, usize)>,
+    /// A `lines=` value that is not a line or range, making the snippet INVALID
+    invalid_lines: Option<String>,
+    /// 1-based line of the opening fence
+    pub line: usize,
+    /// Byte range of the block's content lines, between the fences
+    pub content: Range<usize>,
+    /// Indentation of the opening fence, also on each content line
+    indent: String,
+}
+
+impl Snippet {
+    /// How the snippet is shown in reports: `src/hash.rs:7-14`
+    pub fn label(&self) -> String {
+        if let Some(lines) = &self.invalid_lines {
+            return format!("{}:{}", self.file, lines);
+        }
+        match self.lines {
+            Some((start, end)) if start == end => format!("{}:{}", self.file, start),
+            Some((start, end)) => format!("{}:{}-{}", self.file, start, end),
+            None => self.file.clone(),
+        }
+    }
+
+    /// The block's code as it would be in the source: without the fence's
+    /// indentation, with `\n` line endings
+    pub fn code(&self, text: &str) -> String {
+        let mut code = String::new();
+        for line in text[self.content.clone()].lines() {
+            code.push_str(line.strip_prefix(self.indent.as_str()).unwrap_or(line));
+            code.push('\n');
+        }
+        code
+    }
+
+    /// The source file, if it exists
+    pub fn source_path(&self, resolver: &PathResolver) -> Option<PathBuf> {
+        let pattern = resolver.mention_pattern(&self.file)?;
+        let path = resolver.resolve(&pattern).ok()?.into_iter().next()?;
+        path.is_file().then_some(path)
+    }
+
+    /// The part of the source file the snippet copies, with `\n` line endings
+    pub fn source(&self, resolver: &PathResolver) -> Result<String> {
+        if let Some(lines) = &self.invalid_lines {
+            return Err(anyhow!(
+                "Invalid lines={}: expected a line or a range like 7-14",
+                lines
+            ));
+        }
+        let path = self
+            .source_path(resolver)
+            .ok_or_else(|| anyhow!("Source file not found: {}", self.file))?;
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+
+        let lines: Vec<&str> = text.lines().collect();
+        let (start, end) = self.lines.unwrap_or((1, lines.len()));
+        if start == 0 || end < start || end > lines.len() {
+            return Err(anyhow!(
+                "{} has {} lines, outside lines {}-{}",
+                self.file,
+                lines.len(),
+                start,
+                end
+            ));
+        }
+        Ok(lines[start - 1..end]
+            .iter()
+            .map(|line| format!("{}\n", line.trim_end_matches('\r')))
+            .collect())
+    }
+
+    /// Compare the block with its source: CURRENT, SNIPPET_DRIFTED, MISSING
+    /// when the source file or lines are gone, or INVALID when `lines=` is
+    /// malformed
+    pub fn check(&self, resolver: &PathResolver, text: &str) -> Status {
+        if self.invalid_lines.is_some() {
+            return Status::Invalid;
+        }
+        match self.source(resolver) {
+            Ok(source) if source == self.code(text) => Status::Current,
+            Ok(_) => Status::SnippetDrifted,
+            Err(_) => Status::Missing,
+        }
+    }
+
+    /// The block content for the given source code, indented like the fence
+    /// and using the text's line endings
+    fn render(&self, code: &str, eol: &str) -> String {
+        code.lines()
+            .map(|line| {
+                if line.is_empty() {
+                    eol.to_string()
+                } else {
+                    format!("{}{}{}", self.indent, line, eol)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Find the fenced blocks of a Markdown text that declare a source `file=`
+pub fn find(text: &str) -> Vec<Snippet> {
+    let mut snippets = Vec::new();
+    let mut open: Option<(String, Option<Snippet>)> = None;
+    let mut pos = 0;
+
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let start = pos;
+        pos += line.len();
+        let body = line.trim_end_matches(['\r', '\n']);
+        let trimmed = body.trim_start();
+        let indent = &body[..body.len() - trimmed.len()];
+
+        match &mut open {
+            Some((marker, snippet)) => {
+                let closes = trimmed.starts_with(marker.as_str())
+                    && trimmed
+                        .trim_start_matches(marker.chars().next().unwrap_or('`'))
+                        .trim()
+                        .is_empty();
+                if closes {
+                    if let Some(mut snippet) = snippet.take() {
+                        snippet.content.end = start;
+                        snippets.push(snippet);
+                    }
+                    open = None;
+                }
+            }
+            None => {
+                let Some(marker) = references::fence_marker(trimmed) else {
+                    continue;
+                };
+                let snippet = parse_info(&trimmed[marker.len()..]).map(|(file, lines)| Snippet {
+                    file,
+                    lines: lines.as_ref().ok().copied().flatten(),
+                    invalid_lines: lines.err(),
+                    line: i + 1,
+                    content: pos..pos,
+                    indent: indent.to_string(),
+                });
+                open = Some((marker, snippet));
+            }
+        }
+    }
+    snippets
+}
+
+/// The snippets of a doc with their status. Only Markdown docs are searched,
+/// and a doc that can't be read has none.
+pub fn check_doc(path: &Path) -> Vec<(Snippet, Status)> {
+    if Format::from_path(path) != Some(Format::Markdown) {
+        return Vec::new();
+    }
+    let (Ok(text), Ok(resolver)) = (frontmatter::read_file(path), PathResolver::new(path)) else {
+        return Vec::new();
+    };
+    find(&text)
+        .into_iter()
+        .map(|snippet| {
+            let status = snippet.check(&resolver, &text);
+            (snippet, status)
+        })
+        .collect()
+}
+
+/// Replace the content of every snippet with its source. Returns the new
+/// text and how many blocks changed; snippets whose source can't be read are
+/// left alone.
+pub fn sync(text: &str, resolver: &PathResolver) -> (String, usize) {
+    let eol = frontmatter::line_ending(text);
+    let mut out = String::new();
+    let mut last = 0;
+    let mut changed = 0;
+
+    for snippet in find(text) {
+        let Ok(source) = snippet.source(resolver) else {
+            continue;
+        };
+        if source == snippet.code(text) {
+            continue;
+        }
+        out.push_str(&text[last..snippet.content.start]);
+        out.push_str(&snippet.render(&source, eol));
+        last = snippet.content.end;
+        changed += 1;
+    }
+    out.push_str(&text[last..]);
+    (out, changed)
+}
+
+/// Line range of a snippet as declared, or the `lines=` value when it is not
+/// a line or range
+type Lines = std::result::Result<Option<(usize, usize)>, String>;
+
+/// The `file=` and `lines=` attributes of a fence's info string
+fn parse_info(info: &str) -> Option<(String, Lines)> {
+    let mut file = None;
+    let mut lines = Ok(None);
+    for word in info.split_whitespace() {
+        let Some((key, value)) = word.split_once('=') else {
+            continue;
+        };
+        let value = value.trim_matches(|c| c == '"' || c == '\'');
+        match key {
+            "file" => file = Some(value.to_string()),
+            "lines" => {
+                let (start, end) = value.split_once('-').unwrap_or((value, value));
+                lines = match (start.parse(), end.parse()) {
+                    (Ok(start), Ok(end)) => Ok(Some((start, end))),
+                    _ => Err(value.to_string()),
+                };
+            }
+            _ => {}
+        }
+    }
+    Some((file.filter(|f| !f.is_empty())?, lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find() {
+        let text = "# Doc\n\n```rust file=src/hash.rs lines=7-14\nfn a() {}\n```\n\n```rust\nfn b() {}\n```\n\n  ~~~ file=\"a.toml\" lines=3\n  x = 1\n  ~~~\n";
+        let snippets = find(text);
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].label(), "src/hash.rs:7-14");
+        assert_eq!(snippets[0].line, 3);
+        assert_eq!(&text[snippets[0].content.clone()], "fn a() {}\n");
+        assert_eq!(snippets[1].label(), "a.toml:3");
+        assert_eq!(snippets[1].code(text), "x = 1\n");
+    }
+
+    #[test]
+    fn test_malformed_lines_are_invalid() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join("a.rs"), "fn a() {}\n").unwrap();
+        let resolver = PathResolver::new(&dir.path().join("doc.md")).unwrap();
+
+        let text = "```rust file=a.rs lines=1..2\nfn a() {}\n```\n";
+        let snippets = find(text);
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].label(), "a.rs:1..2");
+        assert_eq!(snippets[0].check(&resolver, text), Status::Invalid);
+        assert!(snippets[0].source(&resolver).is_err());
+        assert_eq!(sync(text, &resolver), (text.to_string(), 0));
+    }
+
+    #[test]
+    fn test_check_and_sync() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "use a;\n\nfn a() {\n    1\n}\n").unwrap();
+        let resolver = PathResolver::new(&root.join("docs/doc.md")).unwrap();
+
+        let text = "Intro\r\n\r\n- item\r\n\r\n  ```rust file=src/lib.rs lines=3-5\r\n  fn a() {\r\n      1\r\n  }\r\n  ```\r\n";
+        let snippet = &find(text)[0];
+        assert_eq!(snippet.check(&resolver, text), Status::Current);
+
+        fs::write(root.join("src/lib.rs"), "use a;\n\nfn a() {\n\n    2\n}\n").unwrap();
+        assert_eq!(snippet.check(&resolver, text), Status::SnippetDrifted);
+
+        let (synced, changed) = sync(text, &resolver);
+        assert_eq!(changed, 1);
+        assert_eq!(
+            synced,
+            "Intro\r\n\r\n- item\r\n\r\n  ```rust file=src/lib.rs lines=3-5\r\n  fn a() {\r\n\r\n      2\r\n  ```\r\n"
+        );
+
+        fs::write(root.join("src/lib.rs"), "fn a() {}\n").unwrap();
+        assert_eq!(snippet.check(&resolver, text), Status::Missing);
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How long a snoozed entry stays out of the interactive review
+pub const SNOOZE_DAYS: u64 = 7;
+
+/// A drifted entry the user chose to look at later. The snooze ends when it
+/// expires or when the watched files change again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snooze {
+    /// Unix timestamp (seconds) when the snooze expires
+    pub until: u64,
+    /// Hash of the files at the time of snoozing
+    pub hash: String,
+    /// Doc path relative to the project root
+    pub doc: PathBuf,
+    pub pattern: String,
+}
+
+/// Snoozes are personal, so they live inside `.git` where they are never
+/// committed (falling back to the project root when `.git` is a file)
+pub fn snooze_file(project_root: &Path) -> PathBuf {
+    let git_dir = project_root.join(".git");
+    if git_dir.is_dir() {
+        git_dir.join("drifty-snooze")
+    } else {
+        project_root.join(".drifty-snooze")
+    }
+}
+
+/// Load the snoozes of a project, dropping expired ones
+pub fn load(project_root: &Path) -> Vec<Snooze> {
+    let now = now();
+    fs::read_to_string(snooze_file(project_root))
+        .map(|content| parse(&content))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|s| s.until > now)
+        .collect()
+}
+
+pub fn save(project_root: &Path, snoozes: &[Snooze]) -> Result<()> {
+    let path = snooze_file(project_root);
+    fs::write(&path, serialize(snoozes))
+        .with_context(|| format!("Failed to write file: {}", path.display()))
+}
+
+/// Whether an entry is snoozed at its current hash
+pub fn is_snoozed(snoozes: &[Snooze], doc: &Path, pattern: &str, hash: &str) -> bool {
+    snoozes
+        .iter()
+        .any(|s| s.doc == doc && s.pattern == pattern && s.hash == hash)
+}
+
+/// A snooze for an entry starting now
+pub fn new_snooze(doc: &Path, pattern: &str, hash: &str) -> Snooze {
+    Snooze {
+        until: now() + SNOOZE_DAYS * 24 * 60 * 60,
+        hash: hash.to_string(),
+        doc: doc.to_path_buf(),
+        pattern: pattern.to_string(),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// One snooze per line: `until<TAB>hash<TAB>doc<TAB>pattern`
+fn parse(content: &str) -> Vec<Snooze> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+            Some(Snooze {
+                until: fields.next()?.parse().ok()?,
+                hash: fields.next()?.to_string(),
+                doc: PathBuf::from(fields.next()?),
+                pattern: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn serialize(snoozes: &[Snooze]) -> String {
+    snoozes
+        .iter()
+        .map(|s| {
+            format!(
+                "{}\t{}\t{}\t{}\n",
+                s.until,
+                s.hash,
+                s.doc.display(),
+                s.pattern
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let snoozes = vec![new_snooze(Path::new("docs/a.md"), "src/**/*.rs", "abc")];
+        assert_eq!(parse(&serialize(&snoozes)), snoozes);
+        assert!(is_snoozed(
+            &snoozes,
+            Path::new("docs/a.md"),
+            "src/**/*.rs",
+            "abc"
+        ));
+        assert!(!is_snoozed(
+            &snoozes,
+            Path::new("docs/a.md"),
+            "src/**/*.rs",
+            "def"
+        ));
+    }
+
+    #[test]
+    fn test_parse_skips_malformed_lines() {
+        assert!(parse("not a snooze\n").is_empty());
+    }
+}
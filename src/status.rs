@@ -1,28 +1,492 @@
+use anyhow::Result;
 use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::frontmatter::{self, WatchEntry};
+use crate::hash;
+use crate::manifest::Manifest;
+use crate::paths::{self, PathResolver};
+use crate::scanner;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
-#[serde(rename_all = "UPPERCASE")]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Status {
     Current,
     Drifted,
+    /// The watched files are unchanged, but among them is a doc that drifted
+    /// (directly or through the docs it watches in turn)
+    TransitivelyDrifted,
+    /// A code block copied from a source file no longer matches the source
+    SnippetDrifted,
     Missing,
     Invalid,
 }
 
 impl Status {
     pub fn is_problem(&self) -> bool {
-        matches!(self, Status::Drifted | Status::Missing)
+        matches!(
+            self,
+            Status::Drifted
+                | Status::TransitivelyDrifted
+                | Status::SnippetDrifted
+                | Status::Missing
+        )
     }
 }
 
 impl fmt::Display for Status {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Status::Current => write!(f, "CURRENT"),
-            Status::Drifted => write!(f, "DRIFTED"),
-            Status::Missing => write!(f, "MISSING"),
-            Status::Invalid => write!(f, "INVALID"),
+            Status::Current => f.pad("CURRENT"),
+            Status::Drifted => f.pad("DRIFTED"),
+            Status::TransitivelyDrifted => f.pad("TRANSITIVELY_DRIFTED"),
+            Status::SnippetDrifted => f.pad("SNIPPET_DRIFTED"),
+            Status::Missing => f.pad("MISSING"),
+            Status::Invalid => f.pad("INVALID"),
+        }
+    }
+}
+
+/// Separates the hash of an entry's files from the state of the docs among
+/// them that the entry was acknowledged with
+const DOCS_STATE_SEPARATOR: char = '+';
+
+/// Split a stored hash into the hash of the files and, for entries that watch
+/// docs, the state of those docs when the entry was acknowledged
+pub fn split_hash(hash: &str) -> (&str, Option<&str>) {
+    match hash.split_once(DOCS_STATE_SEPARATOR) {
+        Some((files, docs)) => (files, Some(docs)),
+        None => (hash, None),
+    }
+}
+
+/// A new hash of an entry's files, keeping the state of its docs recorded in
+/// the stored hash
+pub fn keep_docs_state(files_hash: String, stored: &str) -> String {
+    match split_hash(stored).1 {
+        Some(docs) => format!("{}{}{}", files_hash, DOCS_STATE_SEPARATOR, docs),
+        None => files_hash,
+    }
+}
+
+/// Compare an entry's stored hash with the current state of its files.
+/// Returns the status and, when the files could be hashed, the current hash.
+/// The state of watched docs is left to [`Propagation`].
+pub fn check_entry(resolver: &PathResolver, entry: &WatchEntry) -> (Status, Option<String>) {
+    // Check if entry has a hash
+    let stored_hash = match &entry.hash {
+        Some(h) => h,
+        None => return (Status::Invalid, None),
+    };
+
+    // Check if files exist
+    let paths = match resolver.resolve(&entry.pattern) {
+        Ok(p) => p,
+        Err(_) => return (Status::Missing, None),
+    };
+
+    if paths.is_empty() {
+        return (Status::Missing, None);
+    }
+
+    // Compute current hash
+    let current_hash = match resolver.hash_pattern(&entry.pattern) {
+        Ok(h) => h,
+        Err(_) => return (Status::Missing, None),
+    };
+
+    if current_hash == split_hash(stored_hash).0 {
+        (Status::Current, Some(current_hash))
+    } else {
+        (Status::Drifted, Some(current_hash))
+    }
+}
+
+/// Works out which current entries watch a drifted doc, directly or through
+/// further docs. Results are cached per doc, so one instance should be shared
+/// by all the entries a command checks.
+///
+/// An entry watching docs is acknowledged with the state of those docs (the
+/// stored and current hashes of their entries) after its own hash. It is
+/// TRANSITIVELY_DRIFTED when that state changes, i.e. when a watched doc
+/// drifts or is re-acknowledged. Entries stored with only the hash of their
+/// files are TRANSITIVELY_DRIFTED while a watched doc has drift.
+#[derive(Default)]
+pub struct Propagation {
+    /// Whether each fully evaluated doc has drift
+    docs: HashMap<PathBuf, bool>,
+    /// Docs being evaluated, innermost last
+    stack: Vec<PathBuf>,
+    /// State of each fully evaluated doc's entries
+    states: HashMap<PathBuf, String>,
+    /// Docs whose state is being evaluated, innermost last
+    state_stack: Vec<PathBuf>,
+    cycles: Vec<Vec<PathBuf>>,
+    /// Docs listed in each project's manifest
+    manifest_docs: HashMap<PathBuf, BTreeSet<PathBuf>>,
+    /// Whether each file met so far is a doc with entries of its own
+    entry_docs: HashMap<PathBuf, bool>,
+}
+
+impl Propagation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check an entry like [`check_entry`], reporting a current entry whose
+    /// watched docs changed since it was acknowledged as TRANSITIVELY_DRIFTED.
+    /// The current hash includes the state of the watched docs.
+    pub fn check_entry(
+        &mut self,
+        resolver: &PathResolver,
+        entry: &WatchEntry,
+    ) -> (Status, Option<String>) {
+        let (status, hash, _) = self.entry_state(resolver, entry);
+        (status, hash)
+    }
+
+    /// The hash to acknowledge a pattern with: the hash of its files, and the
+    /// state of the docs among them
+    pub fn hash_pattern(&mut self, resolver: &PathResolver, pattern: &str) -> Result<String> {
+        let files_hash = resolver.hash_pattern(pattern)?;
+        let docs = self.watched_docs(resolver, pattern);
+        if docs.is_empty() {
+            return Ok(files_hash);
+        }
+        let (state, _) = self.docs_state(&docs);
+        Ok(format!("{}{}{}", files_hash, DOCS_STATE_SEPARATOR, state))
+    }
+
+    /// Status and current hash of an entry, and whether the state of its
+    /// watched docs is final (see [`Propagation::doc_state`])
+    fn entry_state(
+        &mut self,
+        resolver: &PathResolver,
+        entry: &WatchEntry,
+    ) -> (Status, Option<String>, bool) {
+        let (status, files_hash) = check_entry(resolver, entry);
+        let Some(files_hash) = files_hash else {
+            return (status, None, true);
+        };
+        let docs = self.watched_docs(resolver, &entry.pattern);
+        if docs.is_empty() {
+            return (status, Some(files_hash), true);
+        }
+
+        let (state, complete) = self.docs_state(&docs);
+        let acknowledged = match entry.hash.as_deref().map(split_hash) {
+            Some((_, Some(recorded))) => recorded == state,
+            _ => !docs.iter().any(|doc| self.doc_drifted(doc).0),
+        };
+        let status = match status {
+            Status::Current if !acknowledged => Status::TransitivelyDrifted,
+            status => status,
+        };
+        let hash = format!("{}{}{}", files_hash, DOCS_STATE_SEPARATOR, state);
+        (status, Some(hash), complete)
+    }
+
+    /// Fingerprint of the state of some docs, and whether it is final
+    fn docs_state(&mut self, docs: &[PathBuf]) -> (String, bool) {
+        let mut state = String::new();
+        let mut complete = true;
+        for doc in docs {
+            let (doc_state, doc_complete) = self.doc_state(doc);
+            state.push_str(&doc_state);
+            state.push('\n');
+            complete &= doc_complete;
+        }
+        (hash::hash_bytes(state.as_bytes()), complete)
+    }
+
+    /// The stored and current hashes of a doc's entries, and whether that is
+    /// final. It is not when the doc watches a doc of a cycle still being
+    /// evaluated, which is left out for now. Only the files part of stored
+    /// hashes counts: a doc acknowledging the docs it watches is no news to
+    /// the docs watching it, and docs in a cycle would flag each other forever.
+    fn doc_state(&mut self, doc: &Path) -> (String, bool) {
+        let doc = paths::absolute(doc);
+        if let Some(state) = self.states.get(&doc) {
+            return (state.clone(), true);
+        }
+        if self.state_stack.contains(&doc) {
+            return (String::new(), false);
+        }
+
+        let (fm, resolver) = match (frontmatter::parse_file(&doc), PathResolver::new(&doc)) {
+            (Ok(Some(fm)), Ok(resolver)) => (fm, resolver),
+            _ => {
+                self.states.insert(doc, String::new());
+                return (String::new(), true);
+            }
+        };
+
+        self.state_stack.push(doc.clone());
+        let mut state = String::new();
+        let mut complete = true;
+        for entry in &fm.entries {
+            let (_, current, entry_complete) = self.entry_state(&resolver, entry);
+            complete &= entry_complete;
+            state.push_str(&format!(
+                "{}\n{}\n{}\n",
+                entry.pattern,
+                entry
+                    .hash
+                    .as_deref()
+                    .map(|h| split_hash(h).0)
+                    .unwrap_or_default(),
+                current.unwrap_or_default()
+            ));
+        }
+        self.state_stack.pop();
+
+        if complete {
+            self.states.insert(doc, state.clone());
+        }
+        (state, complete)
+    }
+
+    /// The docs among a pattern's files that drifted, directly or transitively
+    pub fn drifted_docs(&mut self, resolver: &PathResolver, pattern: &str) -> Vec<PathBuf> {
+        self.watched_docs(resolver, pattern)
+            .into_iter()
+            .filter(|doc| self.doc_drifted(doc).0)
+            .collect()
+    }
+
+    /// Cycles of docs watching each other met so far, each starting and
+    /// ending with the same doc
+    pub fn cycles(&self) -> &[Vec<PathBuf>] {
+        &self.cycles
+    }
+
+    /// Whether a doc has drift, and whether that answer is final. It is not
+    /// when the doc is part of a cycle still being evaluated, which counts as
+    /// no drift for now.
+    fn doc_drifted(&mut self, doc: &Path) -> (bool, bool) {
+        let doc = paths::absolute(doc);
+        if let Some(&drifted) = self.docs.get(&doc) {
+            return (drifted, true);
+        }
+        if let Some(pos) = self.stack.iter().position(|d| *d == doc) {
+            let mut cycle = self.stack[pos..].to_vec();
+            cycle.push(doc);
+            if !self.cycles.iter().any(|c| same_cycle(c, &cycle)) {
+                self.cycles.push(cycle);
+            }
+            return (false, false);
+        }
+
+        let (fm, resolver) = match (frontmatter::parse_file(&doc), PathResolver::new(&doc)) {
+            (Ok(Some(fm)), Ok(resolver)) => (fm, resolver),
+            _ => {
+                self.docs.insert(doc, false);
+                return (false, true);
+            }
+        };
+
+        self.stack.push(doc.clone());
+        let mut drifted = false;
+        let mut complete = true;
+        for entry in &fm.entries {
+            match check_entry(&resolver, entry).0 {
+                Status::Drifted => drifted = true,
+                Status::Current => {
+                    for target in self.watched_docs(&resolver, &entry.pattern) {
+                        let (target_drifted, target_complete) = self.doc_drifted(&target);
+                        drifted |= target_drifted;
+                        complete &= target_complete;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.stack.pop();
+
+        // Drift is final even inside a cycle
+        let complete = drifted || complete;
+        if complete {
+            self.docs.insert(doc, drifted);
+        }
+        (drifted, complete)
+    }
+
+    /// The files a pattern watches that are docs: files in a document format
+    /// (Rust source only with directives) or listed in the manifest. A
+    /// translation follows its source's text, not what the source documents,
+    /// so body patterns watch no docs.
+    pub fn watched_docs(&mut self, resolver: &PathResolver, pattern: &str) -> Vec<PathBuf> {
+        if paths::split_part(pattern).1 == paths::Part::Body {
+            return Vec::new();
+        }
+        let root = resolver.project_root().to_path_buf();
+        let listed = self.manifest_docs.entry(root.clone()).or_insert_with(|| {
+            Manifest::load(&root)
+                .map(|manifest| manifest.docs().into_iter().collect())
+                .unwrap_or_default()
+        });
+
+        let entry_docs = &mut self.entry_docs;
+        resolver
+            .resolve_files(pattern)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|file| {
+                let file = paths::absolute(file);
+                listed.contains(&file)
+                    || *entry_docs
+                        .entry(file)
+                        .or_insert_with_key(|file| scanner::is_entry_doc(file))
+            })
+            .collect()
+    }
+}
+
+/// Whether two cycles go through the same docs
+fn same_cycle(a: &[PathBuf], b: &[PathBuf]) -> bool {
+    let members = |cycle: &[PathBuf]| -> BTreeSet<PathBuf> { cycle.iter().cloned().collect() };
+    a.len() == b.len() && members(a) == members(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write_doc(path: &Path, watches: &[(&str, &str)]) {
+        let mut content = String::from("---\ndriftwatcher:\n");
+        for (pattern, hash) in watches {
+            content.push_str(&format!("  - {}: {}\n", pattern, hash));
+        }
+        content.push_str("---\n# Doc\n");
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_display_pads() {
+        assert_eq!(format!("{:8}|", Status::Missing), "MISSING |");
+        assert_eq!(format!("{}", Status::Drifted), "DRIFTED");
+    }
+
+    fn hash_of(doc: &Path, pattern: &str) -> String {
+        PathResolver::new(doc)
+            .unwrap()
+            .hash_pattern(pattern)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_transitive_drift() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join("lib.rs"), "fn a() {}\n").unwrap();
+        let reference = root.join("reference.md");
+        let tutorial = root.join("tutorial.md");
+
+        write_doc(&reference, &[("lib.rs", "0")]);
+        let lib_hash = hash_of(&reference, "lib.rs");
+        write_doc(&reference, &[("lib.rs", &lib_hash)]);
+        write_doc(&tutorial, &[("reference.md", "0")]);
+        let reference_hash = hash_of(&tutorial, "reference.md");
+        write_doc(&tutorial, &[("reference.md", &reference_hash)]);
+
+        let resolver = PathResolver::new(&tutorial).unwrap();
+        let entry = WatchEntry {
+            pattern: "reference.md".to_string(),
+            hash: Some(reference_hash.clone()),
+        };
+        assert_eq!(
+            Propagation::new().check_entry(&resolver, &entry).0,
+            Status::Current
+        );
+
+        // The reference's own entries are not part of its hash
+        write_doc(&reference, &[("lib.rs", "1")]);
+        assert_eq!(hash_of(&tutorial, "reference.md"), reference_hash);
+        assert_eq!(
+            Propagation::new().check_entry(&resolver, &entry).0,
+            Status::TransitivelyDrifted
+        );
+    }
+
+    #[test]
+    fn test_acknowledge_transitive_drift() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join("lib.rs"), "fn a() {}\n").unwrap();
+        let reference = root.join("reference.md");
+        let tutorial = root.join("tutorial.md");
+        write_doc(&reference, &[("lib.rs", &hash_of(&reference, "lib.rs"))]);
+
+        let resolver = PathResolver::new(&tutorial).unwrap();
+        let acknowledge = || WatchEntry {
+            pattern: "reference.md".to_string(),
+            hash: Some(
+                Propagation::new()
+                    .hash_pattern(&resolver, "reference.md")
+                    .unwrap(),
+            ),
+        };
+        let status = |entry: &WatchEntry| Propagation::new().check_entry(&resolver, entry).0;
+        let entry = acknowledge();
+        assert!(split_hash(entry.hash.as_deref().unwrap()).1.is_some());
+        assert_eq!(status(&entry), Status::Current);
+
+        // The reference drifts, and the tutorial is reviewed
+        fs::write(root.join("lib.rs"), "fn b() {}\n").unwrap();
+        assert_eq!(status(&entry), Status::TransitivelyDrifted);
+        let entry = acknowledge();
+        assert_eq!(status(&entry), Status::Current);
+
+        // The reference is re-acknowledged, and the tutorial is flagged again
+        write_doc(&reference, &[("lib.rs", &hash_of(&reference, "lib.rs"))]);
+        assert_eq!(status(&entry), Status::TransitivelyDrifted);
+        assert_eq!(status(&acknowledge()), Status::Current);
+    }
+
+    #[test]
+    fn test_cycle_terminates() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".git")).unwrap();
+        let a = root.join("a.md");
+        let b = root.join("b.md");
+        write_doc(&a, &[("b.md", "0")]);
+        write_doc(&b, &[("a.md", "0")]);
+        let a_hash = hash_of(&b, "a.md");
+        let b_hash = hash_of(&a, "b.md");
+        write_doc(&a, &[("b.md", &b_hash)]);
+        write_doc(&b, &[("a.md", &a_hash)]);
+
+        let resolver = PathResolver::new(&a).unwrap();
+        let mut propagation = Propagation::new();
+        assert!(propagation.drifted_docs(&resolver, "b.md").is_empty());
+        assert_eq!(propagation.cycles().len(), 1);
+        assert_eq!(propagation.cycles()[0].len(), 3);
+
+        // Acknowledging one side doesn't keep flagging the other
+        let b_resolver = PathResolver::new(&b).unwrap();
+        let a_ack = Propagation::new().hash_pattern(&resolver, "b.md").unwrap();
+        write_doc(&a, &[("b.md", &a_ack)]);
+        let b_ack = Propagation::new()
+            .hash_pattern(&b_resolver, "a.md")
+            .unwrap();
+        write_doc(&b, &[("a.md", &b_ack)]);
+        for (resolver, pattern, hash) in [(&resolver, "b.md", a_ack), (&b_resolver, "a.md", b_ack)]
+        {
+            let entry = WatchEntry {
+                pattern: pattern.to_string(),
+                hash: Some(hash),
+            };
+            assert_eq!(
+                Propagation::new().check_entry(resolver, &entry).0,
+                Status::Current
+            );
         }
     }
 }
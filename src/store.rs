@@ -0,0 +1,149 @@
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+use crate::document::Format;
+use crate::frontmatter::{self, Frontmatter, WatchEntry};
+use crate::manifest::Manifest;
+
+/// A doc's watch entries, loaded for editing from wherever they are kept: the
+/// project manifest when it lists the doc, or else the doc itself. Callers
+/// should hold the project lock from before loading until after saving.
+pub struct EntryStore {
+    doc: PathBuf,
+    kind: Kind,
+}
+
+enum Kind {
+    /// The doc's content, edited in place
+    Document(String),
+    Manifest(Manifest),
+}
+
+impl EntryStore {
+    pub fn load(doc: &Path) -> Result<Self> {
+        if let Some(manifest) = Manifest::for_doc(doc)? {
+            if manifest.entries(doc).is_some() {
+                return Ok(Self::manifest(doc, manifest));
+            }
+        }
+        Ok(Self {
+            doc: doc.to_path_buf(),
+            kind: Kind::Document(frontmatter::read_file(doc)?),
+        })
+    }
+
+    /// Keep the doc's entries in the manifest, listing it there if it isn't yet
+    pub fn load_manifest(doc: &Path) -> Result<Self> {
+        let mut manifest = Manifest::for_doc(doc)?.ok_or_else(|| {
+            anyhow!(
+                "{} is not in a project (no .git directory found)",
+                doc.display()
+            )
+        })?;
+        if manifest.entries(doc).is_none() {
+            manifest.set_entries(doc, Vec::new())?;
+        }
+        Ok(Self::manifest(doc, manifest))
+    }
+
+    fn manifest(doc: &Path, manifest: Manifest) -> Self {
+        Self {
+            doc: doc.to_path_buf(),
+            kind: Kind::Manifest(manifest),
+        }
+    }
+
+    /// The entries as they are now
+    pub fn frontmatter(&self) -> Result<Option<Frontmatter>> {
+        match &self.kind {
+            Kind::Document(content) => frontmatter::parse_content(&self.doc, content),
+            Kind::Manifest(manifest) => Ok(manifest
+                .entries(&self.doc)
+                .map(|entries| Frontmatter::from_manifest(entries.clone()))),
+        }
+    }
+
+    /// Add an entry (as the first entry)
+    pub fn add(&mut self, pattern: &str, hash: &str) -> Result<()> {
+        let rust = Format::from_path(&self.doc) == Some(Format::Rust);
+        match &mut self.kind {
+            Kind::Document(content) if rust => {
+                *content = frontmatter::add_directive(content, pattern, hash);
+            }
+            Kind::Document(content) => {
+                *content = frontmatter::add_entry(&self.doc, content, pattern, hash)?
+            }
+            Kind::Manifest(_) => {
+                let entries = self.manifest_entries()?;
+                entries.retain(|e| e.pattern != pattern);
+                entries.insert(0, new_entry(pattern, hash));
+            }
+        }
+        Ok(())
+    }
+
+    /// Update the hash of an entry
+    pub fn update(&mut self, pattern: &str, hash: &str) -> Result<()> {
+        self.replace(pattern, pattern, hash)
+    }
+
+    /// Replace an entry with a new pattern and hash
+    pub fn replace(&mut self, pattern: &str, new_pattern: &str, hash: &str) -> Result<()> {
+        if let Kind::Document(content) = &mut self.kind {
+            *content = frontmatter::replace_entry(&self.doc, content, pattern, new_pattern, hash)?;
+            return Ok(());
+        }
+
+        let entries = self.manifest_entries()?;
+        if !entries.iter().any(|e| e.pattern == pattern) {
+            return Err(anyhow!("Entry not found: {}", pattern));
+        }
+        if new_pattern != pattern {
+            entries.retain(|e| e.pattern != new_pattern);
+        }
+        if let Some(entry) = entries.iter_mut().find(|e| e.pattern == pattern) {
+            *entry = new_entry(new_pattern, hash);
+        }
+        Ok(())
+    }
+
+    /// Remove an entry
+    pub fn remove(&mut self, pattern: &str) -> Result<()> {
+        if let Kind::Document(content) = &mut self.kind {
+            *content = frontmatter::remove_entry(&self.doc, content, pattern)?;
+            return Ok(());
+        }
+
+        let entries = self.manifest_entries()?;
+        let len = entries.len();
+        entries.retain(|e| e.pattern != pattern);
+        if entries.len() == len {
+            return Err(anyhow!("Entry not found: {}", pattern));
+        }
+        Ok(())
+    }
+
+    /// Write the doc or the manifest back
+    pub fn save(&self) -> Result<()> {
+        match &self.kind {
+            Kind::Document(content) => frontmatter::write_file(&self.doc, content),
+            Kind::Manifest(manifest) => manifest.save(),
+        }
+    }
+
+    fn manifest_entries(&mut self) -> Result<&mut Vec<WatchEntry>> {
+        match &mut self.kind {
+            Kind::Manifest(manifest) => manifest
+                .entries_mut(&self.doc)
+                .ok_or_else(|| anyhow!("{} is not in the manifest", self.doc.display())),
+            Kind::Document(_) => Err(anyhow!("{} keeps its entries itself", self.doc.display())),
+        }
+    }
+}
+
+fn new_entry(pattern: &str, hash: &str) -> WatchEntry {
+    WatchEntry {
+        pattern: pattern.to_string(),
+        hash: Some(hash.to_string()).filter(|h| !h.is_empty()),
+    }
+}
@@ -0,0 +1,237 @@
+use anyhow::{anyhow, Result};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher as _};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Editors often write a file in several steps; wait this long after the
+/// first event so they arrive as one batch
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Directories to watch, and whether their subdirectories are watched too
+pub type WatchSet = BTreeMap<PathBuf, RecursiveMode>;
+
+/// What changed since the last wait
+pub enum Changes {
+    Paths(BTreeSet<PathBuf>),
+    /// Events were lost (e.g. the kernel queue overflowed), so anything may
+    /// have changed
+    Rescan,
+}
+
+/// Blocks until something changes in a set of watched directories.
+///
+/// Uses the platform's file events (inotify on Linux) and falls back to
+/// polling file metadata when they are unavailable (or polling was requested).
+pub enum Watcher {
+    Native {
+        watcher: RecommendedWatcher,
+        events: Receiver<notify::Result<notify::Event>>,
+        watched: WatchSet,
+    },
+    Polling {
+        interval: Duration,
+        dirs: WatchSet,
+        snapshot: Snapshot,
+    },
+}
+
+/// Modification time and size of every file in the watched directories
+pub type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+impl Watcher {
+    pub fn new(poll: bool, interval: Duration) -> Self {
+        if !poll {
+            let (sender, events) = mpsc::channel();
+            if let Ok(watcher) = notify::recommended_watcher(sender) {
+                return Watcher::Native {
+                    watcher,
+                    events,
+                    watched: WatchSet::new(),
+                };
+            }
+        }
+
+        Watcher::Polling {
+            interval,
+            dirs: WatchSet::new(),
+            snapshot: Snapshot::new(),
+        }
+    }
+
+    pub fn backend(&self) -> &'static str {
+        match self {
+            Watcher::Native { .. } if cfg!(target_os = "linux") => "inotify",
+            Watcher::Native { .. } => "native",
+            Watcher::Polling { .. } => "polling",
+        }
+    }
+
+    /// Replace the set of watched directories
+    pub fn watch(&mut self, new_dirs: &WatchSet) -> Result<()> {
+        match self {
+            Watcher::Native {
+                watcher, watched, ..
+            } => {
+                let stale: Vec<PathBuf> = watched
+                    .iter()
+                    .filter(|(dir, mode)| new_dirs.get(*dir) != Some(mode))
+                    .map(|(dir, _)| dir.clone())
+                    .collect();
+                for dir in stale {
+                    // The watch is already gone if the directory was removed
+                    let _ = watcher.unwatch(&dir);
+                    watched.remove(&dir);
+                }
+                for (dir, mode) in new_dirs {
+                    // Directories that don't exist (yet) are watched from
+                    // their parent
+                    if !watched.contains_key(dir) && watcher.watch(dir, *mode).is_ok() {
+                        watched.insert(dir.clone(), *mode);
+                    }
+                }
+                Ok(())
+            }
+            Watcher::Polling { dirs, snapshot, .. } => {
+                *dirs = new_dirs.clone();
+                *snapshot = take_snapshot(dirs);
+                Ok(())
+            }
+        }
+    }
+
+    /// Wait for the next batch of changes
+    pub fn wait(&mut self) -> Result<Changes> {
+        match self {
+            Watcher::Native {
+                events, watched, ..
+            } => {
+                let first = events.recv().map_err(|_| anyhow!("file watcher stopped"))?;
+                thread::sleep(DEBOUNCE);
+
+                let mut changed = BTreeSet::new();
+                let mut rescan = false;
+                for result in std::iter::once(first).chain(events.try_iter()) {
+                    match result {
+                        Ok(event) if event.need_rescan() => rescan = true,
+                        // Reading files (including our own hashing) is not a change
+                        Ok(event) if event.kind.is_access() => {}
+                        Ok(event) => changed.extend(event.paths),
+                        Err(e) => {
+                            eprintln!("Warning: file watcher: {}", e);
+                            rescan = true;
+                        }
+                    }
+                }
+
+                // Removed directories lose their watch, so forget them to
+                // watch them again if they come back
+                watched.retain(|dir, _| !changed.contains(dir) || dir.is_dir());
+
+                Ok(if rescan {
+                    Changes::Rescan
+                } else {
+                    Changes::Paths(changed)
+                })
+            }
+            Watcher::Polling {
+                interval,
+                dirs,
+                snapshot,
+            } => loop {
+                thread::sleep(*interval);
+                let next = take_snapshot(dirs);
+                let changed = diff_snapshots(snapshot, &next);
+                *snapshot = next;
+                if !changed.is_empty() {
+                    return Ok(Changes::Paths(changed));
+                }
+            },
+        }
+    }
+}
+
+fn take_snapshot(dirs: &WatchSet) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for (dir, mode) in dirs {
+        add_to_snapshot(&mut snapshot, dir, *mode);
+    }
+    snapshot
+}
+
+fn add_to_snapshot(snapshot: &mut Snapshot, dir: &Path, mode: RecursiveMode) {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_dir() && mode == RecursiveMode::Recursive {
+            add_to_snapshot(snapshot, &entry.path(), mode);
+        }
+        snapshot.insert(entry.path(), (meta.modified().ok(), meta.len()));
+    }
+}
+
+/// Paths added, removed or modified between two snapshots
+fn diff_snapshots(old: &Snapshot, new: &Snapshot) -> BTreeSet<PathBuf> {
+    let mut changed: BTreeSet<PathBuf> = old
+        .iter()
+        .filter(|(path, meta)| new.get(*path) != Some(meta))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(new.keys().filter(|path| !old.contains_key(*path)).cloned());
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_snapshots() {
+        let time = Some(SystemTime::UNIX_EPOCH);
+        let old: Snapshot = [
+            (PathBuf::from("same"), (time, 1)),
+            (PathBuf::from("changed"), (time, 1)),
+            (PathBuf::from("removed"), (time, 1)),
+        ]
+        .into_iter()
+        .collect();
+        let new: Snapshot = [
+            (PathBuf::from("same"), (time, 1)),
+            (PathBuf::from("changed"), (time, 2)),
+            (PathBuf::from("added"), (time, 1)),
+        ]
+        .into_iter()
+        .collect();
+
+        let changed: Vec<_> = diff_snapshots(&old, &new).into_iter().collect();
+        assert_eq!(
+            changed,
+            vec![
+                PathBuf::from("added"),
+                PathBuf::from("changed"),
+                PathBuf::from("removed")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_polling_snapshot_sees_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("a.txt"), b"a").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/b.txt"), b"b").unwrap();
+
+        let flat: WatchSet = [(dir.path().to_path_buf(), RecursiveMode::NonRecursive)].into();
+        assert_eq!(take_snapshot(&flat).len(), 2);
+        let tree: WatchSet = [(dir.path().to_path_buf(), RecursiveMode::Recursive)].into();
+        assert!(take_snapshot(&tree).contains_key(&dir.path().join("sub/b.txt")));
+    }
+}